@@ -0,0 +1,64 @@
+use ckb_core::{
+    block::BlockBuilder,
+    transaction::{CellOutPoint, CellOutput, TransactionBuilder},
+};
+use ckb_db::{DBConfig, RocksDB};
+use ckb_store::{ChainKVStore, ChainStore, StoreBatch, COLUMNS};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+// The number of inputs of the verified transaction
+const INPUTS: u32 = 20;
+
+fn bench(c: &mut Criterion) {
+    let tmp_dir = tempfile::Builder::new().tempdir().unwrap();
+    let config = DBConfig {
+        path: tmp_dir.as_ref().to_path_buf(),
+        ..Default::default()
+    };
+
+    let out_points: Vec<CellOutPoint> = {
+        let db = RocksDB::open(&config, COLUMNS);
+        let store = ChainKVStore::new(db);
+
+        let tx = TransactionBuilder::default()
+            .outputs(vec![CellOutput::default(); INPUTS as usize])
+            .build();
+        let out_points = (0..INPUTS)
+            .map(|index| CellOutPoint {
+                tx_hash: tx.hash().to_owned(),
+                index,
+            })
+            .collect();
+        let block = BlockBuilder::default().transaction(tx).build();
+
+        let mut batch = store.new_batch().unwrap();
+        batch.insert_block(&block).unwrap();
+        batch.attach_block(&block).unwrap();
+        batch.commit().unwrap();
+        out_points
+    };
+
+    {
+        let db = RocksDB::open(&config, COLUMNS);
+        let store = ChainKVStore::new(db);
+        let out_points = out_points.clone();
+        c.bench_function("get_cell_meta_20_inputs", move |b| {
+            b.iter(|| {
+                for out_point in &out_points {
+                    let _ = store
+                        .get_cell_meta(&out_point.tx_hash, out_point.index)
+                        .unwrap();
+                }
+            })
+        });
+    }
+
+    let db = RocksDB::open(&config, COLUMNS);
+    let store = ChainKVStore::new(db);
+    c.bench_function("get_cell_metas_batch_20_inputs", move |b| {
+        b.iter(|| store.get_cell_metas_batch(&out_points))
+    });
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
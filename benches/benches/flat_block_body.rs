@@ -0,0 +1,130 @@
+use ckb_core::{
+    script::Script,
+    transaction::{CellInput, CellOutput, OutPoint, Transaction, TransactionBuilder},
+    Bytes, Capacity,
+};
+use ckb_store::{
+    decode_delta_block_body, decode_flat_block_body, encode_delta_block_body,
+    encode_flat_block_body_compressed, Compression,
+};
+use criterion::{criterion_group, criterion_main, Benchmark, Criterion, Throughput};
+use numext_fixed_hash::H256;
+use rand::{thread_rng, Rng};
+
+const BLOCK_BODY_SIZE: usize = 1024 * 1024;
+const OUTPUT_DATA_SIZE: usize = 1024;
+const PAYMENT_TRANSACTIONS: usize = 1_000;
+
+// Each output carries half random bytes and half repeated bytes, which is
+// closer to real cell data than either extreme.
+fn synthetic_transactions() -> Vec<Transaction> {
+    let mut rng = thread_rng();
+    (0..BLOCK_BODY_SIZE / OUTPUT_DATA_SIZE)
+        .map(|i| {
+            let mut data = vec![i as u8; OUTPUT_DATA_SIZE];
+            rng.fill(&mut data[..OUTPUT_DATA_SIZE / 2]);
+            TransactionBuilder::default()
+                .output(CellOutput::new(
+                    Capacity::bytes(OUTPUT_DATA_SIZE).unwrap(),
+                    Bytes::from(data),
+                    Script::default(),
+                    None,
+                ))
+                .build()
+        })
+        .collect()
+}
+
+// A batch of payments sent from one wallet: every transaction pays a token
+// to a random recipient and returns the change to the sender.
+fn payment_transactions() -> Vec<Transaction> {
+    let mut rng = thread_rng();
+    let lock = |args: [u8; 20]| Script::new(vec![Bytes::from(&args[..])], H256::zero());
+    let sender = lock(rng.gen());
+    let token = Some(Script::new(
+        vec![Bytes::from(&rng.gen::<[u8; 32]>()[..])],
+        H256::zero(),
+    ));
+    (0..PAYMENT_TRANSACTIONS)
+        .map(|i| {
+            TransactionBuilder::default()
+                .input(CellInput::new(
+                    OutPoint::new_cell(H256::from_slice(&rng.gen::<[u8; 32]>()).unwrap(), 1),
+                    i as u64,
+                ))
+                .output(CellOutput::new(
+                    Capacity::bytes(200).unwrap(),
+                    Bytes::from(&rng.gen::<[u8; 16]>()[..]),
+                    lock(rng.gen()),
+                    token.clone(),
+                ))
+                .output(CellOutput::new(
+                    Capacity::shannons(rng.gen()),
+                    Bytes::default(),
+                    sender.clone(),
+                    None,
+                ))
+                .witness(vec![Bytes::from(&rng.gen::<[u8; 32]>()[..])])
+                .build()
+        })
+        .collect()
+}
+
+fn bench_delta(c: &mut Criterion) {
+    let txs = payment_transactions();
+    let flat = encode_flat_block_body_compressed(&txs, Compression::None);
+    let delta = encode_delta_block_body(&txs);
+    println!(
+        "payment block body: {} bytes flat, {} bytes delta encoded, ratio {:.3}",
+        flat.len(),
+        delta.len(),
+        delta.len() as f64 / flat.len() as f64
+    );
+
+    let encode_txs = txs.clone();
+    c.bench(
+        "delta_block_body",
+        Benchmark::new("encode", move |b| {
+            b.iter(|| encode_delta_block_body(&encode_txs))
+        })
+        .with_function("decode", move |b| {
+            b.iter(|| decode_delta_block_body(&delta).unwrap().len())
+        })
+        .throughput(Throughput::Bytes(flat.len() as u32)),
+    );
+}
+
+fn bench(c: &mut Criterion) {
+    let txs = synthetic_transactions();
+    let uncompressed = encode_flat_block_body_compressed(&txs, Compression::None);
+    let compressed = encode_flat_block_body_compressed(&txs, Compression::Lz4);
+    println!(
+        "flat block body: {} bytes uncompressed, {} bytes with lz4, ratio {:.3}",
+        uncompressed.len(),
+        compressed.len(),
+        compressed.len() as f64 / uncompressed.len() as f64
+    );
+
+    for compression in &[Compression::None, Compression::Lz4] {
+        let compression = *compression;
+        let txs = txs.clone();
+        let encoded = encode_flat_block_body_compressed(&txs, compression);
+        c.bench(
+            "flat_block_body",
+            Benchmark::new(format!("encode_{:?}", compression), move |b| {
+                b.iter(|| encode_flat_block_body_compressed(&txs, compression))
+            })
+            .with_function(format!("decode_{:?}", compression), move |b| {
+                b.iter(|| decode_flat_block_body(&encoded).unwrap().len())
+            })
+            .throughput(Throughput::Bytes(uncompressed.len() as u32)),
+        );
+    }
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = bench, bench_delta
+}
+criterion_main!(benches);
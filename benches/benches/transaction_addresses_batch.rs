@@ -0,0 +1,50 @@
+use ckb_core::{
+    block::BlockBuilder,
+    transaction::{CellInput, OutPoint, TransactionBuilder},
+};
+use ckb_db::{DBConfig, RocksDB};
+use ckb_store::{ChainKVStore, ChainStore, StoreBatch, COLUMNS};
+use criterion::{criterion_group, criterion_main, Criterion};
+use numext_fixed_hash::H256;
+
+fn bench(c: &mut Criterion) {
+    let tmp_dir = tempfile::Builder::new().tempdir().unwrap();
+    let config = DBConfig {
+        path: tmp_dir.as_ref().to_path_buf(),
+        ..Default::default()
+    };
+
+    let hashes: Vec<H256> = {
+        let db = RocksDB::open(&config, COLUMNS);
+        let store = ChainKVStore::new(db);
+
+        let txs: Vec<_> = (0..1000)
+            .map(|since| {
+                TransactionBuilder::default()
+                    .input(CellInput::new(OutPoint::null(), since))
+                    .build()
+            })
+            .collect();
+        let hashes = txs.iter().map(|tx| tx.hash().to_owned()).collect();
+        let block = BlockBuilder::default().transactions(txs).build();
+
+        let mut batch = store.new_batch().unwrap();
+        batch.insert_block(&block).unwrap();
+        batch.attach_block(&block).unwrap();
+        batch.commit().unwrap();
+        hashes
+    };
+
+    for size in &[1, 10, 100, 1000] {
+        let db = RocksDB::open(&config, COLUMNS);
+        let store = ChainKVStore::new(db);
+        let hashes = hashes[..*size].to_vec();
+        let name = format!("get_transaction_addresses_batch_{}", size);
+        c.bench_function(&name, move |b| {
+            b.iter(|| store.get_transaction_addresses_batch(&hashes))
+        });
+    }
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
@@ -24,6 +24,7 @@ use fnv::{FnvHashMap, FnvHashSet};
 use lru_cache::LruCache;
 use numext_fixed_hash::H256;
 use numext_fixed_uint::U256;
+use reward_calculator::RewardCalculator;
 use serde_derive::{Deserialize, Serialize};
 use std::collections::VecDeque;
 use std::sync::Arc;
@@ -282,7 +283,9 @@ impl<CS: ChainStore + 'static> ChainService<CS> {
             parent_header.number(),
             &parent_header_epoch,
             &parent_ext.dao_stats,
-            self.shared.consensus().secondary_epoch_reward(),
+            self.shared
+                .consensus()
+                .secondary_epoch_reward_at(parent_header_epoch.number()),
         )?;
 
         let ext = BlockExt {
@@ -295,6 +298,10 @@ impl<CS: ChainStore + 'static> ChainService<CS> {
                 accumulated_rate: ar,
                 accumulated_capacity: c.as_u64(),
             },
+            size: block.serialized_size(self.shared.consensus().pow_engine().proof_size()) as u32,
+            reward_components: RewardCalculator::new(&self.shared)
+                .block_reward_components(&parent_header)
+                .ok(),
         };
 
         batch.insert_block_epoch_index(
@@ -399,16 +406,34 @@ impl<CS: ChainStore + 'static> ChainService<CS> {
         detached_blocks: impl Iterator<Item = &'a Block>,
         attached_blocks: impl Iterator<Item = &'a Block>,
     ) -> Result<(), FailureError> {
+        let mut counts = self.shared.store().get_total_counts();
         for block in detached_blocks {
             batch.detach_block(block)?;
+            counts.detach(block);
+            if self.is_epoch_start(block) {
+                batch.delete_epoch_start(block.header().epoch())?;
+            }
         }
 
         for block in attached_blocks {
             batch.attach_block(block)?;
+            counts.attach(block);
+            if self.is_epoch_start(block) {
+                batch.insert_epoch_start(block.header().epoch(), block.header().hash())?;
+            }
         }
+        batch.insert_total_counts(&counts)?;
         Ok(())
     }
 
+    // The parent of an attached or detached block is always stored already
+    fn is_epoch_start(&self, block: &Block) -> bool {
+        self.shared
+            .store()
+            .get_block_header(block.header().parent_hash())
+            .map_or(false, |parent| parent.epoch() != block.header().epoch())
+    }
+
     fn alignment_fork(
         &self,
         fork: &mut ForkChanges,
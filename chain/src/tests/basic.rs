@@ -1,5 +1,6 @@
 use crate::tests::util::{
-    create_transaction, create_transaction_with_out_point, gen_block, start_chain, MockChain,
+    create_cellbase, create_transaction, create_transaction_with_out_point, gen_block, start_chain,
+    MockChain,
 };
 use ckb_chain_spec::consensus::Consensus;
 use ckb_core::block::{Block, BlockBuilder};
@@ -11,8 +12,11 @@ use ckb_core::{capacity_bytes, Bytes, Capacity};
 use ckb_shared::error::SharedError;
 use ckb_store::ChainStore;
 use ckb_traits::ChainProvider;
+use numext_fixed_hash::H256;
 use numext_fixed_uint::U256;
+use std::collections::HashMap;
 use std::sync::Arc;
+use test_chain_utils::create_always_success_cell;
 
 #[test]
 fn test_genesis_transaction_spend() {
@@ -645,3 +649,337 @@ fn test_next_epoch_ext() {
         assert_eq!(epoch.difficulty(), &U256::from(2000u64));
     }
 }
+
+#[test]
+fn test_epoch_start_block() {
+    let genesis_block = BlockBuilder::default()
+        .header_builder(HeaderBuilder::default().difficulty(U256::from(1000u64)))
+        .build();
+    let mut consensus = Consensus::default().set_genesis_block(genesis_block);
+    consensus.genesis_epoch_ext.set_length(10);
+
+    let (chain_controller, shared, genesis) = start_chain(Some(consensus.clone()));
+    let mut epoch_starts = vec![genesis.clone()];
+    let mut parent = genesis;
+    let mut last_epoch = consensus.genesis_epoch_ext.clone();
+    // build a chain spanning 3 epochs
+    while last_epoch.number() < 2 {
+        let epoch = shared
+            .next_epoch_ext(&last_epoch, &parent)
+            .unwrap_or(last_epoch);
+        let number = parent.number() + 1;
+        let new_block = BlockBuilder::default()
+            .transaction(create_cellbase(number))
+            .header_builder(
+                HeaderBuilder::default()
+                    .parent_hash(parent.hash().to_owned())
+                    .timestamp(parent.timestamp() + 20_000)
+                    .number(number)
+                    .epoch(epoch.number())
+                    .difficulty(epoch.difficulty().clone()),
+            )
+            .build();
+        chain_controller
+            .process_block(Arc::new(new_block.clone()), false)
+            .expect("process block ok");
+        if epoch.number() != parent.epoch() {
+            epoch_starts.push(new_block.header().clone());
+        }
+        parent = new_block.header().clone();
+        last_epoch = epoch;
+    }
+
+    assert_eq!(epoch_starts.len(), 3);
+    assert_eq!(epoch_starts[1].number(), 10);
+    for (epoch_number, header) in epoch_starts.iter().enumerate() {
+        assert_eq!(
+            shared.store().get_epoch_start_block(epoch_number as u64),
+            Some(header.clone())
+        );
+    }
+    assert_eq!(shared.store().get_epoch_start_block(3), None);
+    assert_eq!(shared.store().get_current_epoch(&parent), 2);
+}
+
+#[test]
+fn test_delete_blocks_after() {
+    let tx = TransactionBuilder::default()
+        .input(CellInput::new(OutPoint::null(), 0))
+        .output(CellOutput::new(
+            capacity_bytes!(100_000_000),
+            Bytes::default(),
+            Script::default(),
+            None,
+        ))
+        .build();
+
+    let mut root_hash = tx.hash().to_owned();
+    let genesis_block = BlockBuilder::default()
+        .transaction(tx)
+        .header_builder(HeaderBuilder::default().difficulty(U256::from(1000u64)))
+        .build();
+
+    let mut consensus = Consensus::default().set_genesis_block(genesis_block);
+    consensus.genesis_epoch_ext.set_length(10);
+    let (chain_controller, shared, parent) = start_chain(Some(consensus));
+
+    // every block spends the output created in its parent, block 10 starts epoch 1
+    let mut chain = MockChain::new(parent.clone());
+    let mut tx_hashes = vec![root_hash.clone()];
+    for i in 1..=20 {
+        let tx = create_transaction(&root_hash, i as u8);
+        root_hash = tx.hash().to_owned();
+        tx_hashes.push(root_hash.clone());
+        chain.gen_block_with_commit_txs(vec![tx]);
+    }
+    for block in chain.blocks() {
+        chain_controller
+            .process_block(Arc::new(block.clone()), false)
+            .expect("process block ok");
+    }
+
+    let store = shared.store();
+    assert_eq!(store.get_current_epoch_ext().unwrap().number(), 1);
+    assert_eq!(store.delete_blocks_after(20), Ok(0));
+    assert_eq!(store.delete_blocks_after(5), Ok(15));
+    assert_eq!(store.delete_blocks_after(5), Ok(0));
+
+    let tip = store.get_tip_header().unwrap();
+    assert_eq!(&tip, chain.blocks()[4].header());
+    assert_eq!(store.get_block_hash(6), None);
+    for block in &chain.blocks()[5..] {
+        let hash = block.header().hash();
+        assert_eq!(store.get_block_header(hash), None);
+        assert_eq!(store.get_block_number(hash), None);
+        assert_eq!(store.get_block_ext(hash), None);
+        assert_eq!(store.get_block_epoch_index(hash), None);
+        assert_eq!(store.get_epoch_ext(hash), None);
+    }
+    for block in &chain.blocks()[..5] {
+        assert_eq!(store.get_block_epoch_number(block.header().hash()), Some(0));
+    }
+    assert_eq!(store.get_epoch_index(1), None);
+    assert_eq!(store.get_epoch_start_block(1), None);
+    assert_eq!(store.get_current_epoch_ext().unwrap().number(), 0);
+
+    // the output of block 5 is live again, the later transactions are gone
+    let mut cell_set = HashMap::new();
+    store
+        .traverse_cell_set(|tx_hash, meta| {
+            cell_set.insert(tx_hash, meta);
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(cell_set[&tx_hashes[5]].is_dead(0), Some(false));
+    assert!(!cell_set.contains_key(&tx_hashes[4]));
+    for tx_hash in &tx_hashes[6..] {
+        assert!(!cell_set.contains_key(tx_hash));
+        assert!(store.get_transaction(tx_hash).is_none());
+    }
+    assert!(store.get_transaction(&tx_hashes[5]).is_some());
+}
+
+#[test]
+fn test_get_cells_by_script() {
+    let (chain_controller, shared, parent) = start_chain(None);
+    let (_, always_success_script) = create_always_success_cell();
+    let script_hash = always_success_script.hash();
+
+    let mut chain = MockChain::new(parent.clone());
+    for _ in 0..5 {
+        chain.gen_empty_block(100u64);
+    }
+    for block in chain.blocks() {
+        chain_controller
+            .process_block(Arc::new(block.clone()), false)
+            .expect("process block ok");
+    }
+
+    // the genesis cell is locked by the default script, every cellbase by always success
+    let cellbase_out_points: Vec<_> = chain
+        .blocks()
+        .iter()
+        .map(|block| CellOutPoint {
+            tx_hash: block.transactions()[0].hash().to_owned(),
+            index: 0,
+        })
+        .collect();
+    let store = shared.store();
+    assert_eq!(
+        store.get_cells_by_script(&script_hash, 0, 10),
+        cellbase_out_points
+    );
+    assert_eq!(
+        store.get_cells_by_script(&script_hash, 1, 2),
+        cellbase_out_points[1..3].to_vec()
+    );
+    assert!(store.get_cells_by_script(&script_hash, 5, 10).is_empty());
+
+    // the index follows the main chain when blocks are detached
+    assert_eq!(store.delete_blocks_after(3), Ok(2));
+    assert_eq!(
+        store.get_cells_by_script(&script_hash, 0, 10),
+        cellbase_out_points[..3].to_vec()
+    );
+}
+
+#[test]
+fn test_get_live_cell_with_status() {
+    let genesis_output = CellOutput::new(
+        capacity_bytes!(100_000_000),
+        Bytes::default(),
+        Script::default(),
+        None,
+    );
+    let tx = TransactionBuilder::default()
+        .input(CellInput::new(OutPoint::null(), 0))
+        .outputs(vec![genesis_output.clone(); 2])
+        .build();
+    let genesis_tx_hash = tx.hash().to_owned();
+    let genesis_block = BlockBuilder::default()
+        .transaction(tx)
+        .header_builder(HeaderBuilder::default().difficulty(U256::from(1000u64)))
+        .build();
+
+    let consensus = Consensus::default().set_genesis_block(genesis_block);
+    let (chain_controller, shared, parent) = start_chain(Some(consensus));
+    let mut chain = MockChain::new(parent.clone());
+    chain.gen_block_with_commit_txs(vec![create_transaction(&genesis_tx_hash, 1)]);
+    chain_controller
+        .process_block(Arc::new(chain.tip().clone()), false)
+        .expect("process block ok");
+
+    let live = shared.get_live_cell_with_status(&OutPoint::new_cell(genesis_tx_hash.clone(), 1));
+    assert!(live.status.is_live());
+    assert_eq!(live.cell, Some(genesis_output));
+
+    let spent = shared.get_live_cell_with_status(&OutPoint::new_cell(genesis_tx_hash.clone(), 0));
+    assert_eq!(spent.status, CellStatus::Dead);
+    assert_eq!(spent.cell, None);
+
+    let unknown = shared.get_live_cell_with_status(&OutPoint::new_cell(H256::zero(), 0));
+    assert_eq!(unknown.status, CellStatus::Unknown);
+    assert_eq!(unknown.cell, None);
+}
+
+#[test]
+fn test_get_transactions_by_block() {
+    let (chain_controller, shared, parent) = start_chain(None);
+    let genesis_tx_hash = shared.consensus().genesis_block().transactions()[0]
+        .hash()
+        .to_owned();
+    let mut chain = MockChain::new(parent.clone());
+    let tx = create_transaction(&genesis_tx_hash, 1);
+    chain.gen_block_with_commit_txs(vec![tx.clone()]);
+    chain_controller
+        .process_block(Arc::new(chain.tip().clone()), false)
+        .expect("process block ok");
+
+    let block = chain.tip();
+    let transactions = shared
+        .get_transactions_by_block(block.header().hash())
+        .unwrap();
+    assert_eq!(transactions.len(), 2);
+    assert_eq!(transactions[0].0, 0);
+    assert!(transactions[0].1.is_cellbase());
+    assert_eq!(transactions[1], (1, tx.clone()));
+
+    let cellbase = &block.transactions()[0];
+    assert_eq!(
+        shared.get_transaction_index_in_block(cellbase.hash()),
+        Some(0)
+    );
+    assert_eq!(shared.get_transaction_index_in_block(tx.hash()), Some(1));
+    assert_eq!(shared.get_transaction_index_in_block(&H256::zero()), None);
+    assert!(shared.get_transactions_by_block(&H256::zero()).is_none());
+}
+
+#[test]
+fn test_total_counts() {
+    let (chain_controller, shared, parent) = start_chain(None);
+    let store = shared.store();
+    let genesis = shared.consensus().genesis_block();
+    assert_eq!(
+        store.get_total_transactions_count(),
+        genesis.transactions().len() as u64
+    );
+    let initial = store.get_total_counts();
+
+    let mut chain = MockChain::new(parent.clone());
+    let tx = create_transaction(genesis.transactions()[0].hash(), 1);
+    chain.gen_block_with_commit_txs(vec![tx]);
+    for _ in 1..10 {
+        chain.gen_empty_block(100u64);
+    }
+    for block in chain.blocks() {
+        chain_controller
+            .process_block(Arc::new(block.clone()), false)
+            .expect("process block ok");
+    }
+
+    let cellbase_outputs: u64 = chain
+        .blocks()
+        .iter()
+        .map(|block| block.transactions()[0].outputs().len() as u64)
+        .sum();
+    assert_eq!(
+        store.get_total_transactions_count(),
+        initial.transactions + 11
+    );
+    assert_eq!(
+        store.get_total_cells_created_count(),
+        initial.cells_created + cellbase_outputs + 1
+    );
+    assert_eq!(
+        store.get_total_cells_consumed_count(),
+        initial.cells_consumed + 1
+    );
+}
+
+#[test]
+fn test_block_size() {
+    let (chain_controller, shared, parent) = start_chain(None);
+    let proof_size = shared.consensus().pow_engine().proof_size();
+    let genesis = shared.consensus().genesis_block();
+
+    let mut chain = MockChain::new(parent.clone());
+    let tx = create_transaction(genesis.transactions()[0].hash(), 1);
+    chain.gen_block_with_commit_txs(vec![tx]);
+    chain.gen_empty_block(100u64);
+    for block in chain.blocks() {
+        chain_controller
+            .process_block(Arc::new(block.clone()), false)
+            .expect("process block ok");
+    }
+
+    for block in chain.blocks().iter().chain(Some(genesis)) {
+        assert_eq!(
+            shared.get_block_size(block.header().hash()),
+            Some(block.serialized_size(proof_size))
+        );
+    }
+}
+
+#[test]
+fn test_tip_number_and_hash() {
+    let (chain_controller, shared, parent) = start_chain(None);
+    assert_eq!(shared.tip_number(), 0);
+    assert_eq!(&shared.tip_hash(), parent.hash());
+
+    let mut chain = MockChain::new(parent.clone());
+    for _ in 0..3 {
+        chain.gen_empty_block(100u64);
+    }
+    for block in chain.blocks() {
+        chain_controller
+            .process_block(Arc::new(block.clone()), false)
+            .expect("process block ok");
+    }
+
+    let chain_state = shared.lock_chain_state();
+    let (tip_number, tip_hash) = (chain_state.tip_number(), chain_state.tip_hash().to_owned());
+    drop(chain_state);
+    assert_eq!(shared.tip_number(), tip_number);
+    assert_eq!(shared.tip_hash(), tip_hash);
+    assert_eq!(&tip_hash, chain.tip_header().hash());
+}
@@ -70,6 +70,8 @@ fn test_find_fork_case1() {
             accumulated_rate: DEFAULT_ACCUMULATED_RATE,
             accumulated_capacity: 0,
         },
+        size: 0,
+        reward_components: None,
     };
 
     let mut fork = ForkChanges::default();
@@ -144,6 +146,8 @@ fn test_find_fork_case2() {
             accumulated_rate: DEFAULT_ACCUMULATED_RATE,
             accumulated_capacity: 0,
         },
+        size: 0,
+        reward_components: None,
     };
 
     let mut fork = ForkChanges::default();
@@ -219,6 +223,8 @@ fn test_find_fork_case3() {
             accumulated_rate: DEFAULT_ACCUMULATED_RATE,
             accumulated_capacity: 0,
         },
+        size: 0,
+        reward_components: None,
     };
     let mut fork = ForkChanges::default();
 
@@ -293,6 +299,8 @@ fn test_find_fork_case4() {
             accumulated_rate: DEFAULT_ACCUMULATED_RATE,
             accumulated_capacity: 0,
         },
+        size: 0,
+        reward_components: None,
     };
 
     let mut fork = ForkChanges::default();
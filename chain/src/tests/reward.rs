@@ -8,7 +8,9 @@ use ckb_core::transaction::{
 };
 use ckb_core::uncle::UncleBlock;
 use ckb_core::{capacity_bytes, BlockNumber, Bytes, Capacity};
+use ckb_store::ChainStore;
 use ckb_traits::ChainProvider;
+use occupied_capacity::Ratio;
 use std::sync::Arc;
 use test_chain_utils::create_always_success_cell;
 
@@ -194,6 +196,34 @@ fn finalize_reward() {
         .process_block(Arc::new(block.clone()), true)
         .expect("process block ok");
 
+    // the components of the reward paid to bob add up to his cellbase output
+    let components = shared
+        .get_block_reward_components(block.header().hash())
+        .unwrap();
+    assert_eq!(components.base, BLOCK_REWARD);
+    assert_eq!(components.uncles, Capacity::zero());
+    assert_eq!(components.txs_fees, Capacity::zero());
+    assert_eq!(
+        components
+            .base
+            .safe_add(components.proposals)
+            .and_then(|sum| sum.safe_add(components.uncles))
+            .and_then(|sum| sum.safe_add(components.txs_fees))
+            .unwrap(),
+        block.transactions()[0].outputs()[0].capacity
+    );
+    assert_eq!(
+        shared
+            .store()
+            .get_block_ext(block.header().hash())
+            .unwrap()
+            .reward_components,
+        Some(components)
+    );
+    assert!(shared
+        .get_block_reward_components(blocks[0].header().hash())
+        .is_none());
+
     let (target, reward) = shared.finalize_block_reward(block.header()).unwrap();
     assert_eq!(target, alice);
 
@@ -223,3 +253,106 @@ fn finalize_reward() {
         .process_block(Arc::new(block.clone()), true)
         .expect("process block ok");
 }
+
+// alice includes an uncle mined by bob in block 2, which is finalized in block 13
+fn finalize_reward_with_uncle(consensus: Consensus) -> Capacity {
+    let (_, always_success_script) = create_always_success_cell();
+    let (chain_controller, shared, mut parent) = start_chain(Some(consensus));
+
+    let bob = Script {
+        args: vec![Bytes::from(b"b0b".to_vec())],
+        code_hash: always_success_script.code_hash.clone(),
+    };
+
+    let alice = Script {
+        args: vec![Bytes::from(b"a11ce".to_vec())],
+        code_hash: always_success_script.code_hash.clone(),
+    };
+
+    let uncle = gen_block(
+        &parent,
+        vec![],
+        vec![],
+        vec![],
+        bob.clone(),
+        always_success_script.clone(),
+        None,
+    );
+
+    for i in 1..13 {
+        let (uncles, miner_lock) = if i == 2 {
+            (vec![uncle.clone().into()], alice.clone())
+        } else {
+            (vec![], always_success_script.clone())
+        };
+
+        let block = gen_block(
+            &parent,
+            vec![],
+            vec![],
+            uncles,
+            miner_lock,
+            always_success_script.clone(),
+            None,
+        );
+
+        parent = block.header().clone();
+
+        chain_controller
+            .process_block(Arc::new(block), true)
+            .expect("process block ok");
+    }
+
+    let (target, reward) = shared.finalize_block_reward(&parent).unwrap();
+    assert_eq!(target, alice);
+
+    let block = gen_block(
+        &parent,
+        vec![],
+        vec![],
+        vec![],
+        always_success_script.clone(),
+        target,
+        Some(reward),
+    );
+
+    chain_controller
+        .process_block(Arc::new(block), true)
+        .expect("process block ok");
+
+    reward
+}
+
+#[test]
+fn finalize_reward_uncle_bonus() {
+    let consensus = Consensus::default()
+        .set_cellbase_maturity(0)
+        .set_bootstrap_lock(create_always_success_cell().1)
+        .set_genesis_block(
+            BlockBuilder::default()
+                .transaction(create_always_success_tx())
+                .build(),
+        )
+        .set_uncle_inclusion_bonus_ratio(Ratio(1, 10));
+
+    // alice gets 10% of the base block reward for the uncle
+    let reward = finalize_reward_with_uncle(consensus);
+    assert_eq!(reward, BLOCK_REWARD.safe_add(capacity_bytes!(100)).unwrap());
+}
+
+#[test]
+fn finalize_reward_capped() {
+    let consensus = Consensus::default()
+        .set_cellbase_maturity(0)
+        .set_bootstrap_lock(create_always_success_cell().1)
+        .set_genesis_block(
+            BlockBuilder::default()
+                .transaction(create_always_success_tx())
+                .build(),
+        )
+        .set_uncle_inclusion_bonus_ratio(Ratio(1, 10))
+        .set_max_block_reward(capacity_bytes!(1_050));
+
+    let reward = finalize_reward_with_uncle(consensus);
+    assert_eq!(reward, capacity_bytes!(1_050));
+}
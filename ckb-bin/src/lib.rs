@@ -26,20 +26,29 @@ pub fn run_app(version: Version) -> Result<(), ExitCode> {
                 _ => unreachable!(),
             };
         }
+        (cli::CMD_CONFIG, Some(matches)) => {
+            return match matches.subcommand() {
+                (cli::CMD_DIFF, Some(sub_matches)) => subcommand::config_diff(sub_matches),
+                _ => unreachable!(),
+            };
+        }
         _ => {
             // continue
         }
     }
 
     let setup = Setup::from_matches(&app_matches)?;
-    let _guard = setup.setup_app(&version);
+    let _guard = setup.setup_app(&version)?;
 
     match app_matches.subcommand() {
-        (cli::CMD_RUN, _) => subcommand::run(setup.run()?, version),
-        (cli::CMD_MINER, _) => subcommand::miner(setup.miner()?),
+        (cli::CMD_RUN, Some(matches)) => subcommand::run(setup.run(&matches)?, version),
+        (cli::CMD_MINER, Some(matches)) => subcommand::miner(setup.miner(&matches)?),
         (cli::CMD_PROF, Some(matches)) => subcommand::profile(setup.prof(&matches)?),
         (cli::CMD_EXPORT, Some(matches)) => subcommand::export(setup.export(&matches)?),
         (cli::CMD_IMPORT, Some(matches)) => subcommand::import(setup.import(&matches)?),
+        (cli::CMD_DB_STATS, Some(matches)) => subcommand::db_stats(setup.db_stats(&matches)?),
+        (cli::CMD_MIGRATE, _) => subcommand::migrate(setup.migrate()?),
+        (cli::CMD_VERIFY, Some(matches)) => subcommand::verify(setup.verify_chain(&matches)?),
         _ => unreachable!(),
     }
 }
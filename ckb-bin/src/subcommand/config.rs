@@ -0,0 +1,20 @@
+use ckb_app_config::{cli, AppConfig, CKBAppConfig, ExitCode};
+use clap::{value_t, ArgMatches};
+use std::fs;
+use std::path::PathBuf;
+
+pub fn config_diff<'m>(matches: &ArgMatches<'m>) -> Result<(), ExitCode> {
+    let from = value_t!(matches.value_of(cli::ARG_FROM), PathBuf)?;
+    let to = value_t!(matches.value_of(cli::ARG_TO), PathBuf)?;
+    let old: CKBAppConfig = toml::from_slice(&fs::read(from)?)?;
+    let new: CKBAppConfig = toml::from_slice(&fs::read(to)?)?;
+
+    for diff in AppConfig::diff(&old, &new) {
+        println!(
+            "{}: {} -> {}",
+            diff.field_path, diff.old_value, diff.new_value
+        );
+    }
+
+    Ok(())
+}
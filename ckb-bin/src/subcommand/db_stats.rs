@@ -0,0 +1,155 @@
+use ckb_app_config::{DbStatsArgs, ExitCode};
+use ckb_db::{Col, DBConfig, RocksDB};
+use ckb_store::{
+    ChainKVStore, ColumnSize, COLUMNS, COLUMN_BLOCK_BODY, COLUMN_BLOCK_EPOCH, COLUMN_BLOCK_HEADER,
+    COLUMN_BLOCK_PROPOSAL_IDS, COLUMN_BLOCK_TRANSACTION_ADDRESSES, COLUMN_BLOCK_UNCLE,
+    COLUMN_CELL_META, COLUMN_CELL_SET, COLUMN_EPOCH, COLUMN_EPOCH_START, COLUMN_EXT, COLUMN_INDEX,
+    COLUMN_META, COLUMN_SCRIPT_TO_CELLS, COLUMN_TRANSACTION_ADDR, COLUMN_UNCLES,
+    COLUMN_UNCLE_TO_BLOCK,
+};
+use serde_derive::Serialize;
+use std::fmt::Write;
+
+#[derive(Serialize)]
+struct ColumnStats<'a> {
+    name: &'static str,
+    #[serde(flatten)]
+    size: &'a ColumnSize,
+}
+
+pub fn db_stats(args: DbStatsArgs) -> Result<(), ExitCode> {
+    if args.repair {
+        RocksDB::repair(&args.config.db.path).map_err(|err| {
+            eprintln!("DB stats error: {:?}", err);
+            ExitCode::Failure
+        })?;
+    }
+    let sizes = column_sizes(&args.config.db)?;
+
+    if args.json {
+        let stats: Vec<_> = sizes
+            .iter()
+            .map(|size| ColumnStats {
+                name: column_name(size.column),
+                size,
+            })
+            .collect();
+        let json = serde_json::to_string_pretty(&stats).map_err(|err| {
+            eprintln!("DB stats error: {}", err);
+            ExitCode::Failure
+        })?;
+        println!("{}", json);
+    } else {
+        print!("{}", format_table(&sizes));
+    }
+
+    Ok(())
+}
+
+fn column_sizes(config: &DBConfig) -> Result<Vec<ColumnSize>, ExitCode> {
+    // RocksDB creates a new database on an empty path, which is not expected for diagnostics.
+    if !config.path.join("CURRENT").exists() {
+        eprintln!("DB stats error: no database in {}", config.path.display());
+        return Err(ExitCode::Config);
+    }
+
+    let store = ChainKVStore::new(RocksDB::open(config, COLUMNS));
+    store.estimate_column_sizes().map_err(|err| {
+        eprintln!("DB stats error: {:?}", err);
+        ExitCode::Failure
+    })
+}
+
+fn column_name(column: Col) -> &'static str {
+    match column {
+        COLUMN_INDEX => "index",
+        COLUMN_BLOCK_HEADER => "block_header",
+        COLUMN_BLOCK_BODY => "block_body",
+        COLUMN_BLOCK_UNCLE => "block_uncle",
+        COLUMN_META => "meta",
+        COLUMN_TRANSACTION_ADDR => "transaction_addr",
+        COLUMN_EXT => "ext",
+        COLUMN_BLOCK_TRANSACTION_ADDRESSES => "block_transaction_addresses",
+        COLUMN_BLOCK_PROPOSAL_IDS => "block_proposal_ids",
+        COLUMN_CELL_META => "cell_meta",
+        COLUMN_BLOCK_EPOCH => "block_epoch",
+        COLUMN_EPOCH => "epoch",
+        COLUMN_CELL_SET => "cell_set",
+        COLUMN_UNCLES => "uncles",
+        COLUMN_UNCLE_TO_BLOCK => "uncle_to_block",
+        COLUMN_EPOCH_START => "epoch_start",
+        COLUMN_SCRIPT_TO_CELLS => "script_to_cells",
+        _ => "unknown",
+    }
+}
+
+fn format_table(sizes: &[ColumnSize]) -> String {
+    let mut table = String::new();
+    let mut total = ColumnSize::default();
+
+    let _ = writeln!(
+        table,
+        "{:<32}{:>12}{:>16}{:>16}{:>16}",
+        "column", "keys", "key bytes", "value bytes", "total bytes"
+    );
+    for size in sizes {
+        let name = format!("{} ({})", column_name(size.column), size.column);
+        let _ = writeln!(
+            table,
+            "{:<32}{:>12}{:>16}{:>16}{:>16}",
+            name,
+            size.keys,
+            size.key_bytes,
+            size.value_bytes,
+            size.total_bytes()
+        );
+        total.keys += size.keys;
+        total.key_bytes += size.key_bytes;
+        total.value_bytes += size.value_bytes;
+    }
+    let _ = writeln!(
+        table,
+        "{:<32}{:>12}{:>16}{:>16}{:>16}",
+        "total",
+        total.keys,
+        total.key_bytes,
+        total.value_bytes,
+        total.total_bytes()
+    );
+
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_chain_spec::consensus::Consensus;
+    use ckb_store::ChainStore;
+
+    #[test]
+    fn test_column_sizes() {
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("db_stats")
+            .tempdir()
+            .unwrap();
+        let config = DBConfig {
+            path: tmp_dir.path().join("db"),
+            ..Default::default()
+        };
+        assert_eq!(column_sizes(&config), Err(ExitCode::Config));
+
+        {
+            let store = ChainKVStore::new(RocksDB::open(&config, COLUMNS));
+            store.init(&Consensus::default()).unwrap();
+        }
+
+        let sizes = column_sizes(&config).unwrap();
+        assert_eq!(sizes.len(), COLUMNS as usize);
+        assert_eq!(sizes[COLUMN_BLOCK_HEADER as usize].keys, 1);
+
+        let table = format_table(&sizes);
+        assert_eq!(table.lines().count(), COLUMNS as usize + 2);
+        assert!(table.contains("block_header (1)"));
+        assert!(table.lines().last().unwrap().starts_with("total"));
+    }
+}
@@ -1,10 +1,15 @@
-use ckb_app_config::{ExitCode, InitArgs};
+use ckb_app_config::{CKBAppConfig, ExitCode, InitArgs};
+use ckb_chain_spec::ChainSpec;
 use ckb_resource::{
-    Resource, TemplateContext, AVAILABLE_SPECS, CKB_CONFIG_FILE_NAME,
+    Resource, TemplateContext, AVAILABLE_PROFILES, AVAILABLE_SPECS, CKB_CONFIG_FILE_NAME,
     CODE_HASH_SECP256K1_BLAKE160_SIGHASH_ALL, DEFAULT_SPEC, MINER_CONFIG_FILE_NAME,
     SPEC_DEV_FILE_NAME,
 };
 use ckb_script::Runner;
+use std::fs;
+use std::path::Path;
+
+const CONSENSUS_FILE_NAME: &str = "consensus.json";
 
 pub fn init(args: InitArgs) -> Result<(), ExitCode> {
     if args.list_chains {
@@ -14,6 +19,13 @@ pub fn init(args: InitArgs) -> Result<(), ExitCode> {
         return Ok(());
     }
 
+    if let Some(profile) = &args.profile {
+        if !AVAILABLE_PROFILES.contains(&profile.as_str()) {
+            eprintln!("Unknown profile: {}", profile);
+            return Err(ExitCode::Cli);
+        }
+    }
+
     let runner = Runner::default().to_string();
     let default_hash = format!("{:#x}", CODE_HASH_SECP256K1_BLAKE160_SIGHASH_ALL);
     let block_assembler_code_hash = args.block_assembler_code_hash.as_ref().or_else(|| {
@@ -61,6 +73,7 @@ pub fn init(args: InitArgs) -> Result<(), ExitCode> {
         log_to_stdout: args.log_to_stdout,
         runner: &runner,
         block_assembler: &block_assembler,
+        profile: args.profile.as_ref().map(String::as_str),
     };
 
     let exported = Resource::exported_in(&args.root_dir);
@@ -89,5 +102,101 @@ pub fn init(args: InitArgs) -> Result<(), ExitCode> {
         Resource::bundled(SPEC_DEV_FILE_NAME.to_string()).export(&context, &args.root_dir)?;
     }
 
+    if args.export_consensus {
+        println!("create {}", CONSENSUS_FILE_NAME);
+        export_consensus(&args.root_dir)?;
+    }
+
     Ok(())
 }
+
+fn export_consensus(root_dir: &Path) -> Result<(), ExitCode> {
+    let mut config: CKBAppConfig = toml::from_slice(&Resource::ckb_config(root_dir).get()?)?;
+    config.chain.spec.absolutize(root_dir);
+    let consensus = ChainSpec::load_from(&config.chain.spec)
+        .and_then(|spec| spec.build_consensus())
+        .map_err(|err| {
+            eprintln!("Config Error: {:?}", err);
+            ExitCode::Config
+        })?;
+
+    let json = serde_json::to_string_pretty(&consensus.to_spec()).map_err(|err| {
+        eprintln!("Export consensus error: {}", err);
+        ExitCode::Failure
+    })?;
+    fs::write(root_dir.join(CONSENSUS_FILE_NAME), json)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_chain_spec::consensus::{Consensus, ConsensusSpec};
+
+    #[test]
+    fn test_export_consensus() {
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("export_consensus")
+            .tempdir()
+            .unwrap();
+        let args = InitArgs {
+            root_dir: tmp_dir.path().to_path_buf(),
+            chain: DEFAULT_SPEC.to_owned(),
+            rpc_port: "8114".to_owned(),
+            p2p_port: "8115".to_owned(),
+            log_to_file: true,
+            log_to_stdout: true,
+            list_chains: false,
+            force: false,
+            block_assembler_code_hash: None,
+            block_assembler_args: Vec::new(),
+            export_consensus: true,
+            profile: None,
+        };
+        assert_eq!(init(args), Ok(()));
+
+        let json = fs::read_to_string(tmp_dir.path().join(CONSENSUS_FILE_NAME)).unwrap();
+        let spec: ConsensusSpec = serde_json::from_str(&json).unwrap();
+        let consensus = Consensus::from_spec(&spec).unwrap();
+        let expected = ChainSpec::load_from(&Resource::file_system(
+            tmp_dir.path().join(SPEC_DEV_FILE_NAME),
+        ))
+        .unwrap()
+        .build_consensus()
+        .unwrap();
+        assert_eq!(consensus, expected);
+    }
+
+    #[test]
+    fn test_init_with_profile() {
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("init_with_profile")
+            .tempdir()
+            .unwrap();
+        let args = InitArgs {
+            root_dir: tmp_dir.path().to_path_buf(),
+            chain: DEFAULT_SPEC.to_owned(),
+            rpc_port: "8114".to_owned(),
+            p2p_port: "8115".to_owned(),
+            log_to_file: true,
+            log_to_stdout: true,
+            list_chains: false,
+            force: false,
+            block_assembler_code_hash: None,
+            block_assembler_args: Vec::new(),
+            export_consensus: false,
+            profile: Some("mainnet".to_owned()),
+        };
+        assert_eq!(init(args), Ok(()));
+
+        let content = Resource::ckb_config(tmp_dir.path()).get().unwrap();
+        let config: CKBAppConfig = toml::from_slice(&content).unwrap();
+        assert_eq!(config.store.header_cache_size, 16384);
+        assert_eq!(config.store.cell_output_cache_size, 1024);
+        assert!(config.sentry.is_enabled());
+        assert_eq!(config.rpc.listen_address, "127.0.0.1:8114");
+        // the comments of the template are kept
+        assert!(String::from_utf8_lossy(&content).contains("# Set the lock script"));
+    }
+}
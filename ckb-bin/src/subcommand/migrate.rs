@@ -0,0 +1,24 @@
+use ckb_app_config::{ExitCode, MigrateArgs};
+use ckb_db::RocksDB;
+use ckb_store::migration::MigrationRunner;
+use ckb_store::COLUMNS;
+
+pub fn migrate(args: MigrateArgs) -> Result<(), ExitCode> {
+    if args.from_version == args.to_version {
+        println!("The database is already at version {}", args.to_version);
+        return Ok(());
+    }
+
+    let db = RocksDB::open_for_migration(&args.config.db, COLUMNS)?;
+    let version = MigrationRunner::new(&args.consensus)
+        .run(&db, &args.to_version)
+        .map_err(|err| {
+            eprintln!("Migrate error: {:?}", err);
+            ExitCode::Failure
+        })?;
+    println!(
+        "Migrated the database from version {} to {}",
+        args.from_version, version
+    );
+    Ok(())
+}
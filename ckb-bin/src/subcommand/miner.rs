@@ -1,11 +1,21 @@
 use ckb_app_config::{ExitCode, MinerArgs};
-use ckb_miner::{Client, Miner, MinerConfig};
+use ckb_miner::{Client, Miner, MinerConfig, WorkerConfig};
 use crossbeam_channel::unbounded;
 use std::thread;
 
 pub fn miner(args: MinerArgs) -> Result<(), ExitCode> {
     let (new_work_tx, new_work_rx) = unbounded();
-    let MinerConfig { client, workers } = args.config;
+    let MinerConfig {
+        client,
+        mut workers,
+    } = args.config;
+    if let Some(num_workers) = args.num_workers {
+        for worker in workers.iter_mut() {
+            if let WorkerConfig::CuckooSimple(config) = worker {
+                config.threads = num_workers;
+            }
+        }
+    }
 
     let mut client = Client::new(new_work_tx, client);
     let mut miner = Miner::new(args.pow_engine, client.clone(), new_work_rx, &workers);
@@ -1,14 +1,22 @@
 pub mod cli;
+mod config;
+mod db_stats;
 mod export;
 mod import;
 mod init;
+mod migrate;
 mod miner;
 mod prof;
 mod run;
+mod verify;
 
+pub use self::config::config_diff;
+pub use self::db_stats::db_stats;
 pub use self::export::export;
 pub use self::import::import;
 pub use self::init::init;
+pub use self::migrate::migrate;
 pub use self::miner::miner;
 pub use self::prof::profile;
 pub use self::run::run;
+pub use self::verify::verify;
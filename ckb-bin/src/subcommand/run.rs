@@ -1,7 +1,8 @@
 use crate::helper::{deadlock_detection, wait_for_exit};
 use build_info::Version;
-use ckb_app_config::{ExitCode, RunArgs};
+use ckb_app_config::{CKBAppConfig, ExitCode, RunArgs, Setup};
 use ckb_chain::chain::ChainService;
+use ckb_core::BlockNumber;
 use ckb_db::RocksDB;
 use ckb_logger::info_target;
 use ckb_miner::BlockAssembler;
@@ -10,7 +11,7 @@ use ckb_network_alert::alert_relayer::AlertRelayer;
 use ckb_notify::NotifyService;
 use ckb_rpc::{RpcServer, ServiceBuilder};
 use ckb_shared::shared::{Shared, SharedBuilder};
-use ckb_store::ChainStore;
+use ckb_store::{ChainKVStore, ChainStore, COLUMNS};
 use ckb_sync::{NetTimeProtocol, NetworkProtocol, Relayer, SyncSharedState, Synchronizer};
 use ckb_traits::chain_provider::ChainProvider;
 use ckb_verification::{BlockVerifier, Verifier};
@@ -19,9 +20,34 @@ use std::sync::Arc;
 pub fn run(args: RunArgs, version: Version) -> Result<(), ExitCode> {
     deadlock_detection();
 
-    let shared = SharedBuilder::<RocksDB>::new()
+    // A dry run listens on nothing, so it may run next to a node using the same ports
+    if !args.dry_run {
+        Setup::check_port_conflicts(&args.config)?;
+    }
+
+    if args.repair {
+        RocksDB::repair(&args.config.db.path).map_err(|err| {
+            eprintln!("Run error: {:?}", err);
+            ExitCode::Failure
+        })?;
+        info_target!(
+            crate::LOG_TARGET_MAIN,
+            "repaired the database in {}",
+            args.config.db.path.display()
+        );
+    }
+
+    // Delete the blocks before the chain state is loaded from the tip
+    if let Some(keep_from) = args.delete_blocks_after {
+        delete_blocks_after(&args.config, keep_from)?;
+    }
+
+    let shared = SharedBuilder::from_existing(&args.config.db)
+        .map_err(|err| {
+            eprintln!("Run error: {:?}", err);
+            ExitCode::Failure
+        })?
         .consensus(args.consensus)
-        .db(&args.config.db)
         .tx_pool_config(args.config.tx_pool)
         .script_config(args.config.script)
         .store_config(args.config.store)
@@ -34,6 +60,10 @@ pub fn run(args: RunArgs, version: Version) -> Result<(), ExitCode> {
     // Verify genesis every time starting node
     verify_genesis(&shared)?;
 
+    if args.dry_run {
+        return check_tip(&shared);
+    }
+
     let notify = NotifyService::default().start(Some("notify"));
     let chain_service = ChainService::new(shared.clone(), notify.clone());
     let chain_controller = chain_service.start(Some("ChainService"));
@@ -147,6 +177,25 @@ pub fn run(args: RunArgs, version: Version) -> Result<(), ExitCode> {
     Ok(())
 }
 
+fn delete_blocks_after(config: &CKBAppConfig, keep_from: BlockNumber) -> Result<(), ExitCode> {
+    let db = RocksDB::open_with_check(&config.db, COLUMNS).map_err(|err| {
+        eprintln!("Run error: {:?}", err);
+        ExitCode::Failure
+    })?;
+    let store = ChainKVStore::with_config(db, config.store.clone());
+    let deleted = store.delete_blocks_after(keep_from).map_err(|err| {
+        eprintln!("Run error: {:?}", err);
+        ExitCode::Failure
+    })?;
+    info_target!(
+        crate::LOG_TARGET_MAIN,
+        "deleted {} blocks after {}",
+        deleted,
+        keep_from
+    );
+    Ok(())
+}
+
 fn verify_genesis<CS: ChainStore + 'static>(shared: &Shared<CS>) -> Result<(), ExitCode> {
     let genesis = shared.consensus().genesis_block();
     BlockVerifier::new(shared.clone())
@@ -156,3 +205,26 @@ fn verify_genesis<CS: ChainStore + 'static>(shared: &Shared<CS>) -> Result<(), E
             ExitCode::Config
         })
 }
+
+// Check that the tip is on the main chain, so the node would start from a consistent state
+fn check_tip<CS: ChainStore + 'static>(shared: &Shared<CS>) -> Result<(), ExitCode> {
+    let store = shared.store();
+    let tip = store.get_tip_header().ok_or_else(|| {
+        eprintln!("Dry run error: the tip header is missing");
+        ExitCode::Failure
+    })?;
+    if store.get_block_hash(tip.number()).as_ref() != Some(tip.hash()) {
+        eprintln!(
+            "Dry run error: the tip {:#x} at {} is not on the main chain",
+            tip.hash(),
+            tip.number()
+        );
+        return Err(ExitCode::Failure);
+    }
+
+    println!("genesis: {:#x}", shared.genesis_hash());
+    println!("tip: {:#x}", tip.hash());
+    println!("tip number: {}", tip.number());
+    println!("tip epoch: {}", tip.epoch());
+    Ok(())
+}
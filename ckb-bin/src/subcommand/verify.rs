@@ -0,0 +1,165 @@
+use ckb_app_config::{ExitCode, VerifyArgs};
+use ckb_core::BlockNumber;
+use ckb_shared::shared::{Shared, SharedBuilder};
+use ckb_store::ChainStore;
+use ckb_traits::ChainProvider;
+use ckb_verification::{HeaderResolverWrapper, HeaderVerifier, Verifier};
+use std::cmp;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+pub fn verify(args: VerifyArgs) -> Result<(), ExitCode> {
+    // RocksDB creates a new database on an empty path, there would be nothing to verify.
+    if !args.config.db.path.join("CURRENT").exists() {
+        eprintln!(
+            "Verify error: no database in {}",
+            args.config.db.path.display()
+        );
+        return Err(ExitCode::Config);
+    }
+
+    let shared = SharedBuilder::from_existing(&args.config.db)
+        .map_err(|err| {
+            eprintln!("Verify error: {:?}", err);
+            ExitCode::Failure
+        })?
+        .consensus(args.consensus)
+        .tx_pool_config(args.config.tx_pool)
+        .script_config(args.config.script)
+        .read_only()
+        .build()
+        .map_err(|err| {
+            eprintln!("Verify error: {:?}", err);
+            ExitCode::Failure
+        })?;
+
+    // The genesis block has no parent to be verified against
+    let from = cmp::max(args.from.unwrap_or(1), 1);
+    let tip_number = shared.tip_number();
+    let to = args.to.map_or(tip_number, |to| cmp::min(to, tip_number));
+    match verify_blocks(&shared, from, to) {
+        Ok(count) => {
+            println!("verified {} blocks in {}..={}", count, from, to);
+            Ok(())
+        }
+        Err(err) => {
+            eprintln!("Verify error: {}", err);
+            Err(ExitCode::Failure)
+        }
+    }
+}
+
+// Re-verify the headers of the main chain blocks in `from..=to` and check that no cell is
+// spent twice in the range. Stops at the first error found.
+fn verify_blocks<CS: ChainStore>(
+    shared: &Shared<CS>,
+    from: BlockNumber,
+    to: BlockNumber,
+) -> Result<u64, String> {
+    let pow = shared.consensus().pow_engine();
+    let mut spent = HashSet::new();
+    let mut count = 0;
+    for number in from..=to {
+        let block = shared
+            .store()
+            .get_block_hash(number)
+            .and_then(|hash| shared.store().get_block(&hash))
+            .ok_or_else(|| format!("block {} is missing", number))?;
+        let header = block.header();
+
+        let parent_hash = shared.store().get_block_hash(number - 1);
+        if parent_hash.as_ref() != Some(header.parent_hash()) {
+            return Err(format!(
+                "block {} {:#x}: the parent {:#x} is not the main chain block {}",
+                number,
+                header.hash(),
+                header.parent_hash(),
+                number - 1
+            ));
+        }
+
+        let resolver = HeaderResolverWrapper::new(header, shared.clone());
+        {
+            let chain_state = shared.lock_chain_state();
+            HeaderVerifier::new(&*chain_state, Arc::clone(&pow))
+                .verify(&resolver)
+                .map_err(|err| format!("block {} {:#x}: {:?}", number, header.hash(), err))?;
+        }
+
+        for tx in block.transactions().iter().skip(1) {
+            for input in tx.inputs() {
+                if !spent.insert(input.previous_output.clone()) {
+                    return Err(format!(
+                        "block {} {:#x}: transaction {:#x} spends {:?} which is already spent",
+                        number,
+                        header.hash(),
+                        tx.hash(),
+                        input.previous_output
+                    ));
+                }
+            }
+        }
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_chain::chain::ChainService;
+    use ckb_core::block::BlockBuilder;
+    use ckb_core::header::HeaderBuilder;
+    use ckb_core::transaction::{CellInput, TransactionBuilder};
+    use ckb_db::MemoryKeyValueDB;
+    use ckb_notify::NotifyService;
+    use ckb_store::ChainKVStore;
+
+    // Process blocks on top of the genesis with the given timestamps, skipping the
+    // verification so that invalid blocks end up in the store.
+    fn process_blocks(timestamps: &[u64]) -> Shared<ChainKVStore<MemoryKeyValueDB>> {
+        let shared = SharedBuilder::<MemoryKeyValueDB>::new().build().unwrap();
+        let notify = NotifyService::default().start::<&str>(None);
+        let chain_controller = ChainService::new(shared.clone(), notify).start::<&str>(None);
+        let mut parent = shared.consensus().genesis_block().header().to_owned();
+        for timestamp in timestamps {
+            let number = parent.number() + 1;
+            let cellbase = TransactionBuilder::default()
+                .input(CellInput::new_cellbase_input(number))
+                .build();
+            let block = BlockBuilder::default()
+                .transaction(cellbase)
+                .header_builder(
+                    HeaderBuilder::default()
+                        .parent_hash(parent.hash().to_owned())
+                        .number(number)
+                        .timestamp(*timestamp)
+                        .difficulty(parent.difficulty().to_owned()),
+                )
+                .build();
+            chain_controller
+                .process_block(Arc::new(block.clone()), false)
+                .unwrap();
+            parent = block.header().to_owned();
+        }
+        shared
+    }
+
+    #[test]
+    fn test_verify_clean_chain() {
+        let shared = process_blocks(&[20_000, 40_000, 60_000]);
+        assert_eq!(verify_blocks(&shared, 1, 3), Ok(3));
+        assert_eq!(verify_blocks(&shared, 2, 3), Ok(2));
+    }
+
+    #[test]
+    fn test_verify_bad_block() {
+        // the block 4 is older than the median time of its ancestors
+        let shared = process_blocks(&[20_000, 40_000, 60_000, 20_000, 80_000]);
+        assert_eq!(verify_blocks(&shared, 1, 3), Ok(3));
+
+        let err = verify_blocks(&shared, 1, 5).unwrap_err();
+        assert!(err.starts_with("block 4 "), "{}", err);
+        assert!(err.contains("Timestamp"), "{}", err);
+    }
+}
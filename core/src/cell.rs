@@ -192,6 +192,14 @@ impl CellStatus {
     }
 }
 
+/// The status of a cell together with its output
+#[derive(PartialEq, Debug)]
+pub struct CellWithStatus {
+    /// The output of the cell, only present when the cell is live
+    pub cell: Option<CellOutput>,
+    pub status: CellStatus,
+}
+
 #[derive(Clone, PartialEq, Debug)]
 pub enum HeaderStatus {
     /// Header exists on current chain
@@ -5,6 +5,7 @@ use numext_fixed_uint::U256;
 use serde_derive::{Deserialize, Serialize};
 
 pub const DEFAULT_ACCUMULATED_RATE: u64 = 10_000_000_000_000_000;
+pub const DEFAULT_MAX_BLOCK_BYTES: u64 = 2_000_000; // 2mb
 
 #[derive(Clone, Serialize, Deserialize, PartialEq, Default, Debug)]
 pub struct BlockExt {
@@ -14,6 +15,23 @@ pub struct BlockExt {
     pub verified: Option<bool>,
     pub dao_stats: DaoStats,
     pub txs_fees: Vec<Capacity>,
+    /// The serialized size of the block, see `Block::serialized_size`
+    pub size: u32,
+    /// The parts of the reward paid by the cellbase. `None` for the blocks which finalize
+    /// no target and for the blocks stored before 0.1405.0.
+    pub reward_components: Option<BlockRewardComponents>,
+}
+
+/// The parts of the reward paid by a cellbase, before the cap of `max_block_reward`
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub struct BlockRewardComponents {
+    pub base: Capacity,
+    /// The proposers' share of the fees of the transactions first proposed in the target
+    pub proposals: Capacity,
+    /// The bonus for the uncles included in the target
+    pub uncles: Capacity,
+    /// The committer's share of the fees of the transactions committed in the target
+    pub txs_fees: Capacity,
 }
 
 #[derive(Clone, Serialize, Deserialize, PartialEq, Default, Debug)]
@@ -35,6 +53,24 @@ pub struct TransactionAddress {
     pub length: usize,
 }
 
+/// Where a committed transaction is in the main chain
+#[derive(Clone, Serialize, Deserialize, Eq, PartialEq, Debug)]
+pub struct TransactionConfirmation {
+    pub block_hash: H256,
+    pub block_number: BlockNumber,
+    // Index of the transaction in the block
+    pub tx_index: u32,
+}
+
+/// The figures of an epoch most callers need, with the main chain block starting it
+#[derive(Clone, Serialize, Deserialize, Eq, PartialEq, Debug)]
+pub struct EpochInfo {
+    pub start_number: BlockNumber,
+    pub length: BlockNumber,
+    pub start_block_hash: H256,
+    pub difficulty: U256,
+}
+
 #[derive(Clone, Serialize, Deserialize, Eq, PartialEq, Debug)]
 pub struct EpochExt {
     pub(crate) number: EpochNumber,
@@ -44,6 +80,7 @@ pub struct EpochExt {
     pub(crate) start_number: BlockNumber,
     pub(crate) length: BlockNumber,
     pub(crate) difficulty: U256,
+    pub(crate) max_block_bytes: u64,
 }
 
 impl EpochExt {
@@ -87,6 +124,14 @@ impl EpochExt {
         &self.difficulty
     }
 
+    pub fn set_max_block_bytes(&mut self, max_block_bytes: u64) {
+        self.max_block_bytes = max_block_bytes;
+    }
+
+    pub fn max_block_bytes(&self) -> u64 {
+        self.max_block_bytes
+    }
+
     pub fn remainder_reward(&self) -> &Capacity {
         &self.remainder_reward
     }
@@ -112,6 +157,7 @@ impl EpochExt {
             last_block_hash_in_previous_epoch,
             length,
             difficulty,
+            max_block_bytes: DEFAULT_MAX_BLOCK_BYTES,
         }
     }
 
@@ -134,6 +180,7 @@ impl EpochExt {
             last_block_hash_in_previous_epoch,
             length,
             difficulty,
+            ..
         } = self;
         (
             number,
@@ -4,11 +4,25 @@ use fnv::FnvHashMap;
 use lru_cache::LruCache;
 use std::iter::FromIterator;
 use std::ops::Range;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
 use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
-type CacheTable = FnvHashMap<Col, Mutex<LruCache<Vec<u8>, Vec<u8>>>>;
+// The prefetch requests waiting for the worker, more are dropped
+const PREFETCH_QUEUE_SIZE: usize = 256;
+
+type CacheTable = FnvHashMap<Col, Mutex<ColumnCache>>;
 pub type CacheCols = (u32, usize);
 
+struct ColumnCache {
+    // Each cached value is stored along with the time it was put into the cache
+    values: LruCache<Vec<u8>, (Instant, Vec<u8>)>,
+    // Bumped by each batch writing the column, a prefetched value read before a write is
+    // dropped
+    writes: u64,
+}
+
 enum BatchOperation {
     Insert {
         col: Col,
@@ -71,13 +85,15 @@ impl<T: DbBatch> DbBatch for CacheDBBatch<T> {
                 BatchOperation::Insert { col, key, value } => {
                     if let Some(cache) = self.cache.get(&col) {
                         let mut cache_guard = cache.lock();
-                        cache_guard.insert(key, value);
+                        cache_guard.values.insert(key, (Instant::now(), value));
+                        cache_guard.writes += 1;
                     }
                 }
                 BatchOperation::Delete { col, key } => {
                     if let Some(cache) = self.cache.get(&col) {
                         let mut cache_guard = cache.lock();
-                        cache_guard.remove(&key);
+                        cache_guard.values.remove(&key);
+                        cache_guard.writes += 1;
                     }
                 }
             }
@@ -90,8 +106,17 @@ pub struct CacheDB<T>
 where
     T: KeyValueDB,
 {
-    db: T,
+    db: Arc<T>,
     cache: Arc<CacheTable>,
+    ttls: FnvHashMap<Col, Duration>,
+    // Started by the first `prefetch`
+    prefetcher: Mutex<Option<Prefetcher>>,
+}
+
+// The worker serving the prefetch requests, it stops when the sender is dropped
+struct Prefetcher {
+    sender: SyncSender<(Col, Vec<u8>)>,
+    handle: JoinHandle<()>,
 }
 
 impl<T> CacheDB<T>
@@ -99,13 +124,101 @@ where
     T: KeyValueDB,
 {
     pub fn new(db: T, cols: &[CacheCols]) -> Self {
-        let table = FnvHashMap::from_iter(
-            cols.iter()
-                .map(|(idx, capacity)| (*idx, Mutex::new(LruCache::new(*capacity)))),
-        );
+        let table = FnvHashMap::from_iter(cols.iter().map(|(idx, capacity)| {
+            let cache = ColumnCache {
+                values: LruCache::new(*capacity),
+                writes: 0,
+            };
+            (*idx, Mutex::new(cache))
+        }));
         CacheDB {
-            db,
+            db: Arc::new(db),
             cache: Arc::new(table),
+            ttls: FnvHashMap::default(),
+            prefetcher: Mutex::new(None),
+        }
+    }
+
+    /// Expire the cached values of the column `ttl` after they were put into the cache, the
+    /// next read of an expired key evicts it and goes to the db. Values in columns without a
+    /// ttl stay until the LRU drops them.
+    pub fn with_ttl(mut self, col: Col, ttl: Duration) -> Self {
+        self.ttls.insert(col, ttl);
+        self
+    }
+
+    // Apply `f` to the cached value of the key, unless the column has no cache, the key
+    // is not cached or the value has expired
+    fn get_cached<F, R>(&self, col: Col, key: &[u8], f: F) -> Option<R>
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        let cache = self.cache.get(&col)?;
+        let mut cache_guard = cache.lock();
+        if let Some((inserted, value)) = cache_guard.values.get_refresh(key) {
+            match self.ttls.get(&col) {
+                Some(ttl) if inserted.elapsed() > *ttl => {}
+                _ => return Some(f(value)),
+            }
+        } else {
+            return None;
+        }
+        cache_guard.values.remove(key);
+        None
+    }
+
+    /// Read the value on a background thread and put it into the cache, so that a later
+    /// `read` of the key is a cache hit. A single worker serves the requests, which are
+    /// dropped while `PREFETCH_QUEUE_SIZE` are waiting. Keys in columns without a cache are
+    /// ignored.
+    pub fn prefetch(&self, col: Col, key: &[u8])
+    where
+        T: 'static,
+    {
+        if !self.cache.contains_key(&col) {
+            return;
+        }
+        let mut prefetcher = self.prefetcher.lock();
+        let prefetcher = prefetcher.get_or_insert_with(|| {
+            let (sender, receiver) = sync_channel(PREFETCH_QUEUE_SIZE);
+            let db = Arc::clone(&self.db);
+            let cache = Arc::clone(&self.cache);
+            let handle = thread::Builder::new()
+                .name("CacheDBPrefetch".to_owned())
+                .spawn(move || prefetch_values(&*db, &cache, receiver))
+                .expect("start the prefetch thread");
+            Prefetcher { sender, handle }
+        });
+        let _ = prefetcher.sender.try_send((col, key.to_vec()));
+    }
+}
+
+// Serve the prefetch requests until the `CacheDB` is dropped. The db is read without the
+// cache lock, the value is only cached if no batch has written the column meanwhile.
+fn prefetch_values<T: KeyValueDB>(db: &T, cache: &CacheTable, receiver: Receiver<(Col, Vec<u8>)>) {
+    for (col, key) in receiver {
+        let cache = match cache.get(&col) {
+            Some(cache) => cache,
+            None => continue,
+        };
+        let writes = cache.lock().writes;
+        if let Ok(Some(value)) = db.read(col, &key) {
+            let mut cache_guard = cache.lock();
+            if cache_guard.writes == writes && !cache_guard.values.contains_key(&key) {
+                cache_guard.values.insert(key, (Instant::now(), value));
+            }
+        }
+    }
+}
+
+impl<T> Drop for CacheDB<T>
+where
+    T: KeyValueDB,
+{
+    fn drop(&mut self) {
+        if let Some(Prefetcher { sender, handle }) = self.prefetcher.get_mut().take() {
+            drop(sender);
+            let _ = handle.join();
         }
     }
 }
@@ -117,22 +230,17 @@ where
     type Batch = CacheDBBatch<T::Batch>;
 
     fn read(&self, col: Col, key: &[u8]) -> Result<Option<Vec<u8>>> {
-        if let Some(value) = self
-            .cache
-            .get(&col)
-            .and_then(|cache| cache.lock().get_refresh(key).cloned())
-        {
+        if let Some(value) = self.get_cached(col, key, |value| value.to_vec()) {
             return Ok(Some(value));
         }
         self.db.read(col, key)
     }
 
     fn partial_read(&self, col: Col, key: &[u8], range: &Range<usize>) -> Result<Option<Vec<u8>>> {
-        if let Some(cache) = self.cache.get(&col) {
-            let mut cache_guard = cache.lock();
-            if let Some(data) = cache_guard.get_refresh(key) {
-                return Ok(data.get(range.start..range.end).map(|slice| slice.to_vec()));
-            }
+        if let Some(data) = self.get_cached(col, key, |data| {
+            data.get(range.start..range.end).map(|slice| slice.to_vec())
+        }) {
+            return Ok(data);
         }
         self.db.partial_read(col, key, range)
     }
@@ -144,7 +252,119 @@ where
         self.db.traverse(col, callback)
     }
 
+    fn traverse_prefix<F>(&self, col: Col, prefix: &[u8], callback: F) -> Result<()>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<bool>,
+    {
+        self.db.traverse_prefix(col, prefix, callback)
+    }
+
+    fn compact_with_filter<F>(&self, col: Col, filter: F) -> Result<()>
+    where
+        F: Fn(&[u8], &[u8]) -> bool + Send + Sync + 'static,
+    {
+        self.db.compact_with_filter(col, filter)?;
+        // The dropped entries may be cached
+        if let Some(cache) = self.cache.get(&col) {
+            let mut cache_guard = cache.lock();
+            cache_guard.values.clear();
+            cache_guard.writes += 1;
+        }
+        Ok(())
+    }
+
     fn batch(&self) -> Result<Self::Batch> {
         Ok(CacheDBBatch::new(self.db.batch()?, Arc::clone(&self.cache)))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryKeyValueDB;
+
+    #[test]
+    fn prefetch() {
+        let inner = MemoryKeyValueDB::open(2);
+        let mut batch = inner.batch().unwrap();
+        batch.insert(0, &[0], &[1, 2, 3]).unwrap();
+        batch.insert(1, &[0], &[4, 5, 6]).unwrap();
+        batch.commit().unwrap();
+        let db = CacheDB::new(inner, &[(0, 8)]);
+
+        db.prefetch(0, &[0]);
+        db.prefetch(1, &[0]);
+        let cached = |col| {
+            db.cache.get(&col).and_then(|cache| {
+                cache
+                    .lock()
+                    .values
+                    .get_refresh(&[0][..])
+                    .map(|(_, v)| v.clone())
+            })
+        };
+        for _ in 0..1000 {
+            if cached(0).is_some() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(cached(0), Some(vec![1, 2, 3]));
+        assert_eq!(db.read(0, &[0]).unwrap(), Some(vec![1, 2, 3]));
+        // column 1 has no cache, it is read from the inner db
+        assert_eq!(cached(1), None);
+        assert_eq!(db.read(1, &[0]).unwrap(), Some(vec![4, 5, 6]));
+    }
+
+    #[test]
+    fn drop_stops_prefetch() {
+        let db = CacheDB::new(MemoryKeyValueDB::open(1), &[(0, 8)]);
+        db.prefetch(0, &[0]);
+        let inner = Arc::downgrade(&db.db);
+        drop(db);
+        // the worker has been joined and released its handle of the inner db
+        assert!(inner.upgrade().is_none());
+    }
+
+    #[test]
+    fn batch_updates_cache() {
+        let db = CacheDB::new(MemoryKeyValueDB::open(1), &[(0, 8)]);
+        let mut batch = db.batch().unwrap();
+        batch.insert(0, &[0], &[1]).unwrap();
+        batch.insert(0, &[1], &[2]).unwrap();
+        batch.commit().unwrap();
+        assert_eq!(db.read(0, &[0]).unwrap(), Some(vec![1]));
+
+        // e.g. the index entries of the blocks detached in a reorg
+        let mut batch = db.batch().unwrap();
+        batch.insert(0, &[0], &[3]).unwrap();
+        batch.delete(0, &[1]).unwrap();
+        batch.commit().unwrap();
+        assert_eq!(db.read(0, &[0]).unwrap(), Some(vec![3]));
+        assert_eq!(db.read(0, &[1]).unwrap(), None);
+        assert!(db.cache[&0].lock().values.get_refresh(&[1][..]).is_none());
+    }
+
+    #[test]
+    fn with_ttl() {
+        let db = CacheDB::new(MemoryKeyValueDB::open(2), &[(0, 8), (1, 8)])
+            .with_ttl(0, Duration::from_millis(50));
+        let mut batch = db.batch().unwrap();
+        batch.insert(0, &[0], &[1]).unwrap();
+        batch.insert(1, &[0], &[2]).unwrap();
+        batch.commit().unwrap();
+
+        // bypass the cache, the cached values are still returned before the ttl
+        let mut batch = db.db.batch().unwrap();
+        batch.insert(0, &[0], &[3]).unwrap();
+        batch.insert(1, &[0], &[4]).unwrap();
+        batch.commit().unwrap();
+        assert_eq!(db.read(0, &[0]).unwrap(), Some(vec![1]));
+
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(db.read(0, &[0]).unwrap(), Some(vec![3]));
+        assert!(db.cache[&0].lock().values.get_refresh(&[0][..]).is_none());
+        // column 1 has no ttl
+        assert_eq!(db.read(1, &[0]).unwrap(), Some(vec![2]));
+    }
+}
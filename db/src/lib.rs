@@ -34,6 +34,44 @@ pub trait KeyValueDB: Sync + Send {
     fn traverse<F>(&self, col: Col, callback: F) -> Result<()>
     where
         F: FnMut(&[u8], &[u8]) -> Result<()>;
+    /// Visit the entries whose keys start with `prefix` in the ascending order of the keys,
+    /// until `callback` returns `false`. The default implementation collects the matches of
+    /// a full `traverse` and sorts them, a database iterating in key order overrides it.
+    fn traverse_prefix<F>(&self, col: Col, prefix: &[u8], mut callback: F) -> Result<()>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<bool>,
+    {
+        let mut entries = Vec::new();
+        self.traverse(col, |key, value| {
+            if key.starts_with(prefix) {
+                entries.push((key.to_vec(), value.to_vec()));
+            }
+            Ok(())
+        })?;
+        entries.sort();
+        for (key, value) in entries {
+            if !callback(&key, &value)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+    /// Drop the entries of the column `filter` returns true for. The default implementation
+    /// deletes them in a batch after a full `traverse`, RocksDB drops them in a compaction
+    /// filter instead, without writing a deletion for each of them.
+    fn compact_with_filter<F>(&self, col: Col, filter: F) -> Result<()>
+    where
+        F: Fn(&[u8], &[u8]) -> bool + Send + Sync + 'static,
+    {
+        let mut batch = self.batch()?;
+        self.traverse(col, |key, value| {
+            if filter(key, value) {
+                batch.delete(col, key)?;
+            }
+            Ok(())
+        })?;
+        batch.commit()
+    }
 }
 
 pub trait DbBatch {
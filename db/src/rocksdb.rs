@@ -1,42 +1,70 @@
 use crate::{Col, DBConfig, DbBatch, Error, KeyValueDB, Result};
+use ckb_util::RwLock;
+use fnv::FnvHashMap;
 use log::{info, warn};
-use rocksdb::{ColumnFamily, Error as RdbError, IteratorMode, Options, WriteBatch, DB};
+use rocksdb::checkpoint::Checkpoint;
+use rocksdb::{
+    ColumnFamily, ColumnFamilyDescriptor, CompactionDecision, Direction, Error as RdbError,
+    IteratorMode, Options, WriteBatch, DB,
+};
 use std::ops::Range;
+use std::path::Path;
 use std::sync::Arc;
 
 // If any data format in database was changed, we have to update this constant manually.
 //      - If the data can be migrated at startup automatically: update "x.y.z1" to "x.y.z2".
 //      - If the data can be migrated manually: update "x.y1.z" to "x.y2.0".
 //      - If the data can not be migrated: update "x1.y.z" to "x2.0.0".
+//      - `ckb migrate` upgrades the data of the older versions, see `ckb_store::migration`.
 pub(crate) const VERSION_KEY: &str = "db-version";
-pub(crate) const VERSION_VALUE: &str = "0.1400.0";
+pub const VERSION_VALUE: &str = "0.1405.0";
+
+// The filter of `compact_with_filter` for each column being compacted
+type CompactionFilters =
+    Arc<RwLock<FnvHashMap<Col, Arc<dyn Fn(&[u8], &[u8]) -> bool + Send + Sync>>>>;
 
 pub struct RocksDB {
     inner: Arc<DB>,
+    compaction_filters: CompactionFilters,
+}
+
+// Every column has a compaction filter consulting `filters`, RocksDB only accepts them when
+// the database is opened
+fn cf_descriptors(columns: u32, filters: &CompactionFilters) -> Vec<ColumnFamilyDescriptor> {
+    (0..columns)
+        .map(|col| {
+            let filters = Arc::clone(filters);
+            let mut cf_opts = Options::default();
+            cf_opts.set_compaction_filter(
+                "ckb_compaction_filter",
+                move |_level: u32, key: &[u8], value: &[u8]| match filters.read().get(&col) {
+                    Some(filter) if filter(key, value) => CompactionDecision::Remove,
+                    _ => CompactionDecision::Keep,
+                },
+            );
+            ColumnFamilyDescriptor::new(col.to_string(), cf_opts)
+        })
+        .collect()
 }
 
 impl RocksDB {
-    pub(crate) fn open_with_check(
-        config: &DBConfig,
-        columns: u32,
-        ver_key: &str,
-        ver_val: &str,
-    ) -> Result<Self> {
+    // Open the database, a missing one is created at `ver_val`
+    fn open_db(config: &DBConfig, columns: u32, ver_key: &str, ver_val: &str) -> Result<Self> {
         let mut opts = Options::default();
         opts.create_if_missing(false);
         opts.create_missing_column_families(true);
 
-        let cfnames: Vec<_> = (0..columns).map(|c| c.to_string()).collect();
-        let cf_options: Vec<&str> = cfnames.iter().map(|n| n as &str).collect();
+        let compaction_filters = CompactionFilters::default();
+        let cfs = || cf_descriptors(columns, &compaction_filters);
 
-        let db = DB::open_cf(&opts, &config.path, &cf_options).or_else(|err| {
+        let db = DB::open_cf_descriptors(&opts, &config.path, cfs()).or_else(|err| {
             let err_str = err.as_ref();
             if err_str.starts_with("Invalid argument:")
                 && err_str.ends_with("does not exist (create_if_missing is false)")
             {
                 info!("Initialize a new database");
                 opts.create_if_missing(true);
-                let db = DB::open_cf(&opts, &config.path, &cf_options).map_err(|err| {
+                let db = DB::open_cf_descriptors(&opts, &config.path, cfs()).map_err(|err| {
                     Error::DBError(format!("failed to open a new created database: {}", err))
                 })?;
                 db.put(ver_key, ver_val).map_err(|err| {
@@ -45,14 +73,9 @@ impl RocksDB {
                 Ok(db)
             } else if err.as_ref().starts_with("Corruption:") {
                 warn!("Repairing the rocksdb since {} ...", err);
-                let mut repair_opts = Options::default();
-                repair_opts.create_if_missing(false);
-                repair_opts.create_missing_column_families(false);
-                DB::repair(repair_opts, &config.path).map_err(|err| {
-                    Error::DBError(format!("failed to repair the database: {}", err))
-                })?;
+                Self::repair(&config.path)?;
                 warn!("Opening the repaired rocksdb ...");
-                DB::open_cf(&opts, &config.path, &cf_options).map_err(|err| {
+                DB::open_cf_descriptors(&opts, &config.path, cfs()).map_err(|err| {
                     Error::DBError(format!("failed to open the repaired database: {}", err))
                 })
             } else {
@@ -72,7 +95,22 @@ impl RocksDB {
                 .map(|_| Error::DBError("failed to set database option".to_owned()))?;
         }
 
+        Ok(RocksDB {
+            inner: Arc::new(db),
+            compaction_filters,
+        })
+    }
+
+    pub(crate) fn open_with_version(
+        config: &DBConfig,
+        columns: u32,
+        ver_key: &str,
+        ver_val: &str,
+    ) -> Result<Self> {
+        let db = Self::open_db(config, columns, ver_key, ver_val)?;
+
         let version_bytes = db
+            .inner
             .get(ver_key)
             .map_err(|err| {
                 Error::DBError(format!("failed to check the version of database: {}", err))
@@ -98,20 +136,58 @@ impl RocksDB {
                 required_version, version
             );
             // Do data migration here.
-            db.put(ver_key, ver_val).map_err(|err| {
+            db.inner.put(ver_key, ver_val).map_err(|err| {
                 Error::DBError(format!("Failed to update database version: {}", err))
             })?;
         }
 
-        Ok(RocksDB {
-            inner: Arc::new(db),
-        })
+        Ok(db)
+    }
+
+    /// Recover as much data as possible from a database which can't be opened any more, e.g.
+    /// after a crash in the middle of a write. Data in the corrupted files may be lost.
+    pub fn repair(path: &Path) -> Result<()> {
+        let mut repair_opts = Options::default();
+        repair_opts.create_if_missing(false);
+        repair_opts.create_missing_column_families(false);
+        DB::repair(repair_opts, path)
+            .map_err(|err| Error::DBError(format!("failed to repair the database: {}", err)))
+    }
+
+    /// Create a consistent copy of the database in `dest`, which must not exist yet. Table
+    /// files are hard linked when `dest` is on the same file system, so it is cheap to take
+    /// while the database is being written.
+    pub fn checkpoint(&self, dest: &Path) -> Result<()> {
+        Checkpoint::new(&self.inner)
+            .and_then(|checkpoint| checkpoint.create_checkpoint(dest))
+            .map_err(|err| Error::DBError(format!("failed to create checkpoint: {}", err)))
+    }
+
+    /// Open the database, failing instead of panicking when it can't be opened or its
+    /// version doesn't match `VERSION_VALUE`.
+    pub fn open_with_check(config: &DBConfig, columns: u32) -> Result<Self> {
+        Self::open_with_version(config, columns, VERSION_KEY, VERSION_VALUE)
     }
 
     // TODO Change `panic(...)` to `Result<...>`
     pub fn open(config: &DBConfig, columns: u32) -> Self {
-        Self::open_with_check(config, columns, VERSION_KEY, VERSION_VALUE)
-            .unwrap_or_else(|err| panic!("{}", err))
+        Self::open_with_check(config, columns).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Open the database whatever its version is, so that an older database can be migrated.
+    /// A missing database is created at `VERSION_VALUE`.
+    pub fn open_for_migration(config: &DBConfig, columns: u32) -> Result<Self> {
+        Self::open_db(config, columns, VERSION_KEY, VERSION_VALUE)
+    }
+
+    /// The version of the data format recorded in the database
+    pub fn version(&self) -> Result<Option<String>> {
+        let version = self.inner.get(VERSION_KEY)?;
+        Ok(version.map(|bytes| String::from_utf8_lossy(&bytes).into_owned()))
+    }
+
+    pub fn set_version(&self, version: &str) -> Result<()> {
+        self.inner.put(VERSION_KEY, version).map_err(Into::into)
     }
 }
 
@@ -151,12 +227,45 @@ impl KeyValueDB for RocksDB {
         Ok(())
     }
 
+    fn traverse_prefix<F>(&self, col: Col, prefix: &[u8], mut callback: F) -> Result<()>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<bool>,
+    {
+        let cf = cf_handle(&self.inner, col)?;
+        let iter = self
+            .inner
+            .full_iterator_cf(cf, IteratorMode::From(prefix, Direction::Forward))?;
+        for (key, val) in iter.take_while(|(key, _)| key.starts_with(prefix)) {
+            if !callback(&key, &val)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
     fn batch(&self) -> Result<Self::Batch> {
         Ok(Self::Batch {
             db: Arc::clone(&self.inner),
             wb: WriteBatch::default(),
         })
     }
+
+    // Compacting the whole column flushes its memtable and rewrites all its files, the
+    // filter sees every entry. The filter also applies to the automatic compactions running
+    // meanwhile.
+    fn compact_with_filter<F>(&self, col: Col, filter: F) -> Result<()>
+    where
+        F: Fn(&[u8], &[u8]) -> bool + Send + Sync + 'static,
+    {
+        let cf = cf_handle(&self.inner, col)?;
+        self.compaction_filters
+            .write()
+            .insert(col, Arc::new(filter));
+        self.inner
+            .compact_range_cf(cf, None::<&[u8]>, None::<&[u8]>);
+        self.compaction_filters.write().remove(&col);
+        Ok(())
+    }
 }
 
 pub struct RocksdbBatch {
@@ -211,7 +320,7 @@ mod tests {
             ..Default::default()
         };
 
-        RocksDB::open_with_check(&config, columns, ver_key, ver_val)
+        RocksDB::open_with_version(&config, columns, ver_key, ver_val)
     }
 
     #[test]
@@ -290,6 +399,64 @@ mod tests {
         );
     }
 
+    #[test]
+    fn traverse_prefix() {
+        let db = setup_db("traverse_prefix", 1);
+
+        let mut batch = db.batch().unwrap();
+        batch.insert(0, &[1, 2], &[0]).unwrap();
+        batch.insert(0, &[0, 1], &[1]).unwrap();
+        batch.insert(0, &[1, 0], &[2]).unwrap();
+        batch.insert(0, &[2, 0], &[3]).unwrap();
+        batch.commit().unwrap();
+
+        let mut keys = Vec::new();
+        db.traverse_prefix(0, &[1], |key, _| {
+            keys.push(key.to_vec());
+            Ok(true)
+        })
+        .unwrap();
+        assert_eq!(keys, vec![vec![1, 0], vec![1, 2]]);
+
+        let mut keys = Vec::new();
+        db.traverse_prefix(0, &[1], |key, _| {
+            keys.push(key.to_vec());
+            Ok(false)
+        })
+        .unwrap();
+        assert_eq!(keys, vec![vec![1, 0]]);
+    }
+
+    #[test]
+    fn compact_with_filter() {
+        let db = setup_db("compact_with_filter", 2);
+
+        let mut batch = db.batch().unwrap();
+        for key in 0..4u8 {
+            batch.insert(0, &[key], &[key % 2]).unwrap();
+            batch.insert(1, &[key], &[key % 2]).unwrap();
+        }
+        batch.commit().unwrap();
+
+        db.compact_with_filter(0, |_, value| value[0] == 1).unwrap();
+        let mut keys = Vec::new();
+        db.traverse(0, |key, _| {
+            keys.push(key.to_vec());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(keys, vec![vec![0], vec![2]]);
+        // the other columns are kept
+        assert_eq!(db.read(1, &[1]), Ok(Some(vec![1])));
+
+        // the filter is dropped after the compaction
+        let mut batch = db.batch().unwrap();
+        batch.insert(0, &[5], &[1]).unwrap();
+        batch.commit().unwrap();
+        db.compact_with_filter(0, |_, _| false).unwrap();
+        assert_eq!(db.read(0, &[5]), Ok(Some(vec![1])));
+    }
+
     #[test]
     #[should_panic]
     fn test_version_is_not_matched() {
@@ -301,8 +468,34 @@ mod tests {
             path: tmp_dir.as_ref().to_path_buf(),
             ..Default::default()
         };
-        let _ = RocksDB::open_with_check(&config, 1, VERSION_KEY, "0.1.0");
-        let _ = RocksDB::open_with_check(&config, 1, VERSION_KEY, "0.2.0").unwrap();
+        let _ = RocksDB::open_with_version(&config, 1, VERSION_KEY, "0.1.0");
+        let _ = RocksDB::open_with_version(&config, 1, VERSION_KEY, "0.2.0").unwrap();
+    }
+
+    #[test]
+    fn repair_corrupted_db() {
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("repair_corrupted_db")
+            .tempdir()
+            .unwrap();
+        let config = DBConfig {
+            path: tmp_dir.as_ref().to_path_buf(),
+            ..Default::default()
+        };
+        {
+            let db = RocksDB::open_with_version(&config, 2, VERSION_KEY, VERSION_VALUE).unwrap();
+            let mut batch = db.batch().unwrap();
+            batch.insert(0, &[0, 0], &[0, 0, 0]).unwrap();
+            batch.commit().unwrap();
+        }
+
+        ::std::fs::write(config.path.join("CURRENT"), b"corrupted").unwrap();
+        let mut opts = Options::default();
+        opts.create_missing_column_families(true);
+        assert!(DB::open_cf(&opts, &config.path, &["0", "1"]).is_err());
+
+        RocksDB::repair(&config.path).unwrap();
+        assert!(RocksDB::open_with_version(&config, 2, VERSION_KEY, VERSION_VALUE).is_ok());
     }
 
     #[test]
@@ -315,7 +508,7 @@ mod tests {
             path: tmp_dir.as_ref().to_path_buf(),
             ..Default::default()
         };
-        let _ = RocksDB::open_with_check(&config, 1, VERSION_KEY, VERSION_VALUE).unwrap();
-        let _ = RocksDB::open_with_check(&config, 1, VERSION_KEY, VERSION_VALUE).unwrap();
+        let _ = RocksDB::open_with_version(&config, 1, VERSION_KEY, VERSION_VALUE).unwrap();
+        let _ = RocksDB::open_with_version(&config, 1, VERSION_KEY, VERSION_VALUE).unwrap();
     }
 }
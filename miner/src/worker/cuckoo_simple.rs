@@ -6,8 +6,9 @@ use ckb_pow::{pow_message, Cuckoo, CuckooSip};
 use crossbeam_channel::{Receiver, Sender};
 use indicatif::ProgressBar;
 use numext_fixed_hash::H256;
-use rand::random;
+use rand::{thread_rng, Rng};
 use serde_derive::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
 use std::thread;
 use std::time::{Duration, SystemTime};
 
@@ -23,14 +24,25 @@ pub struct CuckooSimple {
     seal_tx: Sender<(H256, Seal)>,
     worker_rx: Receiver<WorkerMessage>,
     seal_candidates_found: u64,
+    nonces: RangeInclusive<u64>,
+    next_nonce: u64,
 }
 
 impl CuckooSimple {
+    /// The worker only tries the nonces in `nonces`, so that the threads of one
+    /// worker never repeat each other's work.
     pub fn new(
         cuckoo: Cuckoo,
         seal_tx: Sender<(H256, Seal)>,
         worker_rx: Receiver<WorkerMessage>,
+        nonces: RangeInclusive<u64>,
     ) -> Self {
+        // Start at a random position, so that separate miner processes are unlikely to overlap
+        let next_nonce = if nonces.start() < nonces.end() {
+            thread_rng().gen_range(*nonces.start(), *nonces.end())
+        } else {
+            *nonces.start()
+        };
         Self {
             start: true,
             pow_hash: None,
@@ -38,9 +50,21 @@ impl CuckooSimple {
             cuckoo,
             seal_tx,
             worker_rx,
+            nonces,
+            next_nonce,
         }
     }
 
+    fn next_nonce(&mut self) -> u64 {
+        let nonce = self.next_nonce;
+        self.next_nonce = if nonce >= *self.nonces.end() {
+            *self.nonces.start()
+        } else {
+            nonce + 1
+        };
+        nonce
+    }
+
     fn poll_worker_message(&mut self) {
         if let Ok(msg) = self.worker_rx.try_recv() {
             match msg {
@@ -159,7 +183,8 @@ impl Worker for CuckooSimple {
             self.poll_worker_message();
             if self.start {
                 if let Some(pow_hash) = self.pow_hash.clone() {
-                    self.solve(&pow_hash, random());
+                    let nonce = self.next_nonce();
+                    self.solve(&pow_hash, nonce);
                     state_update_counter += 1;
 
                     if state_update_counter == STATE_UPDATE_INTERVAL {
@@ -199,7 +224,8 @@ mod test {
         let (seal_tx, seal_rx) = unbounded();
         let (_worker_tx, worker_rx) = unbounded();
         let cuckoo = Cuckoo::new(6, 8);
-        let mut worker = CuckooSimple::new(cuckoo.clone(), seal_tx, worker_rx);
+        let mut worker =
+            CuckooSimple::new(cuckoo.clone(), seal_tx, worker_rx, 0..=u64::max_value());
         worker.solve(pow_hash, nonce);
         let engine = CuckooEngine { cuckoo };
         while let Ok((pow_hash, seal)) = seal_rx.try_recv() {
@@ -211,6 +237,21 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn next_nonce_stays_in_range() {
+        let (seal_tx, _seal_rx) = unbounded();
+        let (_worker_tx, worker_rx) = unbounded();
+        let mut worker = CuckooSimple::new(Cuckoo::new(6, 8), seal_tx, worker_rx, 10..=13);
+
+        let mut nonces: Vec<_> = (0..4).map(|_| worker.next_nonce()).collect();
+        nonces.sort();
+        assert_eq!(nonces, vec![10, 11, 12, 13]);
+        for _ in 0..8 {
+            let nonce = worker.next_nonce();
+            assert!(nonce >= 10 && nonce <= 13);
+        }
+    }
+
     proptest! {
         #[test]
         fn cuckoo_solve(h256 in prop::array::uniform32(0u8..), nonce in any::<u64>()) {
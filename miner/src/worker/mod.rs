@@ -10,6 +10,7 @@ use cuckoo_simple::CuckooSimple;
 use dummy::Dummy;
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use numext_fixed_hash::H256;
+use std::ops::RangeInclusive;
 use std::sync::Arc;
 use std::thread;
 
@@ -83,10 +84,12 @@ pub fn start_worker(
 
                         let (worker_tx, worker_rx) = unbounded();
                         let (cuckoo, seal_tx) = (cuckoo_engine.cuckoo.clone(), seal_tx.clone());
+                        let nonces = nonce_range(i, config.threads);
                         thread::Builder::new()
                             .name(worker_name)
                             .spawn(move || {
-                                let mut worker = CuckooSimple::new(cuckoo, seal_tx, worker_rx);
+                                let mut worker =
+                                    CuckooSimple::new(cuckoo, seal_tx, worker_rx, nonces);
                                 worker.run(pb);
                             })
                             .expect("Start `CuckooSimple` worker thread failed");
@@ -102,6 +105,40 @@ pub fn start_worker(
     }
 }
 
+/// Split the nonce space into `num_threads` disjoint ranges and return the one for `index`,
+/// the last range takes the remainder.
+pub(crate) fn nonce_range(index: usize, num_threads: usize) -> RangeInclusive<u64> {
+    let size = u64::max_value() / num_threads as u64;
+    let start = size * index as u64;
+    let end = if index + 1 == num_threads {
+        u64::max_value()
+    } else {
+        start + size - 1
+    };
+    start..=end
+}
+
 pub trait Worker {
     fn run(&mut self, progress_bar: ProgressBar);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nonce_ranges_are_disjoint_and_cover_all_nonces() {
+        let num_threads = 4;
+        let ranges: Vec<_> = (0..num_threads)
+            .map(|i| nonce_range(i, num_threads))
+            .collect();
+
+        assert_eq!(*ranges[0].start(), 0);
+        assert_eq!(*ranges[num_threads - 1].end(), u64::max_value());
+        for pair in ranges.windows(2) {
+            assert!(pair[0].start() <= pair[0].end());
+            assert_eq!(*pair[0].end() + 1, *pair[1].start());
+        }
+        assert_eq!(nonce_range(0, 1), 0..=u64::max_value());
+    }
+}
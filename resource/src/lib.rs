@@ -4,7 +4,8 @@
 mod template;
 
 pub use self::template::{
-    TemplateContext, AVAILABLE_SPECS, DEFAULT_P2P_PORT, DEFAULT_RPC_PORT, DEFAULT_SPEC,
+    TemplateContext, AVAILABLE_PROFILES, AVAILABLE_SPECS, DEFAULT_P2P_PORT, DEFAULT_RPC_PORT,
+    DEFAULT_SPEC,
 };
 pub use std::io::{Error, Result};
 
@@ -191,6 +192,7 @@ mod tests {
             log_to_stdout: true,
             runner: "Rust",
             block_assembler: "",
+            profile: None,
         };
         Resource::bundled_ckb_config()
             .export(&context, root_dir.path())
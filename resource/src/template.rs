@@ -1,11 +1,15 @@
 pub const DEFAULT_SPEC: &str = "dev";
 pub const AVAILABLE_SPECS: &[&str] = &["dev", "testnet"];
+pub const AVAILABLE_PROFILES: &[&str] = &["dev", "testnet", "mainnet"];
 pub const DEFAULT_RPC_PORT: &str = "8114";
 pub const DEFAULT_P2P_PORT: &str = "8115";
 
 const START_MARKER: &str = " # {{";
 const END_MAKER: &str = "# }}";
-const WILDCARD_BRANCH: &str = "# _ => ";
+const BRANCH_PREFIX: &str = "# ";
+const BRANCH_SEPARATOR: &str = " => ";
+const WILDCARD: &str = "_";
+const PROFILE_PREFIX: &str = "profile:";
 
 use std::io;
 
@@ -19,6 +23,8 @@ pub struct TemplateContext<'a> {
     pub log_to_stdout: bool,
     pub runner: &'a str,
     pub block_assembler: &'a str,
+    /// Selects the `profile:<name>` branches, which take precedence over the chain branches
+    pub profile: Option<&'a str>,
 }
 
 impl<T> Template<T> {
@@ -43,10 +49,42 @@ fn writeln<W: io::Write>(w: &mut W, s: &str, context: &TemplateContext) -> io::R
     )
 }
 
+// Split the branch `# name1, name2 => content` into its names and its content
+fn parse_branch(line: &str) -> Option<(Vec<&str>, &str)> {
+    if !line.starts_with(BRANCH_PREFIX) {
+        return None;
+    }
+    let line = &line[BRANCH_PREFIX.len()..];
+    line.find(BRANCH_SEPARATOR).map(|pos| {
+        (
+            line[..pos].split(", ").collect(),
+            &line[(pos + BRANCH_SEPARATOR.len())..],
+        )
+    })
+}
+
+// Write the matched branch, or the start line without the marker if no branch matches
+fn write_branch<W: io::Write>(
+    w: &mut W,
+    start_line: &str,
+    matched: Option<&str>,
+    context: &TemplateContext,
+) -> io::Result<()> {
+    match matched {
+        Some(content) => writeln(w, content, context),
+        None => writeln!(
+            w,
+            "{}",
+            &start_line[..(start_line.len() - START_MARKER.len())],
+        ),
+    }
+}
+
 #[derive(Debug)]
 pub enum TemplateState<'a> {
     SearchStartMarker,
-    MatchBranch(&'a str),
+    /// The start line and the content of the first branch matching the chain
+    MatchBranch(&'a str, Option<&'a str>),
     SearchEndMarker,
 }
 
@@ -59,7 +97,9 @@ where
         w: &mut W,
         context: &TemplateContext<'c>,
     ) -> io::Result<()> {
-        let spec_branch = format!("# {} => ", context.spec).to_string();
+        let profile = context
+            .profile
+            .map(|profile| format!("{}{}", PROFILE_PREFIX, profile));
 
         let mut state = TemplateState::SearchStartMarker;
         for line in self.0.as_ref().lines() {
@@ -67,25 +107,30 @@ where
             match state {
                 TemplateState::SearchStartMarker => {
                     if line.ends_with(START_MARKER) {
-                        state = TemplateState::MatchBranch(line);
+                        state = TemplateState::MatchBranch(line, None);
                     } else {
                         writeln!(w, "{}", line)?;
                     }
                 }
-                TemplateState::MatchBranch(start_line) => {
+                TemplateState::MatchBranch(start_line, matched) => {
                     if line == END_MAKER {
-                        writeln!(
-                            w,
-                            "{}",
-                            &start_line[..(start_line.len() - START_MARKER.len())],
-                        )?;
+                        write_branch(w, start_line, matched, context)?;
                         state = TemplateState::SearchStartMarker;
-                    } else if line.starts_with(&spec_branch) {
-                        writeln(w, &line[spec_branch.len()..], context)?;
-                        state = TemplateState::SearchEndMarker;
-                    } else if line.starts_with(WILDCARD_BRANCH) {
-                        writeln(w, &line[WILDCARD_BRANCH.len()..], context)?;
-                        state = TemplateState::SearchEndMarker;
+                    } else if let Some((names, content)) = parse_branch(line) {
+                        if names.iter().any(|name| {
+                            profile
+                                .as_ref()
+                                .map_or(false, |profile| *name == profile.as_str())
+                        }) {
+                            writeln(w, content, context)?;
+                            state = TemplateState::SearchEndMarker;
+                        } else if matched.is_none()
+                            && names
+                                .iter()
+                                .any(|name| *name == context.spec || *name == WILDCARD)
+                        {
+                            state = TemplateState::MatchBranch(start_line, Some(content));
+                        }
                     }
                 }
                 TemplateState::SearchEndMarker => {
@@ -96,12 +141,8 @@ where
             }
         }
 
-        if let TemplateState::MatchBranch(start_line) = state {
-            writeln!(
-                w,
-                "{}",
-                &start_line[..(start_line.len() - START_MARKER.len())],
-            )?;
+        if let TemplateState::MatchBranch(start_line, matched) = state {
+            write_branch(w, start_line, matched, context)?;
         }
 
         Ok(())
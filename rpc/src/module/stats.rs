@@ -2,13 +2,17 @@ use ckb_network_alert::notifier::Notifier as AlertNotifier;
 use ckb_shared::shared::Shared;
 use ckb_store::ChainStore;
 use ckb_sync::Synchronizer;
-use ckb_traits::BlockMedianTimeContext;
+use ckb_traits::{BlockMedianTimeContext, ChainProvider};
 use ckb_util::Mutex;
 use jsonrpc_core::Result;
 use jsonrpc_derive::rpc;
-use jsonrpc_types::{AlertMessage, ChainInfo, EpochNumber, PeerState, Timestamp};
+use jsonrpc_types::{AlertMessage, ChainInfo, EpochNumber, PeerState, Timestamp, Unsigned};
 use std::sync::Arc;
 
+// The number of blocks `get_blockchain_info` computes the uncle rate over, fewer when the
+// chain is shorter
+const UNCLE_RATE_WINDOW: u64 = 1000;
+
 #[rpc]
 pub trait StatsRpc {
     #[rpc(name = "get_blockchain_info")]
@@ -39,6 +43,7 @@ impl<CS: ChainStore + 'static> StatsRpc for StatsRpcImpl<CS> {
         };
         let epoch = tip_header.epoch();
         let difficulty = tip_header.difficulty().clone();
+        let uncle_rate = self.shared.get_uncle_rate(&tip_header, UNCLE_RATE_WINDOW);
         let is_initial_block_download = self.synchronizer.shared.is_initial_block_download();
         let alerts: Vec<AlertMessage> = {
             let now = faketime::unix_time_as_millis();
@@ -57,6 +62,7 @@ impl<CS: ChainStore + 'static> StatsRpc for StatsRpcImpl<CS> {
             epoch: EpochNumber(epoch),
             difficulty,
             is_initial_block_download,
+            uncle_rate: Unsigned(uncle_rate),
             alerts,
         })
     }
@@ -57,33 +57,29 @@ impl<CS: ChainStore> ChainState<CS> {
                 .and_then(|header| store.get_current_epoch_ext().map(|epoch| (header, epoch)))
             {
                 Some((tip_header, epoch)) => {
-                    if let Some(genesis_hash) = store.get_block_hash(0) {
-                        let expect_genesis_hash = consensus.genesis_hash();
-                        if &genesis_hash == expect_genesis_hash {
-                            Ok((tip_header, epoch))
-                        } else {
-                            Err(SharedError::InvalidData(format!(
-                                "mismatch genesis hash, expect {:#x} but {:#x} in database",
-                                expect_genesis_hash, genesis_hash
-                            )))
-                        }
+                    if store.get_block_hash(tip_header.number()).as_ref() != Some(tip_header.hash())
+                    {
+                        return Err(SharedError::InvalidData(format!(
+                            "the tip {:#x} is not indexed at number {}, the database may be corrupted",
+                            tip_header.hash(),
+                            tip_header.number()
+                        )));
+                    }
+                    // the genesis hash is compared against the consensus in `SharedBuilder::build`
+                    if store.get_block_hash(0).is_some() {
+                        Ok((tip_header, epoch))
                     } else {
                         Err(SharedError::InvalidData(
                             "the genesis hash was not found".to_owned(),
                         ))
                     }
                 }
-                None => store
-                    .init(&consensus)
-                    .map_err(|e| {
-                        SharedError::InvalidData(format!("failed to init genesis block {:?}", e))
-                    })
-                    .map(|_| {
-                        (
-                            consensus.genesis_block().header().to_owned(),
-                            consensus.genesis_epoch_ext().to_owned(),
-                        )
-                    }),
+                None => store.init(&consensus).map_err(SharedError::from).map(|_| {
+                    (
+                        consensus.genesis_block().header().to_owned(),
+                        consensus.genesis_epoch_ext().to_owned(),
+                    )
+                }),
             }
         }?;
 
@@ -97,7 +93,7 @@ impl<CS: ChainStore> ChainState<CS> {
 
         let total_difficulty = store
             .get_block_ext(&tip_header.hash())
-            .ok_or_else(|| SharedError::InvalidData("failed to get block_ext".to_owned()))?
+            .ok_or_else(|| SharedError::MissingBlock(tip_header.hash().to_owned()))?
             .total_difficulty;
         Ok(ChainState {
             store: Arc::clone(store),
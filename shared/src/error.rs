@@ -1,6 +1,8 @@
 use ckb_core::cell::UnresolvableError;
+use ckb_core::BlockNumber;
 use ckb_db::Error as DBError;
 use failure::Fail;
+use numext_fixed_hash::H256;
 
 #[derive(Debug, PartialEq, Clone, Eq, Fail)]
 pub enum SharedError {
@@ -14,4 +16,68 @@ pub enum SharedError {
     InvalidData(String),
     #[fail(display = "DB error: {}", _0)]
     DB(DBError),
+    #[fail(display = "ConsensusError: {}", _0)]
+    ConsensusError(String),
+    #[fail(display = "MissingBlock: {:#x}", _0)]
+    MissingBlock(H256),
+    #[fail(display = "MissingTransaction: {:#x}", _0)]
+    MissingTransaction(H256),
+    #[fail(display = "ArithmeticOverflow")]
+    ArithmeticOverflow,
+    #[fail(
+        display = "ImmatureCellbase: input {} created at {} matures at {}",
+        input_index, created_at, matures_at
+    )]
+    ImmatureCellbase {
+        input_index: usize,
+        created_at: BlockNumber,
+        matures_at: BlockNumber,
+    },
+    #[fail(
+        display = "GenesisMismatch: {:#x} in database but {:#x} is expected",
+        stored, expected
+    )]
+    GenesisMismatch { stored: H256, expected: H256 },
+}
+
+impl SharedError {
+    /// JSON-RPC error code reported for this error
+    pub fn rpc_error_code(&self) -> i64 {
+        match self {
+            SharedError::DB(_) => -32000,
+            SharedError::InvalidTransaction(_) => -32001,
+            SharedError::MissingBlock(_) => -32002,
+            SharedError::MissingTransaction(_) => -32003,
+            SharedError::UnresolvableTransaction(_) => -32004,
+            SharedError::InvalidParentBlock => -32005,
+            SharedError::InvalidData(_) => -32006,
+            SharedError::ConsensusError(_) => -32007,
+            SharedError::ArithmeticOverflow => -32008,
+            SharedError::ImmatureCellbase { .. } => -32009,
+            SharedError::GenesisMismatch { .. } => -32010,
+        }
+    }
+
+    /// Whether the same request may succeed when it is retried later
+    pub fn is_retriable(&self) -> bool {
+        match self {
+            SharedError::DB(_)
+            | SharedError::MissingBlock(_)
+            | SharedError::MissingTransaction(_)
+            | SharedError::ImmatureCellbase { .. } => true,
+            SharedError::UnresolvableTransaction(_)
+            | SharedError::InvalidTransaction(_)
+            | SharedError::InvalidParentBlock
+            | SharedError::InvalidData(_)
+            | SharedError::ConsensusError(_)
+            | SharedError::ArithmeticOverflow
+            | SharedError::GenesisMismatch { .. } => false,
+        }
+    }
+}
+
+impl From<DBError> for SharedError {
+    fn from(error: DBError) -> Self {
+        SharedError::DB(error)
+    }
 }
@@ -17,5 +17,8 @@ mod tx_proposal_table;
 #[cfg(test)]
 mod tests;
 
+/// A node whose tip is older than this, in milliseconds, is in initial block download
+pub const MAX_TIP_AGE: u64 = 60 * 60 * 1000;
+
 pub(crate) const LOG_TARGET_TX_POOL: &str = "ckb-tx-pool";
 pub(crate) const LOG_TARGET_CHAIN: &str = "ckb-chain";
@@ -1,25 +1,42 @@
 use crate::chain_state::ChainState;
 use crate::error::SharedError;
 use crate::tx_pool::TxPoolConfig;
+use crate::MAX_TIP_AGE;
 use ckb_chain_spec::consensus::Consensus;
+use ckb_core::cell::{CellProvider, CellStatus, CellWithStatus};
 use ckb_core::extras::EpochExt;
 use ckb_core::header::{BlockNumber, Header};
 use ckb_core::script::Script;
+use ckb_core::transaction::{CellOutput, OutPoint, Transaction};
 use ckb_core::Capacity;
 use ckb_core::Cycle;
 use ckb_db::{DBConfig, KeyValueDB, MemoryKeyValueDB, RocksDB};
 use ckb_script::ScriptConfig;
 use ckb_store::{ChainKVStore, ChainStore, StoreConfig, COLUMNS};
-use ckb_traits::ChainProvider;
+use ckb_traits::{BlockRewardComponents, ChainProvider};
 use ckb_util::{lock_or_panic, Mutex, MutexGuard};
+use dao::calculate_maximum_withdraw;
 use failure::Error as FailureError;
+use faketime::unix_time_as_millis;
 use lru_cache::LruCache;
 use numext_fixed_hash::H256;
+use numext_fixed_uint::U256;
 use reward_calculator::RewardCalculator;
+use std::cmp;
 use std::sync::Arc;
 
 const TXS_VERIFY_CACHE_SIZE: usize = 10_000;
 
+/// A summary of the tip, read from the chain state under a single lock
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChainInfo {
+    pub tip_hash: H256,
+    pub tip_number: BlockNumber,
+    pub total_difficulty: U256,
+    /// Whether the tip is more than `MAX_TIP_AGE` behind the wall clock
+    pub is_initial_block_download: bool,
+}
+
 #[derive(Debug)]
 pub struct Shared<CS> {
     store: Arc<CS>,
@@ -49,6 +66,10 @@ impl<CS: ChainStore> Shared<CS> {
         tx_pool_config: TxPoolConfig,
         script_config: ScriptConfig,
     ) -> Result<Self, SharedError> {
+        consensus
+            .validate()
+            .map_err(|err| SharedError::ConsensusError(err.to_string()))?;
+
         let store = Arc::new(store);
         let consensus = Arc::new(consensus);
         let txs_verify_cache = Arc::new(Mutex::new(LruCache::new(TXS_VERIFY_CACHE_SIZE)));
@@ -75,6 +96,90 @@ impl<CS: ChainStore> Shared<CS> {
     pub fn lock_txs_verify_cache(&self) -> MutexGuard<LruCache<H256, Cycle>> {
         lock_or_panic(&self.txs_verify_cache)
     }
+
+    /// Return the tip number, the chain state is locked only while it is copied
+    pub fn tip_number(&self) -> BlockNumber {
+        self.lock_chain_state().tip_number()
+    }
+
+    /// Return the tip hash, the chain state is locked only while it is copied. Unlike
+    /// `lock_chain_state().tip_hash()` in an argument, no lock is held during the call.
+    pub fn tip_hash(&self) -> H256 {
+        self.lock_chain_state().tip_hash().to_owned()
+    }
+
+    pub fn get_chain_info(&self) -> ChainInfo {
+        let chain_state = self.lock_chain_state();
+        let tip_header = chain_state.tip_header();
+        ChainInfo {
+            tip_hash: tip_header.hash().to_owned(),
+            tip_number: tip_header.number(),
+            total_difficulty: chain_state.total_difficulty().to_owned(),
+            is_initial_block_download: unix_time_as_millis().saturating_sub(tip_header.timestamp())
+                > MAX_TIP_AGE,
+        }
+    }
+
+    /// Pin the current tip, so that several reads agree on it even if a block
+    /// arrives in between.
+    pub fn snapshot(&self) -> SharedSnapshot<CS> {
+        let chain_state = self.lock_chain_state();
+        SharedSnapshot {
+            shared: self.clone(),
+            tip_header: chain_state.tip_header().to_owned(),
+            total_difficulty: chain_state.total_difficulty().to_owned(),
+            current_epoch_ext: chain_state.current_epoch_ext().to_owned(),
+        }
+    }
+
+    /// Return the compensation earned by the deposited cell from the deposit block to the
+    /// withdrawal block, the maximum withdraw of the NervosDAO minus the deposited capacity
+    pub fn calculate_dao_compensation(
+        &self,
+        deposit_block: BlockNumber,
+        withdrawal_block: BlockNumber,
+        deposit_output: &CellOutput,
+    ) -> Result<Capacity, SharedError> {
+        dao_compensation(self, deposit_block, withdrawal_block, deposit_output)
+    }
+
+    /// Check that no input of the transaction spends a cellbase output which
+    /// is still immature at `at_block`. Inputs unknown to the store are skipped.
+    pub fn validate_transaction_cellbase_maturity(
+        &self,
+        tx: &Transaction,
+        at_block: BlockNumber,
+    ) -> Result<(), SharedError> {
+        let cellbase_maturity = self.consensus.cellbase_maturity();
+        for (input_index, input) in tx.inputs().iter().enumerate() {
+            let cell_meta = match input
+                .previous_output
+                .cell
+                .as_ref()
+                .and_then(|cell| self.store.get_cell_meta(&cell.tx_hash, cell.index))
+            {
+                Some(cell_meta) => cell_meta,
+                None => continue,
+            };
+            if !cell_meta.is_cellbase() {
+                continue;
+            }
+            if let Some(block_info) = cell_meta.block_info {
+                let matures_at = block_info
+                    .number
+                    .checked_add(cellbase_maturity)
+                    .ok_or(SharedError::ArithmeticOverflow)?;
+                if at_block < matures_at {
+                    return Err(SharedError::ImmatureCellbase {
+                        input_index,
+                        created_at: block_info.number,
+                        matures_at,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 impl<CS: ChainStore> ChainProvider for Shared<CS> {
@@ -149,9 +254,230 @@ impl<CS: ChainStore> ChainProvider for Shared<CS> {
         RewardCalculator::new(self).block_reward(parent)
     }
 
+    fn get_block_reward_components(&self, hash: &H256) -> Option<BlockRewardComponents> {
+        let ext = self.store.get_block_ext(hash)?;
+        if ext.reward_components.is_some() {
+            return ext.reward_components;
+        }
+        // Computed again for the blocks stored before 0.1405.0
+        let header = self.store.get_block_header(hash)?;
+        if header.is_genesis() {
+            return None;
+        }
+        let parent = self.store.get_block_header(header.parent_hash())?;
+        RewardCalculator::new(self)
+            .block_reward_components(&parent)
+            .ok()
+    }
+
     fn consensus(&self) -> &Consensus {
         &*self.consensus
     }
+
+    fn get_live_cell_with_status(&self, out_point: &OutPoint) -> CellWithStatus {
+        let status = self.lock_chain_state().cell(out_point);
+        let cell = match &status {
+            CellStatus::Live(cell_meta) => cell_meta.cell_output.clone().or_else(|| {
+                self.store
+                    .get_cell_output(&cell_meta.out_point.tx_hash, cell_meta.out_point.index)
+            }),
+            _ => None,
+        };
+        CellWithStatus { cell, status }
+    }
+
+    fn block_headers_range(&self, from: BlockNumber, to: BlockNumber) -> Vec<Header> {
+        let to = match self.store.get_tip_header() {
+            Some(tip) => cmp::min(to, tip.number()),
+            None => return Vec::new(),
+        };
+        if from > to {
+            return Vec::new();
+        }
+        match self
+            .store
+            .get_block_hash(to)
+            .and_then(|hash| self.store.get_block_header(&hash))
+        {
+            Some(header) => headers_down_to(self.store.as_ref(), header, from),
+            None => Vec::new(),
+        }
+    }
+}
+
+// Collect the headers from `top` down to the number `from` by following the parent hashes,
+// a single read per block instead of the number index lookup and the header read. The
+// headers are returned in ascending order.
+fn headers_down_to<CS: ChainStore>(store: &CS, top: Header, from: BlockNumber) -> Vec<Header> {
+    let mut headers = Vec::new();
+    let mut header = Some(top);
+    while let Some(current) = header {
+        header = if current.number() > from {
+            store.get_block_header(current.parent_hash())
+        } else {
+            None
+        };
+        headers.push(current);
+    }
+    headers.reverse();
+    headers
+}
+
+fn dao_compensation<P: ChainProvider>(
+    provider: &P,
+    deposit_block: BlockNumber,
+    withdrawal_block: BlockNumber,
+    deposit_output: &CellOutput,
+) -> Result<Capacity, SharedError> {
+    if withdrawal_block < deposit_block {
+        return Err(SharedError::InvalidData(format!(
+            "withdrawal block {} is before deposit block {}",
+            withdrawal_block, deposit_block
+        )));
+    }
+    let dao_stats = |number: BlockNumber| {
+        provider
+            .get_block_hash(number)
+            .and_then(|hash| provider.store().get_block_ext(&hash))
+            .map(|ext| ext.dao_stats)
+            .filter(|stats| stats.accumulated_rate != 0)
+            .ok_or_else(|| {
+                SharedError::InvalidData(format!(
+                    "no accumulated rate for main chain block {}",
+                    number
+                ))
+            })
+    };
+    let deposit_stats = dao_stats(deposit_block)?;
+    let withdrawal_stats = dao_stats(withdrawal_block)?;
+
+    let maximum_withdraw =
+        calculate_maximum_withdraw(deposit_output, &deposit_stats, &withdrawal_stats)
+            .map_err(|_| SharedError::ArithmeticOverflow)?;
+    Ok(maximum_withdraw
+        .safe_sub(deposit_output.capacity)
+        .unwrap_or_else(|_| Capacity::zero()))
+}
+
+/// A view of the chain at the tip taken by `Shared::snapshot`.
+///
+/// Blocks, headers and exts are immutable once stored, so reads by hash are
+/// consistent by themselves, and main chain lookups are answered by walking back
+/// from the pinned tip. The default `ChainProvider` methods read the main chain through
+/// `get_tip_header`, `get_current_epoch_ext` and `get_block_hash`, which are pinned here.
+/// The spent cells are not pinned, see `get_live_cell_with_status`.
+pub struct SharedSnapshot<CS> {
+    shared: Shared<CS>,
+    tip_header: Header,
+    total_difficulty: U256,
+    current_epoch_ext: EpochExt,
+}
+
+impl<CS: ChainStore> SharedSnapshot<CS> {
+    pub fn tip_header(&self) -> &Header {
+        &self.tip_header
+    }
+
+    pub fn total_difficulty(&self) -> &U256 {
+        &self.total_difficulty
+    }
+
+    /// See `Shared::calculate_dao_compensation`, both blocks must be on the pinned chain
+    pub fn calculate_dao_compensation(
+        &self,
+        deposit_block: BlockNumber,
+        withdrawal_block: BlockNumber,
+        deposit_output: &CellOutput,
+    ) -> Result<Capacity, SharedError> {
+        dao_compensation(self, deposit_block, withdrawal_block, deposit_output)
+    }
+}
+
+impl<CS: ChainStore> ChainProvider for SharedSnapshot<CS> {
+    type Store = CS;
+
+    fn store(&self) -> &Arc<CS> {
+        self.shared.store()
+    }
+
+    fn script_config(&self) -> &ScriptConfig {
+        self.shared.script_config()
+    }
+
+    fn genesis_hash(&self) -> &H256 {
+        self.shared.genesis_hash()
+    }
+
+    fn get_ancestor(&self, base: &H256, number: BlockNumber) -> Option<Header> {
+        self.shared.get_ancestor(base, number)
+    }
+
+    fn get_block_epoch(&self, hash: &H256) -> Option<EpochExt> {
+        self.shared.get_block_epoch(hash)
+    }
+
+    fn next_epoch_ext(&self, last_epoch: &EpochExt, header: &Header) -> Option<EpochExt> {
+        self.shared.next_epoch_ext(last_epoch, header)
+    }
+
+    fn finalize_block_reward(&self, parent: &Header) -> Result<(Script, Capacity), FailureError> {
+        self.shared.finalize_block_reward(parent)
+    }
+
+    fn get_block_reward_components(&self, hash: &H256) -> Option<BlockRewardComponents> {
+        self.shared.get_block_reward_components(hash)
+    }
+
+    fn consensus(&self) -> &Consensus {
+        self.shared.consensus()
+    }
+
+    /// The cells created after the pinned tip are unknown. The spends are not pinned, a cell
+    /// spent after the pinned tip is dead.
+    fn get_live_cell_with_status(&self, out_point: &OutPoint) -> CellWithStatus {
+        let created_after_tip = out_point.cell.as_ref().map_or(false, |cell| {
+            self.store()
+                .get_transaction_address(&cell.tx_hash)
+                .and_then(|address| self.store().get_block_header(&address.block_hash))
+                .map_or(false, |header| {
+                    self.get_block_hash(header.number()).as_ref() != Some(header.hash())
+                })
+        });
+        if created_after_tip {
+            CellWithStatus {
+                cell: None,
+                status: CellStatus::Unknown,
+            }
+        } else {
+            self.shared.get_live_cell_with_status(out_point)
+        }
+    }
+
+    fn get_tip_header(&self) -> Option<Header> {
+        Some(self.tip_header.to_owned())
+    }
+
+    fn get_current_epoch_ext(&self) -> Option<EpochExt> {
+        Some(self.current_epoch_ext.to_owned())
+    }
+
+    /// Get the hash of the block at `number` on the chain ending at the pinned tip
+    fn get_block_hash(&self, number: BlockNumber) -> Option<H256> {
+        self.get_ancestor(self.tip_header.hash(), number)
+            .map(|header| header.hash().to_owned())
+    }
+
+    /// Truncated at the pinned tip
+    fn block_headers_range(&self, from: BlockNumber, to: BlockNumber) -> Vec<Header> {
+        let to = cmp::min(to, self.tip_header.number());
+        if from > to {
+            return Vec::new();
+        }
+        match self.get_ancestor(self.tip_header.hash(), to) {
+            Some(header) => headers_down_to(self.store().as_ref(), header, from),
+            None => Vec::new(),
+        }
+    }
 }
 
 pub struct SharedBuilder<DB: KeyValueDB> {
@@ -160,6 +486,8 @@ pub struct SharedBuilder<DB: KeyValueDB> {
     tx_pool_config: Option<TxPoolConfig>,
     script_config: Option<ScriptConfig>,
     store_config: Option<StoreConfig>,
+    verify_genesis: bool,
+    read_only: bool,
 }
 
 impl<DB: KeyValueDB> Default for SharedBuilder<DB> {
@@ -170,6 +498,8 @@ impl<DB: KeyValueDB> Default for SharedBuilder<DB> {
             tx_pool_config: None,
             script_config: None,
             store_config: None,
+            verify_genesis: true,
+            read_only: false,
         }
     }
 }
@@ -178,10 +508,7 @@ impl SharedBuilder<MemoryKeyValueDB> {
     pub fn new() -> Self {
         SharedBuilder {
             db: Some(MemoryKeyValueDB::open(COLUMNS as usize)),
-            consensus: None,
-            tx_pool_config: None,
-            script_config: None,
-            store_config: None,
+            ..Default::default()
         }
     }
 }
@@ -195,6 +522,22 @@ impl SharedBuilder<RocksDB> {
         self.db = Some(RocksDB::open(config, COLUMNS));
         self
     }
+
+    /// Open the database of a node for restart, checking that its version is the one this
+    /// ckb writes. An empty database is accepted and initialized by `build`, which also
+    /// checks that the stored tip is on the main chain index.
+    pub fn from_existing(config: &DBConfig) -> Result<Self, SharedError> {
+        let db = RocksDB::open_with_check(config, COLUMNS).map_err(|err| {
+            SharedError::InvalidData(format!(
+                "failed to open the database: {}, run `ckb migrate` if it was written by an older ckb",
+                err
+            ))
+        })?;
+        Ok(SharedBuilder {
+            db: Some(db),
+            ..Default::default()
+        })
+    }
 }
 
 pub const MIN_TXS_VERIFY_CACHE_SIZE: Option<usize> = Some(100);
@@ -220,12 +563,40 @@ impl<DB: KeyValueDB> SharedBuilder<DB> {
         self
     }
 
+    /// Open the database even if its genesis block is not the one of the consensus, which
+    /// `build` rejects by default
+    pub fn skip_genesis_verification(mut self) -> Self {
+        self.verify_genesis = false;
+        self
+    }
+
+    /// Keep `build` from writing to the database: an empty database is rejected instead of
+    /// being initialized with the genesis block
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
     pub fn build(self) -> Result<Shared<ChainKVStore<DB>>, SharedError> {
-        let store = ChainKVStore::with_config(
-            self.db.unwrap(),
-            self.store_config.unwrap_or_else(Default::default),
-        );
+        let store_config = self.store_config.unwrap_or_else(Default::default);
+        let store = ChainKVStore::with_config(self.db.unwrap(), store_config);
         let consensus = self.consensus.unwrap_or_else(Consensus::default);
+        if self.verify_genesis {
+            // an empty database is initialized with the genesis of the consensus
+            if let Some(stored) = store.get_block_hash(0) {
+                if &stored != consensus.genesis_hash() {
+                    return Err(SharedError::GenesisMismatch {
+                        stored,
+                        expected: consensus.genesis_hash().to_owned(),
+                    });
+                }
+            }
+        }
+        if self.read_only && store.get_tip_header().is_none() {
+            return Err(SharedError::InvalidData(
+                "the database has no chain to read".to_owned(),
+            ));
+        }
         let tx_pool_config = self.tx_pool_config.unwrap_or_else(Default::default);
         let script_config = self.script_config.unwrap_or_else(Default::default);
         Shared::init(store, consensus, tx_pool_config, script_config)
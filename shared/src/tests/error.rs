@@ -0,0 +1,60 @@
+use crate::error::SharedError;
+use ckb_core::cell::UnresolvableError;
+use ckb_db::Error as DBError;
+use numext_fixed_hash::H256;
+
+#[test]
+fn test_rpc_error_code() {
+    let cases = vec![
+        (
+            SharedError::DB(DBError::DBError("db".to_owned())),
+            -32000,
+            true,
+        ),
+        (
+            SharedError::InvalidTransaction("invalid".to_owned()),
+            -32001,
+            false,
+        ),
+        (SharedError::MissingBlock(H256::zero()), -32002, true),
+        (SharedError::MissingTransaction(H256::zero()), -32003, true),
+        (
+            SharedError::UnresolvableTransaction(UnresolvableError::Empty),
+            -32004,
+            false,
+        ),
+        (SharedError::InvalidParentBlock, -32005, false),
+        (
+            SharedError::InvalidData("invalid".to_owned()),
+            -32006,
+            false,
+        ),
+        (
+            SharedError::ConsensusError("consensus".to_owned()),
+            -32007,
+            false,
+        ),
+        (SharedError::ArithmeticOverflow, -32008, false),
+        (
+            SharedError::ImmatureCellbase {
+                input_index: 0,
+                created_at: 1,
+                matures_at: 101,
+            },
+            -32009,
+            true,
+        ),
+        (
+            SharedError::GenesisMismatch {
+                stored: H256::zero(),
+                expected: H256::zero(),
+            },
+            -32010,
+            false,
+        ),
+    ];
+    for (error, code, retriable) in cases {
+        assert_eq!(error.rpc_error_code(), code, "{:?}", error);
+        assert_eq!(error.is_retriable(), retriable, "{:?}", error);
+    }
+}
@@ -1,2 +1,3 @@
+mod error;
 mod proposal_table;
 mod shared;
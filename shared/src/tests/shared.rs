@@ -1,8 +1,18 @@
-use crate::shared::{Shared, SharedBuilder};
-use ckb_core::{block::BlockBuilder, header::HeaderBuilder};
-use ckb_db::{KeyValueDB, MemoryKeyValueDB};
-use ckb_store::{ChainKVStore, ChainStore, StoreBatch};
-use ckb_traits::{BlockMedianTimeContext, ChainProvider};
+use crate::cell_set::CellSetDiff;
+use crate::error::SharedError;
+use crate::shared::{ChainInfo, Shared, SharedBuilder};
+use ckb_chain_spec::consensus::{Consensus, ProposalWindow};
+use ckb_core::extras::{BlockExt, DaoStats, EpochExt};
+use ckb_core::header::BlockNumber;
+use ckb_core::transaction::{CellInput, CellOutput, OutPoint, TransactionBuilder};
+use ckb_core::{block::BlockBuilder, header::HeaderBuilder, Bytes, Capacity};
+use ckb_db::{Col, DBConfig, Error as DBError, KeyValueDB, MemoryKeyValueDB, RocksDB};
+use ckb_store::{ChainKVStore, ChainStore, StoreBatch, COLUMNS};
+use ckb_traits::{verify_transaction_proof, BlockMedianTimeContext, ChainProvider};
+use numext_fixed_hash::H256;
+use numext_fixed_uint::U256;
+use occupied_capacity::{OccupiedCapacity, Ratio};
+use std::ops::Range;
 
 fn new_shared() -> Shared<ChainKVStore<MemoryKeyValueDB>> {
     SharedBuilder::<MemoryKeyValueDB>::new().build().unwrap()
@@ -50,3 +60,1028 @@ fn test_block_median_time() {
     let hash = shared.store().get_block_hash(block_number).unwrap();
     assert_eq!((&*chain_state).block_median_time(block_number, &hash), 17);
 }
+
+#[test]
+fn test_get_max_block_size_bytes() {
+    let shared = new_shared();
+    let store = shared.store();
+    let default_max_block_bytes = shared.consensus().max_block_bytes();
+    let genesis_epoch = shared.consensus().genesis_epoch_ext().to_owned();
+    assert_eq!(shared.get_max_block_size_bytes(0), default_max_block_bytes);
+
+    // block 1 closes the genesis epoch, blocks 2..=3 belong to an epoch with a smaller limit
+    let mut blocks = Vec::new();
+    let mut parent_hash = shared.genesis_hash().to_owned();
+    for number in 1..=3 {
+        let header = HeaderBuilder::default()
+            .parent_hash(parent_hash)
+            .number(number)
+            .build();
+        parent_hash = header.hash().to_owned();
+        blocks.push(BlockBuilder::default().header(header).build());
+    }
+    let epoch_index = blocks[0].header().hash().to_owned();
+    let mut epoch = EpochExt::new(
+        1,
+        Capacity::zero(),
+        Capacity::zero(),
+        epoch_index.clone(),
+        2,
+        2,
+        genesis_epoch.difficulty().to_owned(),
+    );
+    epoch.set_max_block_bytes(1_000);
+
+    let mut batch = store.new_batch().unwrap();
+    for block in &blocks {
+        let block_epoch_index = if block.header().number() < epoch.start_number() {
+            genesis_epoch.last_block_hash_in_previous_epoch()
+        } else {
+            &epoch_index
+        };
+        batch.insert_block(block).unwrap();
+        batch.attach_block(block).unwrap();
+        batch
+            .insert_block_epoch_index(block.header().hash(), block_epoch_index)
+            .unwrap();
+    }
+    batch.insert_epoch_ext(&epoch_index, &epoch).unwrap();
+    batch.insert_current_epoch_ext(&epoch).unwrap();
+    batch.commit().unwrap();
+
+    assert_eq!(shared.get_max_block_size_bytes(1), default_max_block_bytes);
+    assert_eq!(shared.get_max_block_size_bytes(2), 1_000);
+    assert_eq!(shared.get_max_block_size_bytes(3), 1_000);
+    // the next block is not stored yet, it falls into the current epoch
+    assert_eq!(shared.get_max_block_size_bytes(4), 1_000);
+}
+
+#[test]
+fn test_compute_chain_work_between() {
+    let shared = new_shared();
+    let store = shared.store();
+    let genesis_hash = shared.genesis_hash().to_owned();
+    let genesis_ext = store.get_block_ext(&genesis_hash).unwrap();
+
+    let header = HeaderBuilder::default()
+        .parent_hash(genesis_hash.clone())
+        .number(1)
+        .build();
+    let ext = BlockExt {
+        total_difficulty: genesis_ext.total_difficulty.to_owned() + &U256::from(1000u64),
+        ..Default::default()
+    };
+    let mut batch = store.new_batch().unwrap();
+    batch.insert_block_ext(header.hash(), &ext).unwrap();
+    batch.commit().unwrap();
+
+    assert_eq!(
+        shared.compute_chain_work_between(&genesis_hash, header.hash()),
+        Some(U256::from(1000u64))
+    );
+    assert_eq!(
+        shared.compute_chain_work_between(&genesis_hash, &genesis_hash),
+        Some(U256::zero())
+    );
+    // the end has less work than the start
+    assert_eq!(
+        shared.compute_chain_work_between(header.hash(), &genesis_hash),
+        None
+    );
+    assert_eq!(
+        shared.compute_chain_work_between(&genesis_hash, &H256::zero()),
+        None
+    );
+}
+
+// An empty database which fails every write
+struct FailingKeyValueDB;
+
+impl KeyValueDB for FailingKeyValueDB {
+    type Batch = <MemoryKeyValueDB as KeyValueDB>::Batch;
+
+    fn read(&self, _col: Col, _key: &[u8]) -> Result<Option<Vec<u8>>, DBError> {
+        Ok(None)
+    }
+
+    fn partial_read(
+        &self,
+        _col: Col,
+        _key: &[u8],
+        _range: &Range<usize>,
+    ) -> Result<Option<Vec<u8>>, DBError> {
+        Ok(None)
+    }
+
+    fn batch(&self) -> Result<Self::Batch, DBError> {
+        Err(DBError::DBError("write failure".to_owned()))
+    }
+
+    fn traverse<F>(&self, _col: Col, _callback: F) -> Result<(), DBError>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<(), DBError>,
+    {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_db_error_is_returned() {
+    let store = ChainKVStore::new(FailingKeyValueDB);
+    let result = Shared::init(
+        store,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    );
+    assert_eq!(
+        result.err(),
+        Some(SharedError::DB(DBError::DBError(
+            "write failure".to_owned()
+        )))
+    );
+}
+
+#[test]
+fn test_validate_transaction_cellbase_maturity() {
+    let shared = new_shared();
+    let store = shared.store();
+    let cellbase_maturity = shared.consensus().cellbase_maturity();
+
+    let cellbase = TransactionBuilder::default()
+        .input(CellInput::new_cellbase_input(1))
+        .output(CellOutput::default())
+        .build();
+    let header = HeaderBuilder::default()
+        .parent_hash(shared.genesis_hash().to_owned())
+        .number(1)
+        .build();
+    let block = BlockBuilder::default()
+        .header(header)
+        .transaction(cellbase.clone())
+        .build();
+    let mut batch = store.new_batch().unwrap();
+    batch.insert_block(&block).unwrap();
+    batch.attach_block(&block).unwrap();
+    batch.commit().unwrap();
+
+    let tx = TransactionBuilder::default()
+        .input(CellInput::new(OutPoint::null(), 0))
+        .input(CellInput::new(
+            OutPoint::new_cell(cellbase.hash().to_owned(), 0),
+            0,
+        ))
+        .build();
+    assert_eq!(
+        shared.validate_transaction_cellbase_maturity(&tx, cellbase_maturity),
+        Err(SharedError::ImmatureCellbase {
+            input_index: 1,
+            created_at: 1,
+            matures_at: 1 + cellbase_maturity,
+        })
+    );
+    assert_eq!(
+        shared.validate_transaction_cellbase_maturity(&tx, 1 + cellbase_maturity),
+        Ok(())
+    );
+}
+
+#[test]
+fn test_replay_chain() {
+    let shared = new_shared();
+    let store = shared.store();
+    let mut parent_hash = shared.genesis_hash().to_owned();
+    let mut batch = store.new_batch().unwrap();
+    for number in 1..=5 {
+        let header = HeaderBuilder::default()
+            .parent_hash(parent_hash)
+            .number(number)
+            .build();
+        parent_hash = header.hash().to_owned();
+        let transactions = (0..number)
+            .map(|index| {
+                TransactionBuilder::default()
+                    .input(CellInput::new(OutPoint::null(), number * 10 + index))
+                    .build()
+            })
+            .collect();
+        let block = BlockBuilder::default()
+            .header(header)
+            .transactions(transactions)
+            .build();
+        batch.insert_block(&block).unwrap();
+        batch
+            .insert_block_ext(&parent_hash, &Default::default())
+            .unwrap();
+        batch.attach_block(&block).unwrap();
+    }
+    batch.commit().unwrap();
+
+    let mut blocks = 0;
+    let mut transactions = 0;
+    shared.replay_chain(2, 4, |block, _ext| {
+        blocks += 1;
+        transactions += block.transactions().len();
+    });
+    assert_eq!(blocks, 3);
+    assert_eq!(transactions, 2 + 3 + 4);
+
+    // replaying stops at the tip
+    let mut numbers = Vec::new();
+    shared.replay_chain(4, 10, |block, _ext| numbers.push(block.header().number()));
+    assert_eq!(numbers, vec![4, 5]);
+}
+
+#[test]
+fn test_get_block_with_metadata() {
+    let shared = new_shared();
+    let store = shared.store();
+    let genesis_hash = shared.genesis_hash().to_owned();
+
+    let uncle = BlockBuilder::default()
+        .header_builder(HeaderBuilder::default().number(1).timestamp(1))
+        .build();
+    let transactions = (0..3)
+        .map(|index| {
+            TransactionBuilder::default()
+                .input(CellInput::new(OutPoint::null(), index))
+                .build()
+        })
+        .collect();
+    let block = BlockBuilder::default()
+        .header_builder(
+            HeaderBuilder::default()
+                .parent_hash(genesis_hash.clone())
+                .number(1),
+        )
+        .uncle(uncle.into())
+        .transactions(transactions)
+        .build();
+    let ext = BlockExt {
+        total_difficulty: U256::from(1000u64),
+        txs_fees: vec![
+            Capacity::zero(),
+            Capacity::shannons(10),
+            Capacity::shannons(20),
+        ],
+        ..Default::default()
+    };
+    let mut batch = store.new_batch().unwrap();
+    batch.insert_block(&block).unwrap();
+    batch.insert_block_ext(block.header().hash(), &ext).unwrap();
+    batch.commit().unwrap();
+
+    let metadata = shared
+        .get_block_with_metadata(block.header().hash())
+        .unwrap();
+    assert_eq!(metadata.block, block);
+    assert_eq!(metadata.total_difficulty, U256::from(1000u64));
+    assert_eq!(metadata.uncle_count, 1);
+    assert_eq!(metadata.tx_count, 3);
+    assert_eq!(metadata.fee, Capacity::shannons(30));
+
+    assert!(shared.get_block_with_metadata(&H256::zero()).is_none());
+}
+
+#[test]
+fn test_get_block_epoch_number() {
+    let shared = new_shared();
+    let genesis_hash = shared.genesis_hash().to_owned();
+    assert_eq!(shared.get_block_epoch_number(&genesis_hash), Some(0));
+
+    let header = HeaderBuilder::default()
+        .parent_hash(genesis_hash)
+        .number(1)
+        .epoch(1)
+        .build();
+    assert_eq!(shared.get_block_epoch_number(header.hash()), None);
+
+    let epoch_index = H256::from_slice(&[1; 32]).unwrap();
+    let epoch = EpochExt::new(
+        header.epoch(),
+        Capacity::zero(),
+        Capacity::zero(),
+        epoch_index.clone(),
+        1,
+        1,
+        U256::one(),
+    );
+    let mut batch = shared.store().new_batch().unwrap();
+    batch
+        .insert_block_epoch_index(header.hash(), &epoch_index)
+        .unwrap();
+    batch.insert_epoch_ext(&epoch_index, &epoch).unwrap();
+    batch.commit().unwrap();
+    assert_eq!(shared.get_block_epoch_number(header.hash()), Some(1));
+}
+
+#[test]
+fn test_calculate_secondary_issuance() {
+    let shared = new_shared();
+    let consensus = shared.consensus();
+    let epoch_reward = consensus.secondary_epoch_reward().as_u64();
+    let epoch_length = consensus.genesis_epoch_ext().length();
+    let base_issuance = Capacity::shannons(epoch_reward / epoch_length);
+    let total_supply = Capacity::shannons(1_000_000);
+
+    // zero occupied
+    assert_eq!(
+        shared.calculate_secondary_issuance(1, Capacity::zero(), total_supply),
+        Capacity::zero()
+    );
+    // fully occupied, more than the total supply is capped
+    assert_eq!(
+        shared.calculate_secondary_issuance(1, total_supply, total_supply),
+        base_issuance
+    );
+    assert_eq!(
+        shared.calculate_secondary_issuance(1, Capacity::shannons(2_000_000), total_supply),
+        base_issuance
+    );
+    assert_eq!(
+        shared.calculate_secondary_issuance(1, Capacity::shannons(250_000), total_supply),
+        Capacity::shannons(base_issuance.as_u64() / 4)
+    );
+    assert_eq!(
+        shared.calculate_secondary_issuance(1, Capacity::zero(), Capacity::zero()),
+        Capacity::zero()
+    );
+    // the first block of the epoch also gets the remainder of the epoch reward
+    assert_eq!(
+        shared.calculate_secondary_issuance(0, total_supply, total_supply),
+        Capacity::shannons(base_issuance.as_u64() + epoch_reward % epoch_length)
+    );
+
+    // `base_issuance * occupied` does not fit in u64
+    let total_supply = Capacity::shannons(u64::max_value());
+    let occupied = Capacity::shannons(u64::max_value() / 3);
+    let expected = u128::from(base_issuance.as_u64()) * u128::from(occupied.as_u64())
+        / u128::from(total_supply.as_u64());
+    assert_eq!(
+        shared.calculate_secondary_issuance(1, occupied, total_supply),
+        Capacity::shannons(expected as u64)
+    );
+}
+
+#[test]
+fn test_calculate_secondary_issuance_with_decay() {
+    let consensus = Consensus::default().set_secondary_issuance_decay(Ratio(1, 2));
+    let epoch_reward = consensus.secondary_epoch_reward();
+    assert_eq!(consensus.secondary_epoch_reward_at(0), epoch_reward);
+    assert_eq!(
+        consensus.secondary_epoch_reward_at(2),
+        Capacity::shannons(epoch_reward.as_u64() / 4)
+    );
+    let shared = SharedBuilder::<MemoryKeyValueDB>::new()
+        .consensus(consensus)
+        .build()
+        .unwrap();
+
+    let epoch = EpochExt::new(
+        2,
+        Capacity::zero(),
+        Capacity::zero(),
+        H256::zero(),
+        100,
+        10,
+        U256::one(),
+    );
+    let mut batch = shared.store().new_batch().unwrap();
+    batch.insert_current_epoch_ext(&epoch).unwrap();
+    batch.commit().unwrap();
+
+    let total_supply = Capacity::shannons(1_000);
+    assert_eq!(
+        shared.calculate_secondary_issuance(105, total_supply, total_supply),
+        Capacity::shannons(epoch_reward.as_u64() / 4 / 10)
+    );
+}
+
+#[test]
+fn test_build_with_invalid_consensus() {
+    let consensus = Consensus::default().set_epoch_reward(Capacity::zero());
+    let result = SharedBuilder::<MemoryKeyValueDB>::new()
+        .consensus(consensus)
+        .build();
+    assert_eq!(
+        result.err(),
+        Some(SharedError::ConsensusError(
+            "Consensus: epoch_reward must be positive".to_owned()
+        ))
+    );
+}
+
+#[test]
+fn test_is_in_proposal_window() {
+    let consensus = Consensus::default().set_tx_proposal_window(ProposalWindow(2, 10));
+    let shared = SharedBuilder::<MemoryKeyValueDB>::new()
+        .consensus(consensus)
+        .build()
+        .unwrap();
+    assert_eq!(shared.get_proposal_window(), (2, 10));
+
+    assert!(!shared.is_in_proposal_window(13, 14));
+    assert!(shared.is_in_proposal_window(13, 15));
+    assert!(shared.is_in_proposal_window(13, 23));
+    assert!(!shared.is_in_proposal_window(13, 24));
+    // committed before proposed
+    assert!(!shared.is_in_proposal_window(13, 12));
+    assert!(!shared.is_in_proposal_window(BlockNumber::max_value(), 0));
+}
+
+#[test]
+fn test_get_chain_info() {
+    let shared = new_shared();
+    let genesis = shared.consensus().genesis_block().header().to_owned();
+    assert_eq!(
+        shared.get_chain_info(),
+        ChainInfo {
+            tip_hash: genesis.hash().to_owned(),
+            tip_number: 0,
+            total_difficulty: genesis.difficulty().to_owned(),
+            is_initial_block_download: true,
+        }
+    );
+
+    let now = faketime::unix_time_as_millis();
+    let header = HeaderBuilder::default()
+        .parent_hash(genesis.hash().to_owned())
+        .number(1)
+        .timestamp(now)
+        .build();
+    let total_difficulty = genesis.difficulty() + header.difficulty();
+    shared
+        .lock_chain_state()
+        .update_tip(
+            header.clone(),
+            total_difficulty.clone(),
+            CellSetDiff::default(),
+        )
+        .unwrap();
+    assert_eq!(
+        shared.get_chain_info(),
+        ChainInfo {
+            tip_hash: header.hash().to_owned(),
+            tip_number: 1,
+            total_difficulty,
+            is_initial_block_download: false,
+        }
+    );
+}
+
+#[test]
+fn test_get_block_size() {
+    let shared = new_shared();
+    let proof_size = shared.consensus().pow_engine().proof_size();
+    let genesis = shared.consensus().genesis_block().to_owned();
+
+    let mut blocks = vec![genesis];
+    for (number, tx_count) in [1u64, 3, 2].iter().enumerate() {
+        let transactions = (0..*tx_count)
+            .map(|index| {
+                TransactionBuilder::default()
+                    .input(CellInput::new(OutPoint::null(), index))
+                    .build()
+            })
+            .collect();
+        let block = BlockBuilder::default()
+            .header_builder(
+                HeaderBuilder::default()
+                    .parent_hash(blocks.last().unwrap().header().hash().to_owned())
+                    .number(number as u64 + 1),
+            )
+            .transactions(transactions)
+            .build();
+        let ext = BlockExt {
+            size: block.serialized_size(proof_size) as u32,
+            ..Default::default()
+        };
+        let mut batch = shared.store().new_batch().unwrap();
+        batch.insert_block(&block).unwrap();
+        batch.insert_block_ext(block.header().hash(), &ext).unwrap();
+        batch.commit().unwrap();
+        blocks.push(block);
+    }
+    let sizes: Vec<u64> = blocks
+        .iter()
+        .map(|block| block.serialized_size(proof_size) as u64)
+        .collect();
+    let tip_hash = blocks[3].header().hash();
+
+    for (block, size) in blocks.iter().zip(sizes.iter()) {
+        assert_eq!(
+            shared.get_block_size(block.header().hash()),
+            Some(*size as usize)
+        );
+    }
+    assert_eq!(shared.get_block_size(&H256::zero()), None);
+
+    assert_eq!(shared.get_block_median_size(tip_hash, 1), Some(sizes[3]));
+    // blocks with 2, 3 and 1 transactions
+    assert_eq!(shared.get_block_median_size(tip_hash, 3), Some(sizes[3]));
+    let mut all = sizes.clone();
+    all.sort();
+    assert_eq!(shared.get_block_median_size(tip_hash, 10), Some(all[2]));
+    assert_eq!(shared.get_block_median_size(tip_hash, 0), None);
+    assert_eq!(shared.get_block_median_size(&H256::zero(), 3), None);
+}
+
+#[test]
+fn test_snapshot() {
+    let shared = new_shared();
+    let genesis = shared.consensus().genesis_block().header().to_owned();
+    let snapshot = shared.snapshot();
+
+    let header = HeaderBuilder::default()
+        .parent_hash(genesis.hash().to_owned())
+        .number(1)
+        .build();
+    let mut batch = shared.store().new_batch().unwrap();
+    batch
+        .insert_block(&BlockBuilder::default().header(header.clone()).build())
+        .unwrap();
+    batch.commit().unwrap();
+    let total_difficulty = genesis.difficulty() + header.difficulty();
+    shared
+        .lock_chain_state()
+        .update_tip(header.clone(), total_difficulty, CellSetDiff::default())
+        .unwrap();
+    assert_eq!(shared.get_chain_info().tip_hash, *header.hash());
+
+    assert_eq!(snapshot.tip_header(), &genesis);
+    assert_eq!(snapshot.total_difficulty(), genesis.difficulty());
+    assert_eq!(snapshot.get_block_hash(0), Some(genesis.hash().to_owned()));
+    assert_eq!(snapshot.get_block_hash(1), None);
+    assert_eq!(
+        shared.snapshot().get_block_hash(1),
+        Some(header.hash().to_owned())
+    );
+}
+
+#[test]
+fn test_from_existing() {
+    let tmp_dir = tempfile::Builder::new()
+        .prefix("from_existing")
+        .tempdir()
+        .unwrap();
+    let config = DBConfig {
+        path: tmp_dir.path().to_path_buf(),
+        ..Default::default()
+    };
+
+    // a fresh database is initialized with the genesis block
+    let genesis_hash = {
+        let shared = SharedBuilder::from_existing(&config)
+            .unwrap()
+            .build()
+            .unwrap();
+        shared.genesis_hash().to_owned()
+    };
+
+    {
+        let shared = SharedBuilder::from_existing(&config)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(shared.lock_chain_state().tip_header().hash(), &genesis_hash);
+
+        // move the tip to a block missing in the index
+        let header = HeaderBuilder::default()
+            .parent_hash(genesis_hash.clone())
+            .number(1)
+            .build();
+        let mut batch = shared.store().new_batch().unwrap();
+        batch
+            .insert_block(&BlockBuilder::default().header(header.clone()).build())
+            .unwrap();
+        batch.insert_tip_header(&header).unwrap();
+        batch.commit().unwrap();
+    }
+    let result = SharedBuilder::from_existing(&config).and_then(|builder| builder.build());
+    assert!(match result {
+        Err(SharedError::InvalidData(_)) => true,
+        _ => false,
+    });
+}
+
+#[test]
+fn test_read_only() {
+    let builder = SharedBuilder::<MemoryKeyValueDB>::new().read_only();
+    assert!(match builder.build() {
+        Err(SharedError::InvalidData(_)) => true,
+        _ => false,
+    });
+
+    let tmp_dir = tempfile::Builder::new()
+        .prefix("read_only")
+        .tempdir()
+        .unwrap();
+    let config = DBConfig {
+        path: tmp_dir.path().to_path_buf(),
+        ..Default::default()
+    };
+    let genesis_hash = {
+        let shared = SharedBuilder::from_existing(&config)
+            .unwrap()
+            .build()
+            .unwrap();
+        shared.genesis_hash().to_owned()
+    };
+    let shared = SharedBuilder::from_existing(&config)
+        .unwrap()
+        .read_only()
+        .build()
+        .unwrap();
+    assert_eq!(shared.lock_chain_state().tip_header().hash(), &genesis_hash);
+}
+
+#[test]
+fn test_from_existing_with_other_version() {
+    let tmp_dir = tempfile::Builder::new()
+        .prefix("from_existing_with_other_version")
+        .tempdir()
+        .unwrap();
+    let config = DBConfig {
+        path: tmp_dir.path().to_path_buf(),
+        ..Default::default()
+    };
+    RocksDB::open(&config, COLUMNS)
+        .set_version("0.1400.0")
+        .unwrap();
+    assert!(match SharedBuilder::from_existing(&config) {
+        Err(SharedError::InvalidData(_)) => true,
+        _ => false,
+    });
+}
+
+#[test]
+fn test_genesis_verification() {
+    let tmp_dir = tempfile::Builder::new()
+        .prefix("genesis_verification")
+        .tempdir()
+        .unwrap();
+    let config = DBConfig {
+        path: tmp_dir.path().to_path_buf(),
+        ..Default::default()
+    };
+    let consensus = Consensus::default();
+    let genesis = consensus.genesis_block().to_owned();
+    let other_consensus = Consensus::default().set_genesis_block(
+        BlockBuilder::from_block(genesis.clone())
+            .header_builder(
+                HeaderBuilder::from_header(genesis.header().to_owned())
+                    .timestamp(genesis.header().timestamp() + 1),
+            )
+            .build(),
+    );
+
+    let build = |consensus: &Consensus, verify_genesis: bool| {
+        let builder = SharedBuilder::from_existing(&config)
+            .unwrap()
+            .consensus(consensus.to_owned());
+        if verify_genesis {
+            builder.build()
+        } else {
+            builder.skip_genesis_verification().build()
+        }
+    };
+
+    // a fresh database is initialized with the genesis block, which matches on reopen
+    assert!(build(&consensus, true).is_ok());
+    assert_eq!(
+        build(&consensus, true).unwrap().genesis_hash(),
+        genesis.header().hash()
+    );
+
+    assert_eq!(
+        build(&other_consensus, true).err(),
+        Some(SharedError::GenesisMismatch {
+            stored: genesis.header().hash().to_owned(),
+            expected: other_consensus.genesis_hash().to_owned(),
+        })
+    );
+    assert!(build(&other_consensus, false).is_ok());
+}
+
+#[test]
+fn test_get_uncle_rate() {
+    let shared = new_shared();
+    let store = shared.store();
+    let mut parent = shared.consensus().genesis_block().header().to_owned();
+
+    let mut headers = Vec::new();
+    for (number, total_uncles_count) in [1u64, 1, 3, 4].iter().enumerate() {
+        let header = HeaderBuilder::default()
+            .parent_hash(parent.hash().to_owned())
+            .number(number as u64 + 1)
+            .build();
+        let ext = BlockExt {
+            total_uncles_count: *total_uncles_count,
+            ..Default::default()
+        };
+        let mut batch = store.new_batch().unwrap();
+        batch
+            .insert_block(&BlockBuilder::default().header(header.clone()).build())
+            .unwrap();
+        batch.insert_block_ext(header.hash(), &ext).unwrap();
+        batch.commit().unwrap();
+        headers.push(header.clone());
+        parent = header;
+    }
+    let tip = &headers[3];
+
+    assert_eq!(shared.get_uncle_rate(tip, 1), 1000);
+    assert_eq!(shared.get_uncle_rate(tip, 2), 1500);
+    assert_eq!(shared.get_uncle_rate(tip, 3), 1000);
+    assert_eq!(shared.get_uncle_rate(tip, 4), 1000);
+    assert_eq!(shared.get_uncle_rate(&headers[1], 1), 0);
+    // the window is clamped to the blocks after the genesis
+    assert_eq!(shared.get_uncle_rate(tip, 1000), 1000);
+    assert_eq!(shared.get_uncle_rate(&headers[1], 1000), 500);
+    assert_eq!(shared.get_uncle_rate(tip, 0), 0);
+}
+
+#[test]
+fn test_calculate_dao_compensation() {
+    let shared = new_shared();
+    let store = shared.store();
+    let mut parent = shared.consensus().genesis_block().header().to_owned();
+
+    let rates = [
+        10_000_000_000_000_000u64,
+        10_000_000_000_123_456,
+        10_500_000_000_000_000,
+    ];
+    let mut batch = store.new_batch().unwrap();
+    for (number, rate) in rates.iter().enumerate() {
+        let header = HeaderBuilder::default()
+            .parent_hash(parent.hash().to_owned())
+            .number(number as u64 + 1)
+            .build();
+        let block = BlockBuilder::default().header(header.clone()).build();
+        let ext = BlockExt {
+            dao_stats: DaoStats {
+                accumulated_rate: *rate,
+                accumulated_capacity: 0,
+            },
+            ..Default::default()
+        };
+        batch.insert_block(&block).unwrap();
+        batch.insert_block_ext(header.hash(), &ext).unwrap();
+        batch.attach_block(&block).unwrap();
+        parent = header;
+    }
+    batch.commit().unwrap();
+
+    assert_eq!(shared.get_accumulated_rate(2), Some(rates[1]));
+    assert_eq!(shared.get_accumulated_rate(4), None);
+
+    // only the capacity above the occupied one earns the compensation
+    let output = CellOutput {
+        capacity: Capacity::bytes(1000).unwrap(),
+        ..Default::default()
+    };
+    let counted = output
+        .capacity
+        .safe_sub(output.occupied_capacity().unwrap())
+        .unwrap();
+    assert_eq!(
+        shared.calculate_dao_compensation(1, 3, &output).unwrap(),
+        Capacity::shannons(counted.as_u64() / 20)
+    );
+    assert_eq!(
+        shared.calculate_dao_compensation(1, 2, &output).unwrap(),
+        Capacity::shannons(1)
+    );
+    assert_eq!(
+        shared.calculate_dao_compensation(2, 2, &output).unwrap(),
+        Capacity::zero()
+    );
+    assert!(match shared.calculate_dao_compensation(3, 1, &output) {
+        Err(SharedError::InvalidData(_)) => true,
+        _ => false,
+    });
+    assert!(match shared.calculate_dao_compensation(1, 4, &output) {
+        Err(SharedError::InvalidData(_)) => true,
+        _ => false,
+    });
+}
+
+#[test]
+fn test_get_median_transaction_fee() {
+    let shared = new_shared();
+    let store = shared.store();
+    assert_eq!(shared.get_median_transaction_fee(10), None);
+    assert_eq!(shared.get_fee_rate_percentile(50, 10), None);
+
+    let mut parent = shared.consensus().genesis_block().header().to_owned();
+    let mut tx_size = 0;
+    let mut batch = store.new_batch().unwrap();
+    for (number, fees) in [vec![5u64, 1], vec![], vec![30, 100, 20]]
+        .iter()
+        .enumerate()
+    {
+        let number = number as u64 + 1;
+        let transactions: Vec<_> = (0..=fees.len())
+            .map(|index| {
+                let builder = if index == 0 {
+                    TransactionBuilder::default().input(CellInput::new_cellbase_input(number))
+                } else {
+                    TransactionBuilder::default()
+                };
+                builder
+                    .witness(vec![Bytes::from(vec![number as u8, index as u8])])
+                    .build()
+            })
+            .collect();
+        if let Some(tx) = transactions.get(1) {
+            tx_size = tx.serialized_size();
+        }
+        let block = BlockBuilder::default()
+            .header_builder(
+                HeaderBuilder::default()
+                    .parent_hash(parent.hash().to_owned())
+                    .number(number),
+            )
+            .transactions(transactions)
+            .build();
+        let ext = BlockExt {
+            txs_fees: fees.iter().map(|fee| Capacity::shannons(*fee)).collect(),
+            ..Default::default()
+        };
+        batch.insert_block(&block).unwrap();
+        batch.insert_block_ext(block.header().hash(), &ext).unwrap();
+        batch.attach_block(&block).unwrap();
+        batch.insert_tip_header(block.header()).unwrap();
+        parent = block.header().to_owned();
+    }
+    batch.commit().unwrap();
+
+    assert_eq!(
+        shared.get_median_transaction_fee(3),
+        Some(Capacity::shannons(20))
+    );
+    assert_eq!(
+        shared.get_median_transaction_fee(1),
+        Some(Capacity::shannons(30))
+    );
+    assert_eq!(shared.get_median_transaction_fee(0), None);
+
+    let rate = |fee: u64| fee * 1000 / tx_size as u64;
+    assert_eq!(shared.get_fee_rate_percentile(0, 3), Some(rate(1)));
+    assert_eq!(shared.get_fee_rate_percentile(50, 3), Some(rate(20)));
+    assert_eq!(shared.get_fee_rate_percentile(100, 3), Some(rate(100)));
+    assert_eq!(shared.get_fee_rate_percentile(101, 3), None);
+}
+
+#[test]
+fn test_get_transaction_proof() {
+    let shared = new_shared();
+    let store = shared.store();
+    let mut parent = shared.consensus().genesis_block().header().to_owned();
+
+    // block 1 commits 4 transactions, block 2 only its cellbase
+    let mut blocks = Vec::new();
+    for txs_count in [4u8, 1].iter() {
+        let number = parent.number() + 1;
+        let transactions: Vec<_> = (0..*txs_count)
+            .map(|index| {
+                let builder = if index == 0 {
+                    TransactionBuilder::default().input(CellInput::new_cellbase_input(number))
+                } else {
+                    TransactionBuilder::default()
+                };
+                builder
+                    .witness(vec![Bytes::from(vec![number as u8, index])])
+                    .build()
+            })
+            .collect();
+        let block = BlockBuilder::default()
+            .header_builder(
+                HeaderBuilder::default()
+                    .parent_hash(parent.hash().to_owned())
+                    .number(number),
+            )
+            .transactions(transactions)
+            .build();
+        let mut batch = store.new_batch().unwrap();
+        batch.insert_block(&block).unwrap();
+        batch.attach_block(&block).unwrap();
+        batch.commit().unwrap();
+        parent = block.header().to_owned();
+        blocks.push(block);
+    }
+
+    let assert_proof = |block: &ckb_core::block::Block, tx_index: usize| {
+        let tx_hash = block.transactions()[tx_index].hash();
+        let proof = shared.get_transaction_proof(tx_hash).unwrap();
+        assert_eq!(&proof.block_hash, block.header().hash());
+        assert_eq!(proof.tx_index as usize, tx_index);
+        assert_eq!(
+            proof.transactions_count as usize,
+            block.transactions().len()
+        );
+        let root = block.header().transactions_root();
+        assert!(verify_transaction_proof(&proof, tx_hash, root));
+        assert!(!verify_transaction_proof(&proof, &H256::zero(), root));
+        assert!(!verify_transaction_proof(&proof, tx_hash, &H256::zero()));
+        proof
+    };
+    let first = assert_proof(&blocks[0], 0);
+    let last = assert_proof(&blocks[0], 3);
+    assert_ne!(first.merkle_siblings, last.merkle_siblings);
+    let single = assert_proof(&blocks[1], 0);
+    assert!(single.merkle_siblings.is_empty());
+
+    assert_eq!(shared.get_transaction_proof(&H256::zero()), None);
+}
+
+#[test]
+fn test_block_headers_range() {
+    let shared = new_shared();
+    let store = shared.store();
+    let genesis = shared.consensus().genesis_block().header().to_owned();
+    // the snapshot pins the genesis as the tip
+    let snapshot = shared.snapshot();
+
+    let mut headers = vec![genesis.clone()];
+    for number in 1..=3 {
+        let header = HeaderBuilder::default()
+            .parent_hash(headers[headers.len() - 1].hash().to_owned())
+            .number(number)
+            .build();
+        let block = BlockBuilder::default().header(header.clone()).build();
+        let mut batch = store.new_batch().unwrap();
+        batch.insert_block(&block).unwrap();
+        batch.attach_block(&block).unwrap();
+        batch.insert_tip_header(&header).unwrap();
+        batch.commit().unwrap();
+        headers.push(header);
+    }
+
+    assert!(shared.block_headers_range(2, 1).is_empty());
+    assert_eq!(shared.block_headers_range(0, 3), headers);
+    assert_eq!(shared.block_headers_range(1, 1), headers[1..2].to_vec());
+    assert_eq!(shared.block_headers_range(2, 10), headers[2..].to_vec());
+    assert!(shared.block_headers_range(4, 10).is_empty());
+    assert_eq!(shared.block_headers_range(0, std::u64::MAX), headers);
+
+    assert_eq!(snapshot.block_headers_range(0, 3), vec![genesis]);
+    assert!(snapshot.block_headers_range(1, 3).is_empty());
+}
+
+#[test]
+fn test_snapshot_defaults() {
+    let shared = new_shared();
+    let store = shared.store();
+    let genesis = shared.consensus().genesis_block().header().to_owned();
+    let snapshot = shared.snapshot();
+
+    let transactions = vec![
+        TransactionBuilder::default()
+            .input(CellInput::new_cellbase_input(1))
+            .build(),
+        TransactionBuilder::default()
+            .witness(vec![Bytes::from(vec![1])])
+            .build(),
+    ];
+    let tx_hash = transactions[1].hash().to_owned();
+    let block = BlockBuilder::default()
+        .header_builder(
+            HeaderBuilder::default()
+                .parent_hash(genesis.hash().to_owned())
+                .number(1),
+        )
+        .transactions(transactions)
+        .build();
+    let ext = BlockExt {
+        dao_stats: DaoStats {
+            accumulated_rate: 10_000_000_000_123_456,
+            accumulated_capacity: 0,
+        },
+        txs_fees: vec![Capacity::shannons(5)],
+        ..Default::default()
+    };
+    let mut batch = store.new_batch().unwrap();
+    batch.insert_block(&block).unwrap();
+    batch.insert_block_ext(block.header().hash(), &ext).unwrap();
+    batch.attach_block(&block).unwrap();
+    batch.insert_tip_header(block.header()).unwrap();
+    batch.commit().unwrap();
+
+    assert_eq!(shared.get_accumulated_rate(1), Some(10_000_000_000_123_456));
+    assert_eq!(snapshot.get_accumulated_rate(1), None);
+
+    assert_eq!(
+        shared.get_median_transaction_fee(10),
+        Some(Capacity::shannons(5))
+    );
+    assert_eq!(snapshot.get_median_transaction_fee(10), None);
+
+    assert!(shared.get_transaction_proof(&tx_hash).is_some());
+    assert_eq!(snapshot.get_transaction_proof(&tx_hash), None);
+
+    let mut numbers = Vec::new();
+    snapshot.replay_chain(0, 1, |block, _ext| numbers.push(block.header().number()));
+    assert_eq!(numbers, vec![0]);
+}
@@ -1,14 +1,17 @@
 use ckb_core::block::{Block, BlockBuilder};
-use ckb_core::extras::EpochExt;
+use ckb_core::extras::{EpochExt, DEFAULT_MAX_BLOCK_BYTES};
 use ckb_core::header::Header;
 use ckb_core::header::HeaderBuilder;
 use ckb_core::script::Script;
-use ckb_core::{capacity_bytes, BlockNumber, Capacity, Cycle, Version};
+use ckb_core::{capacity_bytes, BlockNumber, Capacity, Cycle, EpochNumber, Version};
 use ckb_pow::{Pow, PowEngine};
 use numext_fixed_hash::H256;
 use numext_fixed_uint::U256;
 use occupied_capacity::Ratio;
+use serde_derive::{Deserialize, Serialize};
 use std::cmp;
+use std::error::Error;
+use std::fmt;
 use std::sync::Arc;
 
 // TODO: add secondary reward for miner
@@ -34,13 +37,19 @@ pub(crate) const MIN_EPOCH_LENGTH: u64 = EPOCH_DURATION_TARGET / MAX_BLOCK_INTER
 // can divide DEFAULT_EPOCH_REWARD.
 pub(crate) const GENESIS_EPOCH_LENGTH: u64 = 1_250;
 
-pub(crate) const MAX_BLOCK_BYTES: u64 = 2_000_000; // 2mb
+pub(crate) const MAX_BLOCK_BYTES: u64 = DEFAULT_MAX_BLOCK_BYTES;
 pub(crate) const MAX_BLOCK_CYCLES: u64 = TWO_IN_TWO_OUT_CYCLES * 200 * 8;
 pub(crate) const MAX_BLOCK_PROPOSALS_LIMIT: u64 = 3_000;
 pub(crate) const BLOCK_VERSION: u32 = 0;
 pub(crate) const PROPOSER_REWARD_RATIO: Ratio = Ratio(4, 10);
-
-#[derive(Clone, PartialEq, Debug, Eq, Copy)]
+// The secondary issuance is not decayed by default
+pub(crate) const SECONDARY_ISSUANCE_DECAY: Ratio = Ratio(1, 1);
+// Including uncles brings no bonus by default
+pub(crate) const UNCLE_INCLUSION_BONUS_RATIO: Ratio = Ratio(0, 1);
+// The block reward is not capped by default
+pub(crate) const MAX_BLOCK_REWARD: Capacity = Capacity::shannons(u64::max_value());
+
+#[derive(Clone, PartialEq, Debug, Eq, Copy, Serialize, Deserialize)]
 pub struct ProposalWindow(pub BlockNumber, pub BlockNumber);
 
 /// Two protocol parameters w_close and w_far define the closest
@@ -75,6 +84,50 @@ impl ProposalWindow {
     }
 }
 
+/// Inconsistent consensus parameters, which would make the chain panic or stall at runtime
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ConsensusError {
+    ZeroOrphanRateTargetRecip,
+    ZeroEpochDurationTarget,
+    ZeroMinDifficulty,
+    ZeroEpochReward,
+    InvalidProposalWindow(ProposalWindow),
+    InvalidProposerRewardRatio(Ratio),
+}
+
+impl Error for ConsensusError {}
+
+impl fmt::Display for ConsensusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConsensusError::ZeroOrphanRateTargetRecip => {
+                write!(f, "Consensus: orphan_rate_target_recip must be positive")
+            }
+            ConsensusError::ZeroEpochDurationTarget => {
+                write!(f, "Consensus: epoch_duration_target must be positive")
+            }
+            ConsensusError::ZeroMinDifficulty => {
+                write!(f, "Consensus: genesis difficulty must be positive")
+            }
+            ConsensusError::ZeroEpochReward => {
+                write!(f, "Consensus: epoch_reward must be positive")
+            }
+            ConsensusError::InvalidProposalWindow(window) => write!(
+                f,
+                "Consensus: tx_proposal_window closest {} is greater than farthest {}",
+                window.closest(),
+                window.farthest()
+            ),
+            ConsensusError::InvalidProposerRewardRatio(ratio) => write!(
+                f,
+                "Consensus: proposer_reward_ratio {}/{} is not in [0, 1]",
+                ratio.numer(),
+                ratio.denom()
+            ),
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Debug)]
 pub struct Consensus {
     pub id: String,
@@ -82,11 +135,17 @@ pub struct Consensus {
     pub genesis_hash: H256,
     pub epoch_reward: Capacity,
     pub secondary_epoch_reward: Capacity,
+    // The secondary issuance of each epoch is this ratio of the previous epoch's
+    pub secondary_issuance_decay: Ratio,
     pub max_uncles_num: usize,
     pub orphan_rate_target_recip: u64,
     pub epoch_duration_target: u64,
     pub tx_proposal_window: ProposalWindow,
     pub proposer_reward_ratio: Ratio,
+    // The miner gets this ratio of the base block reward for each uncle included in its block
+    pub uncle_inclusion_bonus_ratio: Ratio,
+    // Upper bound of the total reward of a block
+    pub max_block_reward: Capacity,
     pub pow: Pow,
     // For each input, if the referenced output transaction is cellbase,
     // it must have at least `cellbase_maturity` confirmations;
@@ -106,6 +165,34 @@ pub struct Consensus {
     pub bootstrap_lock: Script,
 }
 
+/// A serializable snapshot of `Consensus`, which is used to share the parameters of a chain.
+///
+/// The genesis hash is not included, it is always derived from the genesis block.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ConsensusSpec {
+    pub id: String,
+    pub genesis_block: Block,
+    pub epoch_reward: Capacity,
+    pub secondary_epoch_reward: Capacity,
+    pub secondary_issuance_decay: Ratio,
+    pub max_uncles_num: usize,
+    pub orphan_rate_target_recip: u64,
+    pub epoch_duration_target: u64,
+    pub tx_proposal_window: ProposalWindow,
+    pub proposer_reward_ratio: Ratio,
+    pub uncle_inclusion_bonus_ratio: Ratio,
+    pub max_block_reward: Capacity,
+    pub pow: Pow,
+    pub cellbase_maturity: BlockNumber,
+    pub median_time_block_count: usize,
+    pub max_block_cycles: Cycle,
+    pub max_block_bytes: u64,
+    pub block_version: Version,
+    pub max_block_proposals_limit: u64,
+    pub genesis_epoch_ext: EpochExt,
+    pub bootstrap_lock: Script,
+}
+
 // genesis difficulty should not be zero
 impl Default for Consensus {
     fn default() -> Self {
@@ -136,6 +223,7 @@ impl Default for Consensus {
             orphan_rate_target_recip: ORPHAN_RATE_TARGET_RECIP,
             epoch_duration_target: EPOCH_DURATION_TARGET,
             secondary_epoch_reward: DEFAULT_SECONDARY_EPOCH_REWARD,
+            secondary_issuance_decay: SECONDARY_ISSUANCE_DECAY,
             tx_proposal_window: TX_PROPOSAL_WINDOW,
             pow: Pow::Dummy,
             cellbase_maturity: CELLBASE_MATURITY,
@@ -145,6 +233,8 @@ impl Default for Consensus {
             genesis_epoch_ext,
             block_version: BLOCK_VERSION,
             proposer_reward_ratio: PROPOSER_REWARD_RATIO,
+            uncle_inclusion_bonus_ratio: UNCLE_INCLUSION_BONUS_RATIO,
+            max_block_reward: MAX_BLOCK_REWARD,
             max_block_proposals_limit: MAX_BLOCK_PROPOSALS_LIMIT,
             bootstrap_lock: Default::default(),
         }
@@ -183,6 +273,23 @@ impl Consensus {
     }
 
     #[must_use]
+    pub fn set_secondary_issuance_decay(mut self, secondary_issuance_decay: Ratio) -> Self {
+        self.secondary_issuance_decay = secondary_issuance_decay;
+        self
+    }
+
+    #[must_use]
+    pub fn set_uncle_inclusion_bonus_ratio(mut self, uncle_inclusion_bonus_ratio: Ratio) -> Self {
+        self.uncle_inclusion_bonus_ratio = uncle_inclusion_bonus_ratio;
+        self
+    }
+
+    #[must_use]
+    pub fn set_max_block_reward(mut self, max_block_reward: Capacity) -> Self {
+        self.max_block_reward = max_block_reward;
+        self
+    }
+
     pub fn set_max_block_cycles(mut self, max_block_cycles: Cycle) -> Self {
         self.max_block_cycles = max_block_cycles;
         self
@@ -222,6 +329,14 @@ impl Consensus {
         self.proposer_reward_ratio
     }
 
+    pub fn uncle_inclusion_bonus_ratio(&self) -> Ratio {
+        self.uncle_inclusion_bonus_ratio
+    }
+
+    pub fn max_block_reward(&self) -> Capacity {
+        self.max_block_reward
+    }
+
     pub fn reserve_number(&self) -> BlockNumber {
         self.finalization_delay_length()
     }
@@ -275,6 +390,29 @@ impl Consensus {
         self.secondary_epoch_reward
     }
 
+    pub fn secondary_issuance_decay(&self) -> Ratio {
+        self.secondary_issuance_decay
+    }
+
+    /// The secondary epoch reward of the epoch, decayed once per epoch by
+    /// `secondary_issuance_decay`
+    pub fn secondary_epoch_reward_at(&self, epoch_number: EpochNumber) -> Capacity {
+        let decay = self.secondary_issuance_decay;
+        let mut reward = self.secondary_epoch_reward;
+        if decay.numer() == decay.denom() {
+            return reward;
+        }
+        for _ in 0..epoch_number {
+            if reward == Capacity::zero() {
+                break;
+            }
+            let shannons = u128::from(reward.as_u64()) * u128::from(decay.numer())
+                / u128::from(cmp::max(decay.denom(), 1));
+            reward = Capacity::shannons(cmp::min(shannons, u128::from(u64::max_value())) as u64);
+        }
+        reward
+    }
+
     pub fn orphan_rate_target_recip(&self) -> u64 {
         self.orphan_rate_target_recip
     }
@@ -319,7 +457,9 @@ impl Consensus {
 
     pub fn revision_epoch_difficulty(&self, last: U256, raw: U256) -> U256 {
         let min_difficulty = cmp::max(self.min_difficulty().clone(), &last / 2u64);
-        let max_difficulty = last * 2u32;
+        let max_difficulty = last
+            .checked_mul(&U256::from(2u32))
+            .unwrap_or_else(U256::max_value);
 
         if raw > max_difficulty {
             return max_difficulty;
@@ -367,7 +507,7 @@ impl Consensus {
 
         let last_uncles_count = last_total_uncles_count - start_total_uncles_count;
 
-        let epoch_ext = if last_uncles_count > 0 {
+        let mut epoch_ext = if last_uncles_count > 0 {
             let last_epoch_duration = header
                 .timestamp()
                 .saturating_sub(last_block_header_in_previous_epoch.timestamp());
@@ -375,15 +515,25 @@ impl Consensus {
                 return None;
             }
 
-            let numerator =
-                (last_uncles_count + last_epoch_length) * epoch_duration_target * last_epoch_length;
-            let denominator = (target_recip + 1) * last_uncles_count * last_epoch_duration;
-            let raw_next_epoch_length = numerator / denominator;
+            // Pathologically large uncle counts may overflow the intermediate products,
+            // fall back to the last epoch's length and difficulty instead of wrapping.
+            let numerator = last_uncles_count
+                .checked_add(last_epoch_length)
+                .and_then(|n| n.checked_mul(epoch_duration_target))
+                .and_then(|n| n.checked_mul(last_epoch_length));
+            let denominator = (target_recip + 1)
+                .checked_mul(last_uncles_count)
+                .and_then(|d| d.checked_mul(last_epoch_duration));
+            let raw_next_epoch_length = numerator
+                .and_then(|n| denominator.and_then(|d| n.checked_div(d)))
+                .unwrap_or(last_epoch_length);
             let next_epoch_length = self.revision_epoch_length(raw_next_epoch_length);
 
-            let raw_difficulty =
-                last_difficulty * U256::from(last_uncles_count) * U256::from(target_recip)
-                    / U256::from(last_epoch_length);
+            let raw_difficulty = last_difficulty
+                .checked_mul(&U256::from(last_uncles_count))
+                .and_then(|d| d.checked_mul(&U256::from(target_recip)))
+                .and_then(|d| d.checked_div(&U256::from(last_epoch_length)))
+                .unwrap_or_else(|| last_difficulty.clone());
 
             let difficulty =
                 self.revision_epoch_difficulty(last_difficulty.clone(), raw_difficulty);
@@ -418,12 +568,220 @@ impl Consensus {
                 difficulty                  // difficulty,
             )
         };
+        epoch_ext.set_max_block_bytes(self.max_block_bytes());
 
         Some(epoch_ext)
     }
 
+    /// Check the parameters are consistent, `Shared` refuses to start with an invalid consensus.
+    pub fn validate(&self) -> Result<(), ConsensusError> {
+        if self.orphan_rate_target_recip == 0 {
+            return Err(ConsensusError::ZeroOrphanRateTargetRecip);
+        }
+        if self.epoch_duration_target == 0 {
+            return Err(ConsensusError::ZeroEpochDurationTarget);
+        }
+        if self.min_difficulty() == &U256::zero() {
+            return Err(ConsensusError::ZeroMinDifficulty);
+        }
+        if self.epoch_reward == Capacity::zero() {
+            return Err(ConsensusError::ZeroEpochReward);
+        }
+        if self.tx_proposal_window.closest() > self.tx_proposal_window.farthest() {
+            return Err(ConsensusError::InvalidProposalWindow(
+                self.tx_proposal_window,
+            ));
+        }
+        let ratio = self.proposer_reward_ratio;
+        if ratio.denom() == 0 || ratio.numer() > ratio.denom() {
+            return Err(ConsensusError::InvalidProposerRewardRatio(ratio));
+        }
+        Ok(())
+    }
+
+    pub fn from_spec(spec: &ConsensusSpec) -> Result<Consensus, ConsensusError> {
+        let spec = spec.clone();
+        let consensus = Consensus {
+            id: spec.id,
+            genesis_hash: spec.genesis_block.header().hash().to_owned(),
+            genesis_block: spec.genesis_block,
+            epoch_reward: spec.epoch_reward,
+            secondary_epoch_reward: spec.secondary_epoch_reward,
+            secondary_issuance_decay: spec.secondary_issuance_decay,
+            max_uncles_num: spec.max_uncles_num,
+            orphan_rate_target_recip: spec.orphan_rate_target_recip,
+            epoch_duration_target: spec.epoch_duration_target,
+            tx_proposal_window: spec.tx_proposal_window,
+            proposer_reward_ratio: spec.proposer_reward_ratio,
+            uncle_inclusion_bonus_ratio: spec.uncle_inclusion_bonus_ratio,
+            max_block_reward: spec.max_block_reward,
+            pow: spec.pow,
+            cellbase_maturity: spec.cellbase_maturity,
+            median_time_block_count: spec.median_time_block_count,
+            max_block_cycles: spec.max_block_cycles,
+            max_block_bytes: spec.max_block_bytes,
+            block_version: spec.block_version,
+            max_block_proposals_limit: spec.max_block_proposals_limit,
+            genesis_epoch_ext: spec.genesis_epoch_ext,
+            bootstrap_lock: spec.bootstrap_lock,
+        };
+        consensus.validate()?;
+        Ok(consensus)
+    }
+
+    pub fn to_spec(&self) -> ConsensusSpec {
+        ConsensusSpec {
+            id: self.id.clone(),
+            genesis_block: self.genesis_block.clone(),
+            epoch_reward: self.epoch_reward,
+            secondary_epoch_reward: self.secondary_epoch_reward,
+            secondary_issuance_decay: self.secondary_issuance_decay,
+            max_uncles_num: self.max_uncles_num,
+            orphan_rate_target_recip: self.orphan_rate_target_recip,
+            epoch_duration_target: self.epoch_duration_target,
+            tx_proposal_window: self.tx_proposal_window,
+            proposer_reward_ratio: self.proposer_reward_ratio,
+            uncle_inclusion_bonus_ratio: self.uncle_inclusion_bonus_ratio,
+            max_block_reward: self.max_block_reward,
+            pow: self.pow.clone(),
+            cellbase_maturity: self.cellbase_maturity,
+            median_time_block_count: self.median_time_block_count,
+            max_block_cycles: self.max_block_cycles,
+            max_block_bytes: self.max_block_bytes,
+            block_version: self.block_version,
+            max_block_proposals_limit: self.max_block_proposals_limit,
+            genesis_epoch_ext: self.genesis_epoch_ext.clone(),
+            bootstrap_lock: self.bootstrap_lock.clone(),
+        }
+    }
+
     pub fn identify_name(&self) -> String {
         let genesis_hash = format!("{:x}", &self.genesis_hash);
         format!("/{}/{}", self.id, &genesis_hash[..8])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_epoch_ext_with_large_uncles_count() {
+        let consensus = Consensus::default();
+        let genesis_epoch = consensus.genesis_epoch_ext();
+        let genesis_hash = consensus.genesis_hash().to_owned();
+        let last_difficulty = U256::max_value() / 4u64;
+        let header = HeaderBuilder::default()
+            .number(genesis_epoch.length() - 1)
+            .timestamp(consensus.epoch_duration_target())
+            .difficulty(last_difficulty.clone())
+            .build();
+
+        // both the epoch length and the difficulty products overflow
+        let epoch = consensus
+            .next_epoch_ext(
+                genesis_epoch,
+                &header,
+                |_| None,
+                |hash| {
+                    if hash == &genesis_hash {
+                        Some(0)
+                    } else {
+                        Some(1 << 40)
+                    }
+                },
+            )
+            .expect("next epoch");
+
+        assert_eq!(epoch.length(), genesis_epoch.length());
+        assert_eq!(epoch.difficulty(), &last_difficulty);
+    }
+
+    #[test]
+    fn spec_round_trip() {
+        let consensus = Consensus::default()
+            .set_id("private".to_owned())
+            .set_cellbase_maturity(5)
+            .set_tx_proposal_window(ProposalWindow(1, 5))
+            .set_uncle_inclusion_bonus_ratio(Ratio(1, 10));
+        assert_eq!(
+            Consensus::from_spec(&consensus.to_spec()),
+            Ok(consensus.clone())
+        );
+
+        let json = serde_json::to_string(&consensus.to_spec()).unwrap();
+        let spec: ConsensusSpec = serde_json::from_str(&json).unwrap();
+        assert_eq!(Consensus::from_spec(&spec), Ok(consensus));
+    }
+
+    #[test]
+    fn invalid_spec() {
+        let mut spec = Consensus::default().to_spec();
+        spec.epoch_reward = Capacity::zero();
+        assert_eq!(
+            Consensus::from_spec(&spec),
+            Err(ConsensusError::ZeroEpochReward)
+        );
+    }
+
+    #[test]
+    fn validate_default() {
+        assert_eq!(Consensus::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_orphan_rate_target_recip() {
+        let mut consensus = Consensus::default();
+        consensus.orphan_rate_target_recip = 0;
+        assert_eq!(
+            consensus.validate(),
+            Err(ConsensusError::ZeroOrphanRateTargetRecip)
+        );
+    }
+
+    #[test]
+    fn validate_epoch_duration_target() {
+        let mut consensus = Consensus::default();
+        consensus.epoch_duration_target = 0;
+        assert_eq!(
+            consensus.validate(),
+            Err(ConsensusError::ZeroEpochDurationTarget)
+        );
+    }
+
+    #[test]
+    fn validate_min_difficulty() {
+        let genesis_block =
+            BlockBuilder::from_header_builder(HeaderBuilder::default().difficulty(U256::zero()))
+                .build();
+        let consensus = Consensus::default().set_genesis_block(genesis_block);
+        assert_eq!(consensus.validate(), Err(ConsensusError::ZeroMinDifficulty));
+    }
+
+    #[test]
+    fn validate_epoch_reward() {
+        let consensus = Consensus::default().set_epoch_reward(Capacity::zero());
+        assert_eq!(consensus.validate(), Err(ConsensusError::ZeroEpochReward));
+    }
+
+    #[test]
+    fn validate_tx_proposal_window() {
+        let consensus = Consensus::default().set_tx_proposal_window(ProposalWindow(10, 2));
+        assert_eq!(
+            consensus.validate(),
+            Err(ConsensusError::InvalidProposalWindow(ProposalWindow(10, 2)))
+        );
+    }
+
+    #[test]
+    fn validate_proposer_reward_ratio() {
+        let mut consensus = Consensus::default();
+        for ratio in &[Ratio(1, 0), Ratio(11, 10)] {
+            consensus.proposer_reward_ratio = *ratio;
+            assert_eq!(
+                consensus.validate(),
+                Err(ConsensusError::InvalidProposerRewardRatio(*ratio))
+            );
+        }
+    }
+}
@@ -1,10 +1,15 @@
 use crate::ChainStore;
-use ckb_core::transaction::CellOutput;
+use ckb_core::transaction::{CellOutPoint, CellOutput};
 use ckb_core::{cell::CellMeta, extras::BlockExt};
 use ckb_script_data_loader::DataLoader;
 use numext_fixed_hash::H256;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::sync::Arc;
 
+#[cfg(any(test, feature = "testing"))]
+pub mod mock;
+
 pub struct DataLoaderWrapper<CS>(Arc<CS>);
 impl<CS> DataLoaderWrapper<CS> {
     pub fn new(source: Arc<CS>) -> Self {
@@ -29,3 +34,166 @@ impl<CS: ChainStore> DataLoader for DataLoaderWrapper<CS> {
         self.0.get_block_ext(block_hash)
     }
 }
+
+/// Remembers the cell outputs loaded by the inner loader, so a cell read several times
+/// while verifying one transaction is only loaded once. It should not outlive the
+/// verification, the remembered outputs are never evicted.
+pub struct CachedDataLoader<DL> {
+    inner: DL,
+    cell_outputs: RefCell<HashMap<CellOutPoint, CellOutput>>,
+}
+
+impl<DL: DataLoader> CachedDataLoader<DL> {
+    pub fn new(inner: DL) -> Self {
+        CachedDataLoader {
+            inner,
+            cell_outputs: RefCell::new(HashMap::new()),
+        }
+    }
+}
+
+impl<DL: DataLoader> DataLoader for CachedDataLoader<DL> {
+    fn lazy_load_cell_output(&self, cell: &CellMeta) -> CellOutput {
+        if let Some(output) = cell.cell_output.as_ref() {
+            return output.to_owned();
+        }
+        if let Some(output) = self.cell_outputs.borrow().get(&cell.out_point) {
+            return output.to_owned();
+        }
+        let output = self.inner.lazy_load_cell_output(cell);
+        self.cell_outputs
+            .borrow_mut()
+            .insert(cell.out_point.clone(), output.clone());
+        output
+    }
+
+    fn get_block_ext(&self, block_hash: &H256) -> Option<BlockExt> {
+        self.inner.get_block_ext(block_hash)
+    }
+}
+
+/// Computes cell outputs on demand with a closure, e.g. when fuzzing scripts against
+/// generated inputs. It knows no blocks, so `get_block_ext` always returns `None`.
+#[derive(Clone)]
+pub struct LazyDataLoader {
+    loader_fn: Arc<dyn Fn(&CellOutPoint) -> Option<CellOutput> + Send + Sync>,
+}
+
+impl LazyDataLoader {
+    pub fn new<F>(loader_fn: F) -> Self
+    where
+        F: Fn(&CellOutPoint) -> Option<CellOutput> + Send + Sync + 'static,
+    {
+        LazyDataLoader {
+            loader_fn: Arc::new(loader_fn),
+        }
+    }
+}
+
+impl DataLoader for LazyDataLoader {
+    fn lazy_load_cell_output(&self, cell: &CellMeta) -> CellOutput {
+        match cell.cell_output.as_ref() {
+            Some(output) => output.to_owned(),
+            None => (self.loader_fn)(&cell.out_point).expect("lazy load cell output from closure"),
+        }
+    }
+
+    fn get_block_ext(&self, _block_hash: &H256) -> Option<BlockExt> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_core::cell::CellMetaBuilder;
+    use ckb_core::{capacity_bytes, Bytes, Capacity};
+    use std::cell::Cell;
+
+    #[derive(Default)]
+    struct CountingDataLoader {
+        loads: Cell<usize>,
+    }
+
+    impl DataLoader for CountingDataLoader {
+        fn lazy_load_cell_output(&self, cell: &CellMeta) -> CellOutput {
+            self.loads.set(self.loads.get() + 1);
+            CellOutput {
+                capacity: cell.capacity,
+                ..Default::default()
+            }
+        }
+
+        fn get_block_ext(&self, _block_hash: &H256) -> Option<BlockExt> {
+            None
+        }
+    }
+
+    #[test]
+    fn load_cell_output_once() {
+        let loader = CachedDataLoader::new(CountingDataLoader::default());
+        let cell = CellMetaBuilder::default()
+            .out_point(CellOutPoint {
+                tx_hash: H256::zero(),
+                index: 0,
+            })
+            .capacity(capacity_bytes!(100))
+            .build();
+        let other = CellMetaBuilder::default()
+            .out_point(CellOutPoint {
+                tx_hash: H256::zero(),
+                index: 1,
+            })
+            .build();
+
+        let output = loader.lazy_load_cell_output(&cell);
+        assert_eq!(output.capacity, capacity_bytes!(100));
+        assert_eq!(loader.lazy_load_cell_output(&cell), output);
+        assert_eq!(loader.inner.loads.get(), 1);
+        loader.lazy_load_cell_output(&other);
+        assert_eq!(loader.inner.loads.get(), 2);
+
+        // outputs carried by the cell meta are not loaded at all
+        let loaded = CellMetaBuilder::default()
+            .cell_output(CellOutput::new(
+                Capacity::zero(),
+                Bytes::default(),
+                Default::default(),
+                None,
+            ))
+            .build();
+        loader.lazy_load_cell_output(&loaded);
+        assert_eq!(loader.inner.loads.get(), 2);
+    }
+
+    #[test]
+    fn lazy_load_cell_output() {
+        let loader = LazyDataLoader::new(|out_point| {
+            if out_point.index < 2 {
+                Some(CellOutput {
+                    capacity: Capacity::bytes(out_point.index as usize + 1).unwrap(),
+                    data: Bytes::from(out_point.tx_hash.as_bytes()),
+                    ..Default::default()
+                })
+            } else {
+                None
+            }
+        });
+        for index in 0..2 {
+            let out_point = CellOutPoint {
+                tx_hash: H256::zero(),
+                index,
+            };
+            let cell = CellMetaBuilder::default()
+                .out_point(out_point.clone())
+                .build();
+            let output = loader.lazy_load_cell_output(&cell);
+            assert_eq!(
+                output.capacity,
+                Capacity::bytes(index as usize + 1).unwrap()
+            );
+            assert_eq!(output.data, Bytes::from(out_point.tx_hash.as_bytes()));
+        }
+        assert_eq!(loader.get_block_ext(&H256::zero()), None);
+    }
+}
@@ -0,0 +1,76 @@
+use ckb_core::cell::CellMeta;
+use ckb_core::extras::BlockExt;
+use ckb_core::transaction::{CellOutPoint, CellOutput};
+use ckb_script_data_loader::DataLoader;
+use numext_fixed_hash::H256;
+use std::collections::HashMap;
+
+/// An in-memory `DataLoader` for exercising script logic without a store.
+#[derive(Default, Clone)]
+pub struct MockDataLoader {
+    cells: HashMap<CellOutPoint, CellOutput>,
+    block_exts: HashMap<H256, BlockExt>,
+}
+
+impl MockDataLoader {
+    pub fn with_cell(mut self, out_point: CellOutPoint, output: CellOutput) -> Self {
+        self.cells.insert(out_point, output);
+        self
+    }
+
+    pub fn with_block_ext(mut self, block_hash: H256, ext: BlockExt) -> Self {
+        self.block_exts.insert(block_hash, ext);
+        self
+    }
+}
+
+impl DataLoader for MockDataLoader {
+    fn lazy_load_cell_output(&self, cell: &CellMeta) -> CellOutput {
+        match cell.cell_output.as_ref() {
+            Some(output) => output.to_owned(),
+            None => self
+                .cells
+                .get(&cell.out_point)
+                .cloned()
+                .expect("cell output is not mocked"),
+        }
+    }
+
+    fn get_block_ext(&self, block_hash: &H256) -> Option<BlockExt> {
+        self.block_exts.get(block_hash).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_core::cell::CellMetaBuilder;
+    use ckb_core::{capacity_bytes, Bytes};
+    use numext_fixed_hash::h256;
+
+    #[test]
+    fn load_mocked_values() {
+        let out_point = CellOutPoint {
+            tx_hash: H256::zero(),
+            index: 0,
+        };
+        let output = CellOutput::new(
+            capacity_bytes!(100),
+            Bytes::from(vec![1, 2, 3]),
+            Default::default(),
+            None,
+        );
+        let ext = BlockExt {
+            received_at: 42,
+            ..Default::default()
+        };
+        let loader = MockDataLoader::default()
+            .with_cell(out_point.clone(), output.clone())
+            .with_block_ext(H256::zero(), ext.clone());
+
+        let cell = CellMetaBuilder::default().out_point(out_point).build();
+        assert_eq!(loader.lazy_load_cell_output(&cell), output);
+        assert_eq!(loader.get_block_ext(&H256::zero()), Some(ext));
+        assert_eq!(loader.get_block_ext(&h256!("0x1")), None);
+    }
+}
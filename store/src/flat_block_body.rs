@@ -1,12 +1,43 @@
 use bincode::{self, Result};
 use serde_derive::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fmt;
+use std::io;
+use std::slice::SliceIndex;
 
 use ckb_core::{
+    script::Script,
     transaction::{CellInput, CellOutput, OutPoint, Transaction, TransactionBuilder, Witness},
-    Version,
+    Bytes, Capacity, Version,
 };
 use numext_fixed_hash::H256;
 
+/// Version of the flat block body format written by this node.
+///
+/// Every serialized block body starts with a 1-byte version identifier, so the
+/// layout can evolve without silently misreading data written by older nodes.
+/// The highest bit of this byte flags that the rest of the body is compressed,
+/// all offsets in the addresses always refer to the uncompressed layout.
+pub const CURRENT_VERSION: u8 = 1;
+/// Size of the version identifier in front of the block body.
+pub(crate) const VERSION_SIZE: usize = 1;
+/// Flag in the version identifier marking an LZ4 compressed body.
+const COMPRESSION_LZ4_FLAG: u8 = 0x80;
+
+/// Compression applied to the block body before it is stored.
+#[derive(Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Hash, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    None,
+    Lz4,
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Compression::None
+    }
+}
+
 /// This module leverages bincode to build a new serializer with flat structure.
 ///
 /// If we use bincode to serialize Vec<T>, it will create a series of bytes
@@ -28,6 +59,40 @@ const TRANSACTION_HASH_INDEX: usize = 6;
 
 type TransactionHeader = [usize; 9];
 
+#[derive(Debug)]
+pub enum FlatSerializerError {
+    /// The block body was written in a format version this node doesn't know.
+    UnsupportedVersion(u8),
+    /// The block body has no version identifier at all.
+    MissingVersion,
+    Bincode(bincode::Error),
+    Compression(io::Error),
+    /// A delta encoded output inherits a field without anything to inherit it from.
+    InvalidDelta,
+}
+
+impl fmt::Display for FlatSerializerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FlatSerializerError::UnsupportedVersion(version) => {
+                write!(f, "unsupported flat block body version {}", version)
+            }
+            FlatSerializerError::MissingVersion => write!(f, "missing flat block body version"),
+            FlatSerializerError::Bincode(err) => write!(f, "{}", err),
+            FlatSerializerError::Compression(err) => {
+                write!(f, "flat block body compression error: {}", err)
+            }
+            FlatSerializerError::InvalidDelta => write!(f, "invalid delta encoded block body"),
+        }
+    }
+}
+
+impl From<bincode::Error> for FlatSerializerError {
+    fn from(err: bincode::Error) -> Self {
+        FlatSerializerError::Bincode(err)
+    }
+}
+
 /// Address of a CellOutput.
 #[derive(Clone, Serialize, Deserialize, Eq, PartialEq, Hash, Debug)]
 pub(crate) struct CellOutputAddress {
@@ -122,24 +187,44 @@ fn serialized_transaction_size(
     Ok((header, output_addresses))
 }
 
+/// Get a field of a serialized transaction. The offsets come from the stored addresses and
+/// the transaction header, a corrupted body must fail to decode instead of panicking.
+fn field<I>(tx: &[u8], index: I) -> Result<&[u8]>
+where
+    I: SliceIndex<[u8], Output = [u8]>,
+{
+    tx.get(index).ok_or_else(out_of_bounds)
+}
+
+/// Get `length` bytes at `offset`, an address overflowing `usize` is out of bounds as well.
+fn field_at(bytes: &[u8], offset: usize, length: usize) -> Result<&[u8]> {
+    let end = offset.checked_add(length).ok_or_else(out_of_bounds)?;
+    field(bytes, offset..end)
+}
+
+fn out_of_bounds() -> bincode::Error {
+    Box::new(bincode::ErrorKind::Custom(
+        "flat block body address out of bounds".to_owned(),
+    ))
+}
+
 pub(crate) fn deserialize_transaction(
     tx: &[u8],
     output_addresses: &[CellOutputAddress],
 ) -> Result<Transaction> {
     let config = bincode::config();
     let header: [usize; TRANSACTION_FIELDS_SIZE] = config.deserialize(tx)?;
-    let version: Version = config.deserialize(&tx[header[1]..])?;
-    let deps: Vec<OutPoint> = config.deserialize(&tx[header[2]..])?;
-    let inputs: Vec<CellInput> = config.deserialize(&tx[header[3]..])?;
+    let version: Version = config.deserialize(field(tx, header[1]..)?)?;
+    let deps: Vec<OutPoint> = config.deserialize(field(tx, header[2]..)?)?;
+    let inputs: Vec<CellInput> = config.deserialize(field(tx, header[3]..)?)?;
     let mut outputs = Vec::with_capacity(output_addresses.len());
     for addr in output_addresses.iter() {
-        let output: CellOutput =
-            config.deserialize(&tx[addr.offset..(addr.offset + addr.length)])?;
+        let output: CellOutput = config.deserialize(field_at(tx, addr.offset, addr.length)?)?;
         outputs.push(output);
     }
-    let witnesses: Vec<Witness> = config.deserialize(&tx[header[5]..])?;
-    let hash: H256 = config.deserialize(&tx[header[TRANSACTION_HASH_INDEX]..])?;
-    let witness_hash: H256 = config.deserialize(&tx[header[7]..])?;
+    let witnesses: Vec<Witness> = config.deserialize(field(tx, header[5]..)?)?;
+    let hash: H256 = config.deserialize(field(tx, header[TRANSACTION_HASH_INDEX]..)?)?;
+    let witness_hash: H256 = config.deserialize(field(tx, header[7]..)?)?;
     unsafe {
         Ok(TransactionBuilder::default()
             .version(version)
@@ -154,7 +239,7 @@ pub(crate) fn deserialize_transaction(
 pub(crate) fn deserialize_transaction_hash(tx: &[u8]) -> Result<H256> {
     let config = bincode::config();
     let header: [usize; TRANSACTION_FIELDS_SIZE] = config.deserialize(tx)?;
-    config.deserialize(&tx[header[TRANSACTION_HASH_INDEX]..])
+    config.deserialize(field(tx, header[TRANSACTION_HASH_INDEX]..)?)
 }
 
 pub(crate) fn serialize_block_body_size(
@@ -169,7 +254,7 @@ pub(crate) fn serialize_block_body_size(
         })
         .collect::<Result<Vec<(usize, TransactionHeader, Vec<CellOutputAddress>)>>>()?;
     let (block_size, tx_addresses) = tx_indexes.into_iter().fold(
-        (0, Vec::with_capacity(txs.len())),
+        (VERSION_SIZE, Vec::with_capacity(txs.len())),
         |(offset, mut addresses), (index, header, outputs_addresses)| {
             addresses.push(TransactionAddressInner {
                 index,
@@ -188,34 +273,130 @@ pub(crate) fn serialize_block_body(
     txs: &[Transaction],
 ) -> Result<(Vec<u8>, Vec<TransactionAddressInner>)> {
     let (total_size, tx_addresses) = serialize_block_body_size(txs)?;
-    let config = bincode::config();
     let mut bytes = Vec::with_capacity(total_size);
+    bytes.push(CURRENT_VERSION);
     for (idx, tx) in txs.iter().enumerate() {
-        config.serialize_into(&mut bytes, &tx_addresses[idx].header)?;
-        config.serialize_into(&mut bytes, &tx.version())?;
-        config.serialize_into(&mut bytes, tx.deps())?;
-        config.serialize_into(&mut bytes, tx.inputs())?;
-        for output in tx.outputs().iter() {
-            config.serialize_into(&mut bytes, output)?;
-        }
-        config.serialize_into(&mut bytes, tx.witnesses())?;
-        config.serialize_into(&mut bytes, tx.hash())?;
-        config.serialize_into(&mut bytes, tx.witness_hash())?;
+        serialize_transaction_into(&mut bytes, &tx_addresses[idx].header, tx)?;
     }
     Ok((bytes, tx_addresses))
 }
 
+fn serialize_transaction_into(
+    bytes: &mut Vec<u8>,
+    header: &TransactionHeader,
+    tx: &Transaction,
+) -> Result<()> {
+    let config = bincode::config();
+    config.serialize_into(&mut *bytes, header)?;
+    config.serialize_into(&mut *bytes, &tx.version())?;
+    config.serialize_into(&mut *bytes, tx.deps())?;
+    config.serialize_into(&mut *bytes, tx.inputs())?;
+    for output in tx.outputs().iter() {
+        config.serialize_into(&mut *bytes, output)?;
+    }
+    config.serialize_into(&mut *bytes, tx.witnesses())?;
+    config.serialize_into(&mut *bytes, tx.hash())?;
+    config.serialize_into(&mut *bytes, tx.witness_hash())
+}
+
+/// Builds an uncompressed flat block body one transaction at a time, producing the same
+/// bytes as `encode_flat_block_body_compressed` with `Compression::None`.
+pub struct FlatBlockBodyBuilder {
+    bytes: Vec<u8>,
+}
+
+impl Default for FlatBlockBodyBuilder {
+    fn default() -> Self {
+        FlatBlockBodyBuilder {
+            bytes: vec![CURRENT_VERSION],
+        }
+    }
+}
+
+impl FlatBlockBodyBuilder {
+    pub fn push_transaction(&mut self, tx: &Transaction) {
+        let (header, _) =
+            serialized_transaction_size(tx).expect("flat serialize transaction should be ok");
+        serialize_transaction_into(&mut self.bytes, &header, tx)
+            .expect("flat serialize transaction should be ok");
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+pub(crate) fn serialize_block_body_compressed(
+    txs: &[Transaction],
+    compression: Compression,
+) -> ::std::result::Result<(Vec<u8>, Vec<TransactionAddressInner>), FlatSerializerError> {
+    let (bytes, tx_addresses) = serialize_block_body(txs)?;
+    match compression {
+        Compression::None => Ok((bytes, tx_addresses)),
+        Compression::Lz4 => {
+            let compressed = lz4::block::compress(&bytes[VERSION_SIZE..], None, true)
+                .map_err(FlatSerializerError::Compression)?;
+            let mut compressed_bytes = Vec::with_capacity(VERSION_SIZE + compressed.len());
+            compressed_bytes.push(CURRENT_VERSION | COMPRESSION_LZ4_FLAG);
+            compressed_bytes.extend_from_slice(&compressed);
+            Ok((compressed_bytes, tx_addresses))
+        }
+    }
+}
+
+/// Serializes the transactions into a flat block body, compressed as requested.
+pub fn encode_flat_block_body_compressed(
+    transactions: &[Transaction],
+    compression: Compression,
+) -> Vec<u8> {
+    serialize_block_body_compressed(transactions, compression)
+        .map(|(bytes, _)| bytes)
+        .expect("flat serialize block body should be ok")
+}
+
+fn check_version(bytes: &[u8]) -> ::std::result::Result<u8, FlatSerializerError> {
+    match bytes.first() {
+        Some(&header) if header & !COMPRESSION_LZ4_FLAG == CURRENT_VERSION => Ok(header),
+        Some(&header) => Err(FlatSerializerError::UnsupportedVersion(
+            header & !COMPRESSION_LZ4_FLAG,
+        )),
+        None => Err(FlatSerializerError::MissingVersion),
+    }
+}
+
+/// Whether the block body beginning with this byte is stored compressed.
+pub(crate) fn is_compressed(first_byte: u8) -> bool {
+    first_byte & COMPRESSION_LZ4_FLAG != 0
+}
+
+/// Restores the uncompressed layout of a stored block body, which the
+/// transaction addresses refer to.
+pub fn decode_flat_block_body(
+    bytes: &[u8],
+) -> ::std::result::Result<Cow<[u8]>, FlatSerializerError> {
+    let header = check_version(bytes)?;
+    if !is_compressed(header) {
+        return Ok(Cow::Borrowed(bytes));
+    }
+    let decompressed = lz4::block::decompress(&bytes[VERSION_SIZE..], None)
+        .map_err(FlatSerializerError::Compression)?;
+    let mut body = Vec::with_capacity(VERSION_SIZE + decompressed.len());
+    body.push(header & !COMPRESSION_LZ4_FLAG);
+    body.extend_from_slice(&decompressed);
+    Ok(Cow::Owned(body))
+}
+
 pub(crate) fn deserialize_block_body(
     bytes: &[u8],
     tx_addresses: &[TransactionAddressInner],
-) -> Result<Vec<Transaction>> {
+) -> ::std::result::Result<Vec<Transaction>, FlatSerializerError> {
+    let bytes = decode_flat_block_body(bytes)?;
     tx_addresses
         .iter()
         .map(|addr| {
-            deserialize_transaction(
-                &bytes[addr.offset..(addr.offset + addr.length)],
-                &addr.outputs_addresses,
-            )
+            field_at(&bytes, addr.offset, addr.length)
+                .and_then(|tx| deserialize_transaction(tx, &addr.outputs_addresses))
+                .map_err(Into::into)
         })
         .collect()
 }
@@ -223,9 +404,182 @@ pub(crate) fn deserialize_block_body(
 pub(crate) fn deserialize_block_body_for_hashes_only(
     bytes: &[u8],
     tx_addresses: &[TransactionAddressInner],
-) -> Result<Vec<H256>> {
+) -> ::std::result::Result<Vec<H256>, FlatSerializerError> {
+    let bytes = decode_flat_block_body(bytes)?;
     tx_addresses
         .iter()
-        .map(|addr| deserialize_transaction_hash(&bytes[addr.offset..(addr.offset + addr.length)]))
+        .map(|addr| {
+            field_at(&bytes, addr.offset, addr.length)
+                .and_then(deserialize_transaction_hash)
+                .map_err(Into::into)
+        })
         .collect()
 }
+
+/// Upgrades a block body stored before the version identifier was introduced.
+///
+/// The transaction addresses stored along with the body are shifted by the
+/// identifier as well, they should be regenerated by `serialize_block_body_size`.
+pub fn migrate_v0_to_v1(raw: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(VERSION_SIZE + raw.len());
+    bytes.push(CURRENT_VERSION);
+    bytes.extend_from_slice(raw);
+    bytes
+}
+
+/// Bit in `DeltaCellOutput::inherited`, the lock is taken from the reference output.
+const DELTA_INHERIT_LOCK: u8 = 0b01;
+/// Bit in `DeltaCellOutput::inherited`, the type is taken from the reference output.
+const DELTA_INHERIT_TYPE: u8 = 0b10;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct DeltaCellOutput {
+    inherited: u8,
+    capacity: Capacity,
+    data: Bytes,
+    lock: Option<Script>,
+    type_: Option<Option<Script>>,
+}
+
+/// Transaction with its outputs delta encoded against the previous transaction.
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct DeltaTransaction {
+    version: Version,
+    deps: Vec<OutPoint>,
+    inputs: Vec<CellInput>,
+    outputs: Vec<DeltaCellOutput>,
+    witnesses: Vec<Witness>,
+}
+
+fn delta_reference(previous: Option<&Transaction>, index: usize) -> Option<&CellOutput> {
+    previous.and_then(|tx| {
+        let outputs = tx.outputs();
+        outputs.get(index).or_else(|| outputs.last())
+    })
+}
+
+impl DeltaTransaction {
+    fn encode(tx: &Transaction, previous: Option<&Transaction>) -> Self {
+        let outputs = tx
+            .outputs()
+            .iter()
+            .enumerate()
+            .map(|(index, output)| {
+                let reference = delta_reference(previous, index);
+                let mut inherited = 0;
+                let lock = if reference.map_or(false, |r| r.lock == output.lock) {
+                    inherited |= DELTA_INHERIT_LOCK;
+                    None
+                } else {
+                    Some(output.lock.clone())
+                };
+                let type_ = if reference.map_or(false, |r| r.type_ == output.type_) {
+                    inherited |= DELTA_INHERIT_TYPE;
+                    None
+                } else {
+                    Some(output.type_.clone())
+                };
+                DeltaCellOutput {
+                    inherited,
+                    capacity: output.capacity,
+                    data: output.data.clone(),
+                    lock,
+                    type_,
+                }
+            })
+            .collect();
+        DeltaTransaction {
+            version: tx.version(),
+            deps: tx.deps().to_vec(),
+            inputs: tx.inputs().to_vec(),
+            outputs,
+            witnesses: tx.witnesses().to_vec(),
+        }
+    }
+
+    fn decode(
+        self,
+        previous: Option<&Transaction>,
+    ) -> ::std::result::Result<Transaction, FlatSerializerError> {
+        let DeltaTransaction {
+            version,
+            deps,
+            inputs,
+            outputs,
+            witnesses,
+        } = self;
+        let outputs = outputs
+            .into_iter()
+            .enumerate()
+            .map(|(index, output)| {
+                let reference = delta_reference(previous, index);
+                let lock = if output.inherited & DELTA_INHERIT_LOCK != 0 {
+                    reference.map(|r| r.lock.clone())
+                } else {
+                    output.lock
+                };
+                let type_ = if output.inherited & DELTA_INHERIT_TYPE != 0 {
+                    reference.map(|r| r.type_.clone())
+                } else {
+                    output.type_
+                };
+                match (lock, type_) {
+                    (Some(lock), Some(type_)) => {
+                        Ok(CellOutput::new(output.capacity, output.data, lock, type_))
+                    }
+                    _ => Err(FlatSerializerError::InvalidDelta),
+                }
+            })
+            .collect::<::std::result::Result<Vec<_>, _>>()?;
+        Ok(TransactionBuilder::default()
+            .version(version)
+            .deps(deps)
+            .inputs(inputs)
+            .outputs(outputs)
+            .witnesses(witnesses)
+            .build())
+    }
+}
+
+/// Serializes the transactions of a block body with delta encoded outputs, an
+/// alternative layout for a whole block body.
+///
+/// Adjacent transactions often pay to the same lock or carry the same type
+/// script, so each output only stores the scripts which differ from the output
+/// at the same position in the previous transaction (or its last output when
+/// the previous transaction has fewer outputs). The `inherited` bitfield tells
+/// which scripts are omitted. Unlike the flat layout the body has to be
+/// decoded at once, individual transactions can't be read in place.
+pub fn encode_delta_block_body(transactions: &[Transaction]) -> Vec<u8> {
+    let delta_transactions = transactions
+        .iter()
+        .enumerate()
+        .map(|(idx, tx)| {
+            let previous = if idx > 0 {
+                transactions.get(idx - 1)
+            } else {
+                None
+            };
+            DeltaTransaction::encode(tx, previous)
+        })
+        .collect::<Vec<_>>();
+    bincode::config()
+        .serialize(&delta_transactions)
+        .expect("delta serialize block body should be ok")
+}
+
+/// Restores the transactions of a block body written by `encode_delta_block_body`.
+pub fn decode_delta_block_body(
+    bytes: &[u8],
+) -> ::std::result::Result<Vec<Transaction>, FlatSerializerError> {
+    let delta_transactions: Vec<DeltaTransaction> = bincode::config().deserialize(bytes)?;
+    let mut transactions: Vec<Transaction> = Vec::with_capacity(delta_transactions.len());
+    for delta_transaction in delta_transactions {
+        let tx = delta_transaction.decode(transactions.last())?;
+        transactions.push(tx);
+    }
+    Ok(transactions)
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,342 @@
+use super::*;
+use ckb_core::script::Script;
+use ckb_core::transaction::CellOutPoint;
+use ckb_core::{Bytes, Capacity};
+use proptest::prelude::*;
+
+fn arb_bytes() -> impl Strategy<Value = Bytes> {
+    prop::collection::vec(any::<u8>(), 0..32).prop_map(Bytes::from)
+}
+
+fn arb_h256() -> impl Strategy<Value = H256> {
+    prop::collection::vec(any::<u8>(), 32)
+        .prop_map(|bytes| H256::from_slice(&bytes).expect("H256 from 32 bytes"))
+}
+
+fn arb_script() -> impl Strategy<Value = Script> {
+    (prop::collection::vec(arb_bytes(), 0..3), arb_h256())
+        .prop_map(|(args, code_hash)| Script::new(args, code_hash))
+}
+
+fn arb_out_point() -> impl Strategy<Value = OutPoint> {
+    (
+        prop::option::of((arb_h256(), any::<u32>())),
+        prop::option::of(arb_h256()),
+    )
+        .prop_map(|(cell, block_hash)| OutPoint {
+            cell: cell.map(|(tx_hash, index)| CellOutPoint { tx_hash, index }),
+            block_hash,
+        })
+}
+
+fn arb_output() -> impl Strategy<Value = CellOutput> {
+    (
+        any::<u64>(),
+        arb_bytes(),
+        arb_script(),
+        prop::option::of(arb_script()),
+    )
+        .prop_map(|(capacity, data, lock, type_)| {
+            CellOutput::new(Capacity::shannons(capacity), data, lock, type_)
+        })
+}
+
+fn arb_transaction() -> impl Strategy<Value = Transaction> {
+    (
+        any::<Version>(),
+        prop::collection::vec(arb_out_point(), 0..3),
+        prop::collection::vec((arb_out_point(), any::<u64>()), 0..3),
+        prop::collection::vec(arb_output(), 0..4),
+        prop::collection::vec(prop::collection::vec(arb_bytes(), 0..3), 0..3),
+    )
+        .prop_map(|(version, deps, inputs, outputs, witnesses)| {
+            TransactionBuilder::default()
+                .version(version)
+                .deps(deps)
+                .inputs(
+                    inputs
+                        .into_iter()
+                        .map(|(out_point, since)| CellInput::new(out_point, since))
+                        .collect(),
+                )
+                .outputs(outputs)
+                .witnesses(witnesses)
+                .build()
+        })
+}
+
+type TransactionFields = (
+    Version,
+    Vec<OutPoint>,
+    Vec<CellInput>,
+    Vec<CellOutput>,
+    Vec<Witness>,
+);
+
+// Transactions compare by witness hash only, which the flat layout stores
+// along with the fields instead of recomputing it.
+fn fields(txs: &[Transaction]) -> Vec<TransactionFields> {
+    txs.iter()
+        .map(|tx| {
+            (
+                tx.version(),
+                tx.deps().to_vec(),
+                tx.inputs().to_vec(),
+                tx.outputs().to_vec(),
+                tx.witnesses().to_vec(),
+            )
+        })
+        .collect()
+}
+
+fn build_transactions() -> Vec<Transaction> {
+    (0..3u64)
+        .map(|i| {
+            TransactionBuilder::default()
+                .input(CellInput::new(OutPoint::null(), i))
+                .output(CellOutput::new(
+                    Capacity::bytes(100 + i as usize).unwrap(),
+                    Bytes::from(vec![i as u8; 8]),
+                    Script::default(),
+                    None,
+                ))
+                .build()
+        })
+        .collect()
+}
+
+#[test]
+fn round_trip_at_current_version() {
+    let txs = build_transactions();
+    let (bytes, addresses) = serialize_block_body(&txs).unwrap();
+    assert_eq!(bytes[0], CURRENT_VERSION);
+    assert_eq!(txs, deserialize_block_body(&bytes, &addresses).unwrap());
+
+    let hashes: Vec<H256> = txs.iter().map(|tx| tx.hash().to_owned()).collect();
+    assert_eq!(
+        hashes,
+        deserialize_block_body_for_hashes_only(&bytes, &addresses).unwrap()
+    );
+}
+
+#[test]
+fn unsupported_version() {
+    let txs = build_transactions();
+    let (mut bytes, addresses) = serialize_block_body(&txs).unwrap();
+    bytes[0] = CURRENT_VERSION + 1;
+    match deserialize_block_body(&bytes, &addresses) {
+        Err(FlatSerializerError::UnsupportedVersion(version)) => {
+            assert_eq!(version, CURRENT_VERSION + 1)
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn overflowing_address() {
+    let txs = build_transactions();
+    let (bytes, mut addresses) = serialize_block_body(&txs).unwrap();
+    addresses[0].length = usize::max_value();
+    match deserialize_block_body(&bytes, &addresses) {
+        Err(FlatSerializerError::Bincode(_)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    let (_, mut addresses) = serialize_block_body(&txs).unwrap();
+    addresses[0].outputs_addresses[0].length = usize::max_value();
+    match deserialize_block_body(&bytes, &addresses) {
+        Err(FlatSerializerError::Bincode(_)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn migrate_from_v0() {
+    let txs = build_transactions();
+    let (bytes, addresses) = serialize_block_body(&txs).unwrap();
+    let migrated = migrate_v0_to_v1(&bytes[VERSION_SIZE..]);
+    assert_eq!(bytes, migrated);
+    assert_eq!(txs, deserialize_block_body(&migrated, &addresses).unwrap());
+}
+
+#[test]
+fn build_incrementally() {
+    let txs = build_transactions();
+    let mut builder = FlatBlockBodyBuilder::default();
+    for tx in &txs {
+        builder.push_transaction(tx);
+    }
+    assert_eq!(
+        builder.finish(),
+        encode_flat_block_body_compressed(&txs, Compression::None)
+    );
+
+    assert_eq!(
+        FlatBlockBodyBuilder::default().finish(),
+        encode_flat_block_body_compressed(&[], Compression::None)
+    );
+}
+
+#[test]
+fn compressed_round_trip() {
+    let txs = build_transactions();
+    let (bytes, addresses) = serialize_block_body_compressed(&txs, Compression::Lz4).unwrap();
+    assert!(is_compressed(bytes[0]));
+    assert_eq!(txs, deserialize_block_body(&bytes, &addresses).unwrap());
+    assert_eq!(
+        serialize_block_body(&txs).unwrap().0,
+        decode_flat_block_body(&bytes).unwrap().into_owned()
+    );
+}
+
+#[test]
+fn delta_round_trip() {
+    let lock = Script::new(vec![Bytes::from(vec![1; 20])], H256::zero());
+    let type_ = Some(Script::new(vec![], H256::zero()));
+    let txs: Vec<Transaction> = (0..4u64)
+        .map(|i| {
+            TransactionBuilder::default()
+                .input(CellInput::new(OutPoint::null(), i))
+                .output(CellOutput::new(
+                    Capacity::bytes(100).unwrap(),
+                    Bytes::default(),
+                    lock.clone(),
+                    type_.clone(),
+                ))
+                .build()
+        })
+        .collect();
+    let bytes = encode_delta_block_body(&txs);
+    assert_eq!(txs, decode_delta_block_body(&bytes).unwrap());
+
+    let delta_transactions: Vec<DeltaTransaction> = bincode::config().deserialize(&bytes).unwrap();
+    assert_eq!(delta_transactions[0].outputs[0].inherited, 0);
+    for delta_transaction in &delta_transactions[1..] {
+        assert_eq!(
+            delta_transaction.outputs[0].inherited,
+            DELTA_INHERIT_LOCK | DELTA_INHERIT_TYPE
+        );
+    }
+}
+
+#[test]
+fn delta_without_reference() {
+    let delta_transactions = vec![DeltaTransaction {
+        version: 0,
+        deps: vec![],
+        inputs: vec![],
+        outputs: vec![DeltaCellOutput {
+            inherited: DELTA_INHERIT_LOCK,
+            capacity: Capacity::zero(),
+            data: Bytes::default(),
+            lock: None,
+            type_: Some(None),
+        }],
+        witnesses: vec![],
+    }];
+    let bytes = bincode::config().serialize(&delta_transactions).unwrap();
+    match decode_delta_block_body(&bytes) {
+        Err(FlatSerializerError::InvalidDelta) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+proptest! {
+    #[test]
+    fn delta_round_trip_with_any_outputs(
+        ref transactions in prop::collection::vec(
+            prop::collection::vec((0..3u8, prop::option::of(0..3u8), any::<u64>()), 0..4),
+            0..16,
+        )
+    ) {
+        // draw scripts from a small pool so that both inherited and stored
+        // fields show up
+        let script = |i: u8| Script::new(vec![Bytes::from(vec![i])], H256::zero());
+        let txs: Vec<Transaction> = transactions
+            .iter()
+            .map(|outputs| {
+                let outputs = outputs
+                    .iter()
+                    .map(|(lock, type_, capacity)| {
+                        CellOutput::new(
+                            Capacity::shannons(*capacity),
+                            Bytes::default(),
+                            script(*lock),
+                            type_.map(script),
+                        )
+                    })
+                    .collect();
+                TransactionBuilder::default().outputs(outputs).build()
+            })
+            .collect();
+        let bytes = encode_delta_block_body(&txs);
+        prop_assert_eq!(&txs, &decode_delta_block_body(&bytes).unwrap());
+    }
+
+    #[test]
+    fn round_trip_with_any_compression(
+        ref outputs in prop::collection::vec(
+            (any::<u64>(), prop::collection::vec(any::<u8>(), 0..256)),
+            0..16,
+        )
+    ) {
+        let txs: Vec<Transaction> = outputs
+            .iter()
+            .map(|(capacity, data)| {
+                TransactionBuilder::default()
+                    .output(CellOutput::new(
+                        Capacity::shannons(*capacity),
+                        Bytes::from(data.clone()),
+                        Script::default(),
+                        None,
+                    ))
+                    .build()
+            })
+            .collect();
+        for compression in &[Compression::None, Compression::Lz4] {
+            let (bytes, addresses) = serialize_block_body_compressed(&txs, *compression).unwrap();
+            prop_assert_eq!(&txs, &deserialize_block_body(&bytes, &addresses).unwrap());
+        }
+    }
+
+    #[test]
+    fn round_trip_with_any_transactions(
+        ref txs in prop::collection::vec(arb_transaction(), 0..8)
+    ) {
+        for compression in &[Compression::None, Compression::Lz4] {
+            let (bytes, addresses) = serialize_block_body_compressed(txs, *compression).unwrap();
+            let decoded = deserialize_block_body(&bytes, &addresses).unwrap();
+            prop_assert_eq!(txs, &decoded);
+            prop_assert_eq!(fields(txs), fields(&decoded));
+            let hashes: Vec<H256> = txs.iter().map(|tx| tx.hash().to_owned()).collect();
+            prop_assert_eq!(
+                hashes,
+                deserialize_block_body_for_hashes_only(&bytes, &addresses).unwrap()
+            );
+        }
+
+        let decoded = decode_delta_block_body(&encode_delta_block_body(txs)).unwrap();
+        prop_assert_eq!(txs, &decoded);
+        prop_assert_eq!(fields(txs), fields(&decoded));
+    }
+
+    // A corrupted byte in the payload of a field may still decode to
+    // something, the decoders must only never panic.
+    #[test]
+    fn decode_corrupted_body(
+        ref txs in prop::collection::vec(arb_transaction(), 1..4),
+        position in any::<usize>(),
+        mask in 1..=u8::max_value(),
+    ) {
+        let (mut bytes, addresses) = serialize_block_body(txs).unwrap();
+        let position = position % bytes.len();
+        bytes[position] ^= mask;
+        let _ = deserialize_block_body(&bytes, &addresses);
+        let _ = deserialize_block_body_for_hashes_only(&bytes, &addresses);
+
+        let mut bytes = encode_delta_block_body(txs);
+        let position = position % bytes.len();
+        bytes[position] ^= mask;
+        let _ = decode_delta_block_body(&bytes);
+    }
+}
@@ -1,12 +1,18 @@
 pub mod data_loader_wrapper;
 mod flat_block_body;
+pub mod migration;
 mod store;
 
-pub use store::{ChainKVStore, ChainStore, StoreBatch, StoreConfig};
+pub use crate::flat_block_body::{
+    decode_delta_block_body, decode_flat_block_body, encode_delta_block_body,
+    encode_flat_block_body_compressed, migrate_v0_to_v1, Compression, FlatBlockBodyBuilder,
+    FlatSerializerError,
+};
+pub use store::{ChainKVStore, ChainStore, ColumnSize, StoreBatch, StoreConfig, TotalCounts};
 
 use ckb_db::Col;
 
-pub const COLUMNS: u32 = 14;
+pub const COLUMNS: u32 = 17;
 pub const COLUMN_INDEX: Col = 0;
 pub const COLUMN_BLOCK_HEADER: Col = 1;
 pub const COLUMN_BLOCK_BODY: Col = 2;
@@ -21,3 +27,6 @@ pub const COLUMN_BLOCK_EPOCH: Col = 10;
 pub const COLUMN_EPOCH: Col = 11;
 pub const COLUMN_CELL_SET: Col = 12;
 pub const COLUMN_UNCLES: Col = 13;
+pub const COLUMN_UNCLE_TO_BLOCK: Col = 14;
+pub const COLUMN_EPOCH_START: Col = 15;
+pub const COLUMN_SCRIPT_TO_CELLS: Col = 16;
@@ -0,0 +1,872 @@
+//! Migrations of the database from one data format version to the next.
+//!
+//! The version is the `db-version` checked by `RocksDB::open_with_check`. A node
+//! refuses to open a database of an older minor version, `ckb migrate` upgrades it
+//! with the migrations registered in `MigrationRunner::new`.
+
+use crate::flat_block_body::{
+    deserialize_transaction_hash, migrate_v0_to_v1, TransactionAddressInner,
+    TransactionAddressStored, VERSION_SIZE,
+};
+use crate::store::META_CURRENT_EPOCH_KEY;
+use crate::{
+    ChainKVStore, ChainStore, StoreBatch, TotalCounts, COLUMN_BLOCK_BODY,
+    COLUMN_BLOCK_TRANSACTION_ADDRESSES, COLUMN_EPOCH, COLUMN_EXT, COLUMN_META,
+    COLUMN_TRANSACTION_ADDR,
+};
+use bincode::{deserialize, serialize, serialized_size};
+use ckb_chain_spec::consensus::Consensus;
+use ckb_core::extras::{DaoStats, EpochExt};
+use ckb_core::{Capacity, EpochNumber};
+use ckb_db::{Col, DbBatch, Error, KeyValueDB, RocksDB};
+use numext_fixed_hash::H256;
+use numext_fixed_uint::U256;
+use semver::Version;
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use std::mem;
+use std::ops::Range;
+
+/// A migration upgrades the database to `version()` from the version before it.
+///
+/// A migration may be interrupted before the new version is saved and run again on
+/// the next start, so it must be idempotent.
+pub trait Migration<T: KeyValueDB> {
+    fn version(&self) -> &str;
+
+    fn migrate(&self, db: &T) -> Result<(), Error>;
+}
+
+fn parse_version(version: &str) -> Result<Version, Error> {
+    Version::parse(version).map_err(|err| {
+        Error::DBError(format!(
+            "database version {} is malformed: {}",
+            version, err
+        ))
+    })
+}
+
+fn decode<T: DeserializeOwned>(raw: &[u8], name: &str) -> Result<T, Error> {
+    deserialize(raw).map_err(|err| Error::DBError(format!("malformed {}: {}", name, err)))
+}
+
+// Whether `raw` holds a `T` and nothing more, i.e. none of the fields appended by the
+// later versions
+fn is_exactly<T: serde::Serialize + DeserializeOwned>(raw: &[u8]) -> bool {
+    deserialize::<T>(raw)
+        .ok()
+        .and_then(|value| serialized_size(&value).ok())
+        == Some(raw.len() as u64)
+}
+
+// Lets a migration read and write through `ChainKVStore` without owning the database
+struct Borrowed<'a, T>(&'a T);
+
+impl<'a, T: KeyValueDB> KeyValueDB for Borrowed<'a, T> {
+    type Batch = T::Batch;
+
+    fn read(&self, col: Col, key: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        self.0.read(col, key)
+    }
+
+    fn partial_read(
+        &self,
+        col: Col,
+        key: &[u8],
+        range: &Range<usize>,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        self.0.partial_read(col, key, range)
+    }
+
+    fn batch(&self) -> Result<Self::Batch, Error> {
+        self.0.batch()
+    }
+
+    fn traverse<F>(&self, col: Col, callback: F) -> Result<(), Error>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<(), Error>,
+    {
+        self.0.traverse(col, callback)
+    }
+
+    fn traverse_prefix<F>(&self, col: Col, prefix: &[u8], callback: F) -> Result<(), Error>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<bool, Error>,
+    {
+        self.0.traverse_prefix(col, prefix, callback)
+    }
+
+    fn compact_with_filter<F>(&self, col: Col, filter: F) -> Result<(), Error>
+    where
+        F: Fn(&[u8], &[u8]) -> bool + Send + Sync + 'static,
+    {
+        self.0.compact_with_filter(col, filter)
+    }
+}
+
+/// Applies the migrations in the order of their versions
+pub struct MigrationRunner {
+    migrations: Vec<(Version, Box<dyn Migration<RocksDB>>)>,
+}
+
+impl MigrationRunner {
+    /// The migrations of the data written by the older versions of the store
+    pub fn new(consensus: &Consensus) -> Self {
+        MigrationRunner {
+            migrations: Vec::new(),
+        }
+        .add_migration(Box::new(AddBlockBodyVersion))
+        .add_migration(Box::new(AddEpochMaxBlockBytes {
+            max_block_bytes: consensus.max_block_bytes(),
+        }))
+        .add_migration(Box::new(IndexMainChain))
+        .add_migration(Box::new(AddBlockSize {
+            proof_size: consensus.pow_engine().proof_size(),
+        }))
+        .add_migration(Box::new(AddBlockRewardComponents))
+    }
+
+    pub fn add_migration(mut self, migration: Box<dyn Migration<RocksDB>>) -> Self {
+        let version = parse_version(migration.version()).expect("migration version should be ok");
+        self.migrations.push((version, migration));
+        self.migrations.sort_by(|a, b| a.0.cmp(&b.0));
+        self
+    }
+
+    /// Run the migrations after the database version up to and including `to_version`,
+    /// saving the version after each step. Return the resulting version.
+    pub fn run(&self, db: &RocksDB, to_version: &str) -> Result<String, Error> {
+        let target = parse_version(to_version)?;
+        let mut version = db
+            .version()?
+            .ok_or_else(|| Error::DBError("version info about database is lost".to_owned()))
+            .and_then(|version| parse_version(&version))?;
+        if version > target {
+            return Err(Error::DBError(format!(
+                "the database version {} is newer than {}",
+                version, target
+            )));
+        }
+
+        for (migration_version, migration) in &self.migrations {
+            if *migration_version <= version || *migration_version > target {
+                continue;
+            }
+            migration.migrate(db)?;
+            db.set_version(migration.version())?;
+            version = migration_version.to_owned();
+        }
+
+        // A newer patch version needs no migration, the same as in `RocksDB::open_with_check`
+        if version.major != target.major || version.minor != target.minor {
+            return Err(Error::DBError(format!(
+                "missing migration from database version {} to {}",
+                version, target
+            )));
+        }
+        db.set_version(to_version)?;
+        Ok(to_version.to_owned())
+    }
+}
+
+/// Prefixes the block bodies stored before 0.1401.0 with the format version
+/// identifier, see `migrate_v0_to_v1`, and shifts their transaction addresses.
+pub struct AddBlockBodyVersion;
+
+impl<T: KeyValueDB> Migration<T> for AddBlockBodyVersion {
+    fn version(&self) -> &str {
+        "0.1401.0"
+    }
+
+    fn migrate(&self, db: &T) -> Result<(), Error> {
+        let mut block_hashes = Vec::new();
+        db.traverse(COLUMN_BLOCK_TRANSACTION_ADDRESSES, |key, _| {
+            block_hashes.push(key.to_vec());
+            Ok(())
+        })?;
+
+        // One batch per block, so an interrupted run leaves every block either upgraded or not
+        for block_hash in block_hashes {
+            let mut addresses: Vec<TransactionAddressInner> =
+                match db.read(COLUMN_BLOCK_TRANSACTION_ADDRESSES, &block_hash)? {
+                    Some(raw) => decode(&raw, "transaction addresses")?,
+                    None => continue,
+                };
+            let body = db.read(COLUMN_BLOCK_BODY, &block_hash)?.unwrap_or_default();
+            // The first transaction of an upgraded body starts after the version identifier
+            let is_upgraded = match addresses.first() {
+                Some(address) => address.offset >= VERSION_SIZE,
+                None => !body.is_empty(),
+            };
+            if is_upgraded {
+                continue;
+            }
+
+            let mut batch = db.batch()?;
+            for address in &mut addresses {
+                let tx_hash = body
+                    .get(address.offset..address.offset + address.length)
+                    .ok_or_else(|| {
+                        Error::DBError("flat block body address out of bounds".to_owned())
+                    })
+                    .and_then(|tx| {
+                        deserialize_transaction_hash(tx)
+                            .map_err(|err| Error::DBError(format!("malformed block body: {}", err)))
+                    })?;
+                address.offset += VERSION_SIZE;
+
+                // The index points to the main chain block when a transaction is in several blocks
+                if let Some(raw) = db.read(COLUMN_TRANSACTION_ADDR, tx_hash.as_bytes())? {
+                    let mut stored: TransactionAddressStored = decode(&raw, "transaction address")?;
+                    if stored.block_hash.as_bytes() == block_hash.as_slice() {
+                        stored.inner.offset = address.offset;
+                        let value = serialize(&stored).expect("serializing should be ok");
+                        batch.insert(COLUMN_TRANSACTION_ADDR, tx_hash.as_bytes(), &value)?;
+                    }
+                }
+            }
+            let value = serialize(&addresses).expect("serializing should be ok");
+            batch.insert(COLUMN_BLOCK_TRANSACTION_ADDRESSES, &block_hash, &value)?;
+            batch.insert(COLUMN_BLOCK_BODY, &block_hash, &migrate_v0_to_v1(&body))?;
+            batch.commit()?;
+        }
+        Ok(())
+    }
+}
+
+/// Adds `max_block_bytes` to the `EpochExt`s stored before 0.1402.0. They were all
+/// bound by the limit of the consensus.
+pub struct AddEpochMaxBlockBytes {
+    pub max_block_bytes: u64,
+}
+
+impl AddEpochMaxBlockBytes {
+    // `max_block_bytes` is the last field of `EpochExt` and bincode writes a u64 as 8
+    // little endian bytes, so an old epoch only lacks them at the end.
+    fn upgrade(&self, raw: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        if deserialize::<EpochExt>(raw).is_ok() {
+            return Ok(None);
+        }
+        let mut upgraded = raw.to_vec();
+        upgraded.extend_from_slice(&self.max_block_bytes.to_le_bytes());
+        decode::<EpochExt>(&upgraded, "epoch ext")?;
+        Ok(Some(upgraded))
+    }
+}
+
+impl<T: KeyValueDB> Migration<T> for AddEpochMaxBlockBytes {
+    fn version(&self) -> &str {
+        "0.1402.0"
+    }
+
+    fn migrate(&self, db: &T) -> Result<(), Error> {
+        let mut epochs = Vec::new();
+        db.traverse(COLUMN_EPOCH, |key, value| {
+            // The column also maps the epoch numbers to the epoch hashes
+            if key.len() != mem::size_of::<EpochNumber>() {
+                epochs.push((key.to_vec(), value.to_vec()));
+            }
+            Ok(())
+        })?;
+
+        let mut batch = db.batch()?;
+        for (key, value) in epochs {
+            if let Some(upgraded) = self.upgrade(&value)? {
+                batch.insert(COLUMN_EPOCH, &key, &upgraded)?;
+            }
+        }
+        if let Some(value) = db.read(COLUMN_META, META_CURRENT_EPOCH_KEY)? {
+            if let Some(upgraded) = self.upgrade(&value)? {
+                batch.insert(COLUMN_META, META_CURRENT_EPOCH_KEY, &upgraded)?;
+            }
+        }
+        batch.commit()
+    }
+}
+
+/// Builds the indexes of the main chain added after 0.1400.0: the blocks including each
+/// uncle, the first block of each epoch, the cells of each lock and type script and the
+/// total counts. The main chain blocks are attached again, which rewrites their older
+/// indexes with the same values.
+pub struct IndexMainChain;
+
+impl<T: KeyValueDB> Migration<T> for IndexMainChain {
+    fn version(&self) -> &str {
+        "0.1403.0"
+    }
+
+    fn migrate(&self, db: &T) -> Result<(), Error> {
+        let store = ChainKVStore::new(Borrowed(db));
+        let tip = match store.get_tip_header() {
+            Some(tip) => tip,
+            None => return Ok(()),
+        };
+
+        // One batch per block, the total counts are saved once every block is indexed
+        let mut counts = TotalCounts::default();
+        let mut parent_epoch = None;
+        for number in 0..=tip.number() {
+            let block = store
+                .get_block_hash(number)
+                .and_then(|hash| store.get_block(&hash))
+                .ok_or_else(|| Error::DBError(format!("main chain block {} not found", number)))?;
+            let mut batch = store.new_batch()?;
+            batch.attach_block(&block)?;
+            if parent_epoch != Some(block.header().epoch()) {
+                batch.insert_epoch_start(block.header().epoch(), block.header().hash())?;
+            }
+            batch.commit()?;
+            counts.attach(&block);
+            parent_epoch = Some(block.header().epoch());
+        }
+        let mut batch = store.new_batch()?;
+        batch.insert_total_counts(&counts)?;
+        batch.commit()
+    }
+}
+
+// `BlockExt` as stored before 0.1404.0
+#[derive(Serialize, Deserialize)]
+struct BlockExtV1403 {
+    received_at: u64,
+    total_difficulty: U256,
+    total_uncles_count: u64,
+    verified: Option<bool>,
+    dao_stats: DaoStats,
+    txs_fees: Vec<Capacity>,
+}
+
+/// Adds the serialized size of the block to the `BlockExt`s stored before 0.1404.0.
+pub struct AddBlockSize {
+    pub proof_size: usize,
+}
+
+impl<T: KeyValueDB> Migration<T> for AddBlockSize {
+    fn version(&self) -> &str {
+        "0.1404.0"
+    }
+
+    fn migrate(&self, db: &T) -> Result<(), Error> {
+        let mut block_hashes = Vec::new();
+        db.traverse(COLUMN_EXT, |key, value| {
+            if is_exactly::<BlockExtV1403>(value) {
+                block_hashes.push(key.to_vec());
+            }
+            Ok(())
+        })?;
+
+        // `size` is the last field of `BlockExt` and bincode writes a u32 as 4 little
+        // endian bytes, so an old ext only lacks them at the end
+        let store = ChainKVStore::new(Borrowed(db));
+        for block_hash in block_hashes {
+            let block = H256::from_slice(&block_hash)
+                .ok()
+                .and_then(|hash| store.get_block(&hash))
+                .ok_or_else(|| Error::DBError("block of the block ext not found".to_owned()))?;
+            let mut ext = match db.read(COLUMN_EXT, &block_hash)? {
+                Some(raw) => raw,
+                None => continue,
+            };
+            ext.extend_from_slice(&(block.serialized_size(self.proof_size) as u32).to_le_bytes());
+            let mut batch = db.batch()?;
+            batch.insert(COLUMN_EXT, &block_hash, &ext)?;
+            batch.commit()?;
+        }
+        Ok(())
+    }
+}
+
+// `BlockExt` as stored before 0.1405.0
+#[derive(Serialize, Deserialize)]
+struct BlockExtV1404 {
+    ext: BlockExtV1403,
+    size: u32,
+}
+
+/// Adds no `reward_components` to the `BlockExt`s stored before 0.1405.0, the provider
+/// computes them again for these blocks.
+pub struct AddBlockRewardComponents;
+
+impl<T: KeyValueDB> Migration<T> for AddBlockRewardComponents {
+    fn version(&self) -> &str {
+        "0.1405.0"
+    }
+
+    fn migrate(&self, db: &T) -> Result<(), Error> {
+        let mut block_hashes = Vec::new();
+        db.traverse(COLUMN_EXT, |key, value| {
+            if is_exactly::<BlockExtV1404>(value) {
+                block_hashes.push(key.to_vec());
+            }
+            Ok(())
+        })?;
+
+        // bincode writes `None` as a single 0 byte
+        for block_hash in block_hashes {
+            let mut ext = match db.read(COLUMN_EXT, &block_hash)? {
+                Some(raw) => raw,
+                None => continue,
+            };
+            ext.push(0);
+            let mut batch = db.batch()?;
+            batch.insert(COLUMN_EXT, &block_hash, &ext)?;
+            batch.commit()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flat_block_body::serialize_block_body;
+    use crate::store::{
+        META_TOTAL_CELLS_CONSUMED_COUNT_KEY, META_TOTAL_CELLS_CREATED_COUNT_KEY,
+        META_TOTAL_TX_COUNT_KEY,
+    };
+    use crate::{
+        COLUMNS, COLUMN_BLOCK_HEADER, COLUMN_EPOCH_START, COLUMN_SCRIPT_TO_CELLS,
+        COLUMN_UNCLE_TO_BLOCK,
+    };
+    use ckb_core::block::{Block, BlockBuilder};
+    use ckb_core::extras::BlockExt;
+    use ckb_core::header::HeaderBuilder;
+    use ckb_core::script::Script;
+    use ckb_core::transaction::{CellInput, CellOutput, OutPoint, TransactionBuilder};
+    use ckb_core::uncle::UncleBlock;
+    use ckb_core::{Bytes, Capacity};
+    use ckb_db::rocksdb::VERSION_VALUE;
+    use ckb_db::{DBConfig, MemoryKeyValueDB};
+    use numext_fixed_hash::h256;
+
+    // A column only known to the toy version 0.2.0, which maps each header hash to its length
+    const COLUMN_HEADER_SIZE: u32 = COLUMNS;
+
+    struct AddHeaderSize;
+
+    impl Migration<RocksDB> for AddHeaderSize {
+        fn version(&self) -> &str {
+            "0.2.0"
+        }
+
+        fn migrate(&self, db: &RocksDB) -> Result<(), Error> {
+            let mut batch = db.batch()?;
+            db.traverse(COLUMN_BLOCK_HEADER, |key, value| {
+                batch.insert(COLUMN_HEADER_SIZE, key, &(value.len() as u64).to_le_bytes())
+            })?;
+            batch.commit()
+        }
+    }
+
+    struct Noop(&'static str);
+
+    impl Migration<RocksDB> for Noop {
+        fn version(&self) -> &str {
+            self.0
+        }
+
+        fn migrate(&self, _db: &RocksDB) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    fn empty_runner() -> MigrationRunner {
+        MigrationRunner {
+            migrations: Vec::new(),
+        }
+    }
+
+    fn setup_db(prefix: &str) -> RocksDB {
+        let tmp_dir = tempfile::Builder::new().prefix(prefix).tempdir().unwrap();
+        let config = DBConfig {
+            path: tmp_dir.as_ref().to_path_buf(),
+            ..Default::default()
+        };
+        let db = RocksDB::open_for_migration(&config, COLUMNS + 1).unwrap();
+        let mut batch = db.batch().unwrap();
+        batch.insert(COLUMN_BLOCK_HEADER, b"a", &[0; 3]).unwrap();
+        batch.insert(COLUMN_BLOCK_HEADER, b"b", &[0; 5]).unwrap();
+        batch.commit().unwrap();
+        db.set_version("0.1.0").unwrap();
+        db
+    }
+
+    #[test]
+    fn migrate_to_toy_version() {
+        let db = setup_db("migrate_to_toy_version");
+        let runner = empty_runner()
+            .add_migration(Box::new(AddHeaderSize))
+            .add_migration(Box::new(Noop("0.1.0")));
+
+        assert_eq!(runner.run(&db, "0.2.0"), Ok("0.2.0".to_owned()));
+        assert_eq!(db.version(), Ok(Some("0.2.0".to_owned())));
+        assert_eq!(
+            db.read(COLUMN_HEADER_SIZE, b"a"),
+            Ok(Some(3u64.to_le_bytes().to_vec()))
+        );
+        assert_eq!(
+            db.read(COLUMN_HEADER_SIZE, b"b"),
+            Ok(Some(5u64.to_le_bytes().to_vec()))
+        );
+
+        // already at 0.2.0, nothing to do
+        assert_eq!(runner.run(&db, "0.2.0"), Ok("0.2.0".to_owned()));
+    }
+
+    #[test]
+    fn migration_is_idempotent() {
+        let db = setup_db("migration_is_idempotent");
+        AddHeaderSize.migrate(&db).unwrap();
+        let runner = empty_runner().add_migration(Box::new(AddHeaderSize));
+
+        // as if the previous run was interrupted before saving the version
+        assert_eq!(runner.run(&db, "0.2.0"), Ok("0.2.0".to_owned()));
+        let mut sizes = Vec::new();
+        db.traverse(COLUMN_HEADER_SIZE, |key, value| {
+            sizes.push((key.to_vec(), value.to_vec()));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(sizes.len(), 2);
+    }
+
+    #[test]
+    fn missing_or_newer_versions() {
+        let db = setup_db("missing_or_newer_versions");
+        let runner = empty_runner().add_migration(Box::new(Noop("0.3.0")));
+        assert!(runner.run(&db, "0.4.0").is_err());
+        assert_eq!(db.version(), Ok(Some("0.3.0".to_owned())));
+        assert!(runner.run(&db, "0.2.0").is_err());
+        assert_eq!(db.version(), Ok(Some("0.3.0".to_owned())));
+
+        // a patch version is reached without any migration
+        assert_eq!(runner.run(&db, "0.3.1"), Ok("0.3.1".to_owned()));
+    }
+
+    #[test]
+    fn add_block_body_version() {
+        let db = MemoryKeyValueDB::open(COLUMNS as usize);
+        let block_hash = h256!("0x1");
+        let lock = Script::new(vec![], h256!("0x2"));
+        let transactions = vec![
+            TransactionBuilder::default()
+                .output(CellOutput::new(
+                    Capacity::shannons(100),
+                    Bytes::from(vec![1]),
+                    lock.clone(),
+                    None,
+                ))
+                .build(),
+            TransactionBuilder::default()
+                .output(CellOutput::new(
+                    Capacity::shannons(200),
+                    Bytes::new(),
+                    lock.clone(),
+                    Some(lock),
+                ))
+                .build(),
+        ];
+
+        // Store the block as the nodes before 0.1401.0 did, without the version identifier
+        let (body, mut addresses) = serialize_block_body(&transactions).unwrap();
+        let mut batch = db.batch().unwrap();
+        for (address, tx) in addresses.iter_mut().zip(&transactions) {
+            address.offset -= VERSION_SIZE;
+            let stored = address.clone().into_stored(block_hash.clone());
+            batch
+                .insert(
+                    COLUMN_TRANSACTION_ADDR,
+                    tx.hash().as_bytes(),
+                    &serialize(&stored).unwrap(),
+                )
+                .unwrap();
+        }
+        batch
+            .insert(
+                COLUMN_BLOCK_TRANSACTION_ADDRESSES,
+                block_hash.as_bytes(),
+                &serialize(&addresses).unwrap(),
+            )
+            .unwrap();
+        batch
+            .insert(
+                COLUMN_BLOCK_BODY,
+                block_hash.as_bytes(),
+                &body[VERSION_SIZE..],
+            )
+            .unwrap();
+        batch.commit().unwrap();
+
+        AddBlockBodyVersion.migrate(&db).unwrap();
+        assert_eq!(
+            db.read(COLUMN_BLOCK_BODY, block_hash.as_bytes()),
+            Ok(Some(body.clone()))
+        );
+        // running again leaves the upgraded block alone
+        AddBlockBodyVersion.migrate(&db).unwrap();
+        assert_eq!(
+            db.read(COLUMN_BLOCK_BODY, block_hash.as_bytes()),
+            Ok(Some(body))
+        );
+
+        let store = ChainKVStore::new(db);
+        assert_eq!(
+            store.get_block_txs_hashes(&block_hash),
+            Some(transactions.iter().map(|tx| tx.hash().to_owned()).collect())
+        );
+        for tx in &transactions {
+            assert_eq!(
+                store.get_transaction(tx.hash()),
+                Some((tx.to_owned(), block_hash.clone()))
+            );
+            assert_eq!(
+                store.get_cell_output(tx.hash(), 0),
+                tx.outputs().get(0).cloned()
+            );
+        }
+    }
+
+    #[test]
+    fn add_epoch_max_block_bytes() {
+        let db = MemoryKeyValueDB::open(COLUMNS as usize);
+        let epoch_hash = h256!("0x1");
+        let epoch = Consensus::default().genesis_epoch_ext().to_owned();
+        // `EpochExt` as stored before 0.1402.0
+        let raw = serialize(&epoch).unwrap();
+        let old_raw = &raw[..raw.len() - mem::size_of::<u64>()];
+        let mut batch = db.batch().unwrap();
+        batch
+            .insert(COLUMN_EPOCH, epoch_hash.as_bytes(), old_raw)
+            .unwrap();
+        batch
+            .insert(COLUMN_EPOCH, &0u64.to_le_bytes(), epoch_hash.as_bytes())
+            .unwrap();
+        batch
+            .insert(COLUMN_META, META_CURRENT_EPOCH_KEY, old_raw)
+            .unwrap();
+        batch.commit().unwrap();
+
+        let migration = AddEpochMaxBlockBytes {
+            max_block_bytes: 1_000_000,
+        };
+        migration.migrate(&db).unwrap();
+        migration.migrate(&db).unwrap();
+
+        let store = ChainKVStore::new(db);
+        let mut expected = epoch;
+        expected.set_max_block_bytes(1_000_000);
+        assert_eq!(store.get_epoch_ext(&epoch_hash), Some(expected.clone()));
+        assert_eq!(store.get_epoch_index(0), Some(epoch_hash));
+        assert_eq!(store.get_current_epoch_ext(), Some(expected));
+    }
+
+    // The columns of the main chain indexes added after 0.1400.0
+    const MAIN_CHAIN_INDEXES: [Col; 3] = [
+        COLUMN_UNCLE_TO_BLOCK,
+        COLUMN_EPOCH_START,
+        COLUMN_SCRIPT_TO_CELLS,
+    ];
+
+    fn entries(db: &RocksDB, col: Col) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut entries = Vec::new();
+        db.traverse(col, |key, value| {
+            entries.push((key.to_vec(), value.to_vec()));
+            Ok(())
+        })
+        .unwrap();
+        entries
+    }
+
+    // Rewrite the database as 0.1400.0 stored it: the block bodies have no version
+    // identifier, the epochs have no `max_block_bytes`, the block exts have no `size` and
+    // `reward_components` and the main chain indexes added since are missing
+    fn downgrade_to_v1400(db: &RocksDB) {
+        let mut batch = db.batch().unwrap();
+        for (block_hash, raw) in entries(db, COLUMN_BLOCK_TRANSACTION_ADDRESSES) {
+            let mut addresses: Vec<TransactionAddressInner> = deserialize(&raw).unwrap();
+            for address in &mut addresses {
+                address.offset -= VERSION_SIZE;
+            }
+            let body = db.read(COLUMN_BLOCK_BODY, &block_hash).unwrap().unwrap();
+            batch
+                .insert(
+                    COLUMN_BLOCK_TRANSACTION_ADDRESSES,
+                    &block_hash,
+                    &serialize(&addresses).unwrap(),
+                )
+                .unwrap();
+            batch
+                .insert(COLUMN_BLOCK_BODY, &block_hash, &body[VERSION_SIZE..])
+                .unwrap();
+        }
+        for (tx_hash, raw) in entries(db, COLUMN_TRANSACTION_ADDR) {
+            let mut stored: TransactionAddressStored = deserialize(&raw).unwrap();
+            stored.inner.offset -= VERSION_SIZE;
+            batch
+                .insert(
+                    COLUMN_TRANSACTION_ADDR,
+                    &tx_hash,
+                    &serialize(&stored).unwrap(),
+                )
+                .unwrap();
+        }
+        for (key, raw) in entries(db, COLUMN_EPOCH) {
+            if key.len() != mem::size_of::<EpochNumber>() {
+                batch
+                    .insert(
+                        COLUMN_EPOCH,
+                        &key,
+                        &raw[..raw.len() - mem::size_of::<u64>()],
+                    )
+                    .unwrap();
+            }
+        }
+        // The exts of the test chain have no `reward_components`, a single byte
+        for (block_hash, raw) in entries(db, COLUMN_EXT) {
+            batch
+                .insert(
+                    COLUMN_EXT,
+                    &block_hash,
+                    &raw[..raw.len() - mem::size_of::<u32>() - 1],
+                )
+                .unwrap();
+        }
+        let raw = db
+            .read(COLUMN_META, META_CURRENT_EPOCH_KEY)
+            .unwrap()
+            .unwrap();
+        batch
+            .insert(
+                COLUMN_META,
+                META_CURRENT_EPOCH_KEY,
+                &raw[..raw.len() - mem::size_of::<u64>()],
+            )
+            .unwrap();
+        for col in &MAIN_CHAIN_INDEXES {
+            for (key, _) in entries(db, *col) {
+                batch.delete(*col, &key).unwrap();
+            }
+        }
+        for key in &[
+            META_TOTAL_TX_COUNT_KEY,
+            META_TOTAL_CELLS_CREATED_COUNT_KEY,
+            META_TOTAL_CELLS_CONSUMED_COUNT_KEY,
+        ] {
+            batch.delete(COLUMN_META, key).unwrap();
+        }
+        batch.commit().unwrap();
+        db.set_version("0.1400.0").unwrap();
+    }
+
+    // Blocks 1 to 4 after the genesis, block 2 includes an uncle and block 3 starts epoch 1
+    fn build_chain(genesis: &Block) -> Vec<Block> {
+        let uncle = UncleBlock::new(HeaderBuilder::default().number(1).build(), vec![]);
+        let mut parent = genesis.header().to_owned();
+        let mut blocks = Vec::new();
+        for number in 1..=4 {
+            let tx = TransactionBuilder::default()
+                .input(CellInput::new(OutPoint::null(), number))
+                .output(CellOutput::new(
+                    Capacity::shannons(100 * number),
+                    Bytes::new(),
+                    Script::new(vec![], h256!("0x1")),
+                    Some(Script::new(vec![], h256!("0x2"))),
+                ))
+                .build();
+            let uncles = if number == 2 {
+                vec![uncle.clone()]
+            } else {
+                vec![]
+            };
+            let block = BlockBuilder::default()
+                .header_builder(
+                    HeaderBuilder::default()
+                        .parent_hash(parent.hash().to_owned())
+                        .number(number)
+                        .epoch(if number < 3 { 0 } else { 1 }),
+                )
+                .transaction(tx)
+                .uncles(uncles)
+                .build();
+            parent = block.header().to_owned();
+            blocks.push(block);
+        }
+        blocks
+    }
+
+    #[test]
+    fn migrate_from_v1400() {
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("migrate_from_v1400")
+            .tempdir()
+            .unwrap();
+        let config = DBConfig {
+            path: tmp_dir.as_ref().to_path_buf(),
+            ..Default::default()
+        };
+        let db = RocksDB::open_for_migration(&config, COLUMNS).unwrap();
+        let consensus = Consensus::default();
+        let store = ChainKVStore::new(Borrowed(&db));
+        store.init(&consensus).unwrap();
+        let blocks = build_chain(consensus.genesis_block());
+        for (index, block) in blocks.iter().enumerate() {
+            let mut batch = store.new_batch().unwrap();
+            batch.insert_block(block).unwrap();
+            batch
+                .insert_block_ext(block.header().hash(), &BlockExt::default())
+                .unwrap();
+            batch.attach_block(block).unwrap();
+            if index == 2 {
+                batch.insert_epoch_start(1, block.header().hash()).unwrap();
+            }
+            batch.insert_tip_header(block.header()).unwrap();
+            let mut counts = store.get_total_counts();
+            counts.attach(block);
+            batch.insert_total_counts(&counts).unwrap();
+            batch.commit().unwrap();
+        }
+        let indexes = |db: &RocksDB| {
+            MAIN_CHAIN_INDEXES
+                .iter()
+                .map(|col| entries(db, *col))
+                .collect::<Vec<_>>()
+        };
+        let expected_indexes = indexes(&db);
+        let expected_counts = store.get_total_counts();
+        assert_eq!(expected_counts.transactions, 4);
+
+        downgrade_to_v1400(&db);
+        assert!(indexes(&db).iter().all(Vec::is_empty));
+        assert_eq!(
+            MigrationRunner::new(&consensus).run(&db, VERSION_VALUE),
+            Ok(VERSION_VALUE.to_owned())
+        );
+        assert_eq!(indexes(&db), expected_indexes);
+
+        let store = ChainKVStore::new(Borrowed(&db));
+        assert_eq!(store.get_total_counts(), expected_counts);
+        assert_eq!(
+            store.get_blocks_including_uncle(blocks[1].uncles()[0].hash()),
+            vec![blocks[1].header().hash().to_owned()]
+        );
+        assert_eq!(
+            store.get_epoch_start_block(1).as_ref(),
+            Some(blocks[2].header())
+        );
+        let lock_hash = blocks[0].transactions()[0].outputs()[0].lock.hash();
+        assert_eq!(store.get_cells_by_script(&lock_hash, 0, 10).len(), 4);
+        let proof_size = consensus.pow_engine().proof_size();
+        for block in blocks.iter().chain(Some(consensus.genesis_block())) {
+            assert_eq!(store.get_block(block.header().hash()).as_ref(), Some(block));
+            let ext = store.get_block_ext(block.header().hash()).unwrap();
+            assert_eq!(ext.size, block.serialized_size(proof_size) as u32);
+            assert_eq!(ext.reward_components, None);
+        }
+        assert_eq!(
+            store
+                .get_current_epoch_ext()
+                .map(|epoch| epoch.max_block_bytes()),
+            Some(consensus.max_block_bytes())
+        );
+    }
+}
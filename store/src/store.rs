@@ -1,39 +1,117 @@
 use crate::flat_block_body::{
-    deserialize_block_body, deserialize_block_body_for_hashes_only, deserialize_transaction,
-    serialize_block_body, serialize_block_body_size, TransactionAddressInner,
-    TransactionAddressStored,
+    decode_flat_block_body, deserialize_block_body, deserialize_block_body_for_hashes_only,
+    deserialize_transaction, is_compressed, serialize_block_body_compressed,
+    serialize_block_body_size, Compression, TransactionAddressInner, TransactionAddressStored,
 };
 use crate::{
-    COLUMN_BLOCK_BODY, COLUMN_BLOCK_EPOCH, COLUMN_BLOCK_HEADER, COLUMN_BLOCK_PROPOSAL_IDS,
+    COLUMNS, COLUMN_BLOCK_BODY, COLUMN_BLOCK_EPOCH, COLUMN_BLOCK_HEADER, COLUMN_BLOCK_PROPOSAL_IDS,
     COLUMN_BLOCK_TRANSACTION_ADDRESSES, COLUMN_BLOCK_UNCLE, COLUMN_CELL_META, COLUMN_CELL_SET,
-    COLUMN_EPOCH, COLUMN_EXT, COLUMN_INDEX, COLUMN_META, COLUMN_TRANSACTION_ADDR, COLUMN_UNCLES,
+    COLUMN_EPOCH, COLUMN_EPOCH_START, COLUMN_EXT, COLUMN_INDEX, COLUMN_META,
+    COLUMN_SCRIPT_TO_CELLS, COLUMN_TRANSACTION_ADDR, COLUMN_UNCLES, COLUMN_UNCLE_TO_BLOCK,
 };
 use bincode::{deserialize, serialize};
 use ckb_chain_spec::consensus::Consensus;
 use ckb_core::block::{Block, BlockBuilder};
 use ckb_core::cell::{BlockInfo, CellMeta};
 use ckb_core::extras::{
-    BlockExt, DaoStats, EpochExt, TransactionAddress, DEFAULT_ACCUMULATED_RATE,
+    BlockExt, DaoStats, EpochExt, EpochInfo, TransactionAddress, TransactionConfirmation,
+    DEFAULT_ACCUMULATED_RATE,
 };
 use ckb_core::header::{BlockNumber, Header};
 use ckb_core::transaction::{CellKey, CellOutPoint, CellOutput, ProposalShortId, Transaction};
 use ckb_core::transaction_meta::TransactionMeta;
 use ckb_core::uncle::UncleBlock;
 use ckb_core::{Capacity, EpochNumber};
-use ckb_db::{Col, DbBatch, Error, KeyValueDB};
+use ckb_db::{Col, DbBatch, Error, KeyValueDB, RocksDB};
 use lru_cache::LruCache;
 use numext_fixed_hash::H256;
 use serde_derive::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
+use std::mem;
 use std::ops::Range;
-use std::sync::Mutex;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 
 const META_TIP_HEADER_KEY: &[u8] = b"TIP_HEADER";
-const META_CURRENT_EPOCH_KEY: &[u8] = b"CURRENT_EPOCH";
+pub(crate) const META_CURRENT_EPOCH_KEY: &[u8] = b"CURRENT_EPOCH";
+pub(crate) const META_TOTAL_TX_COUNT_KEY: &[u8] = b"TOTAL_TX_COUNT";
+pub(crate) const META_TOTAL_CELLS_CREATED_COUNT_KEY: &[u8] = b"TOTAL_CELLS_CREATED_COUNT";
+pub(crate) const META_TOTAL_CELLS_CONSUMED_COUNT_KEY: &[u8] = b"TOTAL_CELLS_CONSUMED_COUNT";
+
+// script_hash (32) ++ block_number (8) ++ tx_hash (32) ++ output_index (4), the integers are
+// big endian so the keys of a script sort by block number.
+const SCRIPT_CELL_KEY_LEN: usize = 76;
+
+fn script_cell_key(
+    script_hash: &H256,
+    block_number: BlockNumber,
+    tx_hash: &H256,
+    index: u32,
+) -> Vec<u8> {
+    let mut key = Vec::with_capacity(SCRIPT_CELL_KEY_LEN);
+    key.extend_from_slice(script_hash.as_bytes());
+    key.extend_from_slice(&block_number.to_be_bytes());
+    key.extend_from_slice(tx_hash.as_bytes());
+    key.extend_from_slice(&index.to_be_bytes());
+    key
+}
+
+/// The hashes of the lock script and of the type script if any
+fn output_script_hashes(output: &CellOutput) -> Vec<H256> {
+    let lock_hash = output.lock.hash();
+    match output.type_.as_ref().map(|type_| type_.hash()) {
+        Some(type_hash) if type_hash != lock_hash => vec![lock_hash, type_hash],
+        _ => vec![lock_hash],
+    }
+}
+
+/// Running totals over the main chain blocks, kept in `COLUMN_META` and updated in the
+/// batch which attaches or detaches the blocks
+#[derive(Clone, Copy, Default, Eq, PartialEq, Debug)]
+pub struct TotalCounts {
+    /// Committed transactions, cellbases included
+    pub transactions: u64,
+    pub cells_created: u64,
+    /// Cells spent by the inputs of the transactions
+    pub cells_consumed: u64,
+}
+
+impl TotalCounts {
+    pub fn attach(&mut self, block: &Block) {
+        let (transactions, cells_created, cells_consumed) = block_counts(block);
+        self.transactions += transactions;
+        self.cells_created += cells_created;
+        self.cells_consumed += cells_consumed;
+    }
+
+    pub fn detach(&mut self, block: &Block) {
+        let (transactions, cells_created, cells_consumed) = block_counts(block);
+        self.transactions = self.transactions.saturating_sub(transactions);
+        self.cells_created = self.cells_created.saturating_sub(cells_created);
+        self.cells_consumed = self.cells_consumed.saturating_sub(cells_consumed);
+    }
+}
+
+fn block_counts(block: &Block) -> (u64, u64, u64) {
+    let transactions = block.transactions();
+    let cells_created = transactions
+        .iter()
+        .map(|tx| tx.outputs().len() as u64)
+        .sum();
+    let cells_consumed = transactions
+        .iter()
+        .map(|tx| tx.input_pts_iter().filter(|pt| pt.cell.is_some()).count() as u64)
+        .sum();
+    (transactions.len() as u64, cells_created, cells_consumed)
+}
 
 #[derive(Clone, Serialize, Deserialize, Eq, PartialEq, Hash, Debug)]
 pub struct StoreConfig {
     pub header_cache_size: usize,
     pub cell_output_cache_size: usize,
+    #[serde(default)]
+    pub block_body_compression: Compression,
 }
 
 impl Default for StoreConfig {
@@ -41,14 +119,31 @@ impl Default for StoreConfig {
         Self {
             header_cache_size: 4096,
             cell_output_cache_size: 128,
+            block_body_compression: Compression::None,
         }
     }
 }
 
+/// Number of entries and bytes stored in a column
+#[derive(Clone, Serialize, Deserialize, Eq, PartialEq, Default, Debug)]
+pub struct ColumnSize {
+    pub column: Col,
+    pub keys: u64,
+    pub key_bytes: u64,
+    pub value_bytes: u64,
+}
+
+impl ColumnSize {
+    pub fn total_bytes(&self) -> u64 {
+        self.key_bytes + self.value_bytes
+    }
+}
+
 pub struct ChainKVStore<T> {
     db: T,
     header_cache: Mutex<LruCache<H256, Header>>,
     cell_output_cache: Mutex<LruCache<(H256, u32), CellOutput>>,
+    block_body_compression: Compression,
 }
 
 impl<T: KeyValueDB> ChainKVStore<T> {
@@ -61,6 +156,7 @@ impl<T: KeyValueDB> ChainKVStore<T> {
             db,
             header_cache: Mutex::new(LruCache::new(config.header_cache_size)),
             cell_output_cache: Mutex::new(LruCache::new(config.cell_output_cache_size)),
+            block_body_compression: config.block_body_compression,
         }
     }
 
@@ -80,6 +176,78 @@ impl<T: KeyValueDB> ChainKVStore<T> {
     {
         self.db.traverse(col, callback)
     }
+
+    /// Walk through every column and sum up its entries. The sizes are the raw
+    /// key and value lengths, they do not account for the compression and the
+    /// overhead of the underlying database.
+    pub fn estimate_column_sizes(&self) -> Result<Vec<ColumnSize>, Error> {
+        (0..COLUMNS)
+            .map(|column| {
+                let mut size = ColumnSize {
+                    column,
+                    ..Default::default()
+                };
+                self.traverse(column, |key, value| {
+                    size.keys += 1;
+                    size.key_bytes += key.len() as u64;
+                    size.value_bytes += value.len() as u64;
+                    Ok(())
+                })?;
+                Ok(size)
+            })
+            .collect()
+    }
+
+    /// Get the cell set entry of a transaction whose outputs are being revived. The entry
+    /// is deleted once all the outputs are spent, in which case it is rebuilt from the
+    /// transaction with all the outputs dead.
+    fn get_spent_transaction_meta(&self, tx_hash: &H256) -> Result<TransactionMeta, Error> {
+        if let Some(raw) = self.get(COLUMN_CELL_SET, tx_hash.as_bytes()) {
+            return Ok(deserialize(&raw[..]).expect("deserialize TransactionMeta should be ok"));
+        }
+
+        let (tx, block_hash) = self
+            .get_transaction(tx_hash)
+            .ok_or_else(|| Error::DBError(format!("transaction {:#x} not found", tx_hash)))?;
+        let header = self
+            .get_block_header(&block_hash)
+            .ok_or_else(|| Error::DBError(format!("block {:#x} not found", block_hash)))?;
+        let meta = if tx.is_cellbase() {
+            TransactionMeta::new_cellbase(header.number(), header.epoch(), tx.outputs().len(), true)
+        } else {
+            TransactionMeta::new(header.number(), header.epoch(), tx.outputs().len(), true)
+        };
+        Ok(meta)
+    }
+
+    /// Read a range of the uncompressed block body, the range is usually
+    /// taken from a transaction address.
+    fn get_block_body_slice(&self, block_hash: &H256, range: &Range<usize>) -> Option<Vec<u8>> {
+        // Uncompressed bodies are sliced right away, the first byte tells us
+        // whether we have to fall back to decompress the whole body.
+        if let Some(prefix) =
+            self.partial_get(COLUMN_BLOCK_BODY, block_hash.as_bytes(), &(0..range.end))
+        {
+            if prefix.first().map_or(false, |first| !is_compressed(*first)) {
+                return prefix.get(range.start..).map(<[u8]>::to_vec);
+            }
+        }
+        self.get(COLUMN_BLOCK_BODY, block_hash.as_bytes())
+            .and_then(|raw| {
+                decode_flat_block_body(&raw)
+                    .expect("decode block body should be ok")
+                    .get(range.start..range.end)
+                    .map(<[u8]>::to_vec)
+            })
+    }
+}
+
+impl ChainKVStore<RocksDB> {
+    /// Copy the database to `dest` for a backup while the node keeps running, see
+    /// `RocksDB::checkpoint`
+    pub fn backup_to(&self, dest: &Path) -> Result<(), Error> {
+        self.db.checkpoint(dest)
+    }
 }
 
 /// Store interface by chain
@@ -105,6 +273,26 @@ pub trait ChainStore: Sync + Send {
     fn get_block_ext(&self, block_hash: &H256) -> Option<BlockExt>;
 
     fn init(&self, consensus: &Consensus) -> Result<(), Error>;
+
+    /// Delete the main chain blocks after `keep_from` and rewind the tip to it, the cell set
+    /// and the indexes are reverted as if the blocks were detached. The epochs started after
+    /// `keep_from` are deleted as well. Return the number of deleted blocks.
+    fn delete_blocks_after(&self, keep_from: BlockNumber) -> Result<u64, Error>;
+    /// Delete the transaction addresses and the cell metas of the transactions committed in
+    /// the main chain blocks before `before_block` whose outputs are all spent, so that they
+    /// can no longer be looked up. The body of a block is deleted once all its transactions
+    /// are vacuumed, `get_block` returns `None` for it. Return the number of vacuumed
+    /// transactions.
+    ///
+    /// The entries are dropped in compaction filters, see `KeyValueDB::compact_with_filter`,
+    /// rather than deleted one by one. A reorg reviving the outputs of a vacuumed transaction
+    /// fails, so the blocks must be at least `finalization_delay_length` below the tip, a
+    /// later block is refused.
+    fn vacuum_spent_transactions(
+        &self,
+        before_block: BlockNumber,
+        finalization_delay_length: BlockNumber,
+    ) -> Result<u64, Error>;
     /// Get block header hash by block number
     fn get_block_hash(&self, number: BlockNumber) -> Option<H256>;
     /// Get block number by block header hash
@@ -114,8 +302,24 @@ pub trait ChainStore: Sync + Send {
     /// Get commit transaction and block hash by it's hash
     fn get_transaction(&self, h: &H256) -> Option<(Transaction, H256)>;
     fn get_transaction_address(&self, hash: &H256) -> Option<TransactionAddress>;
+    /// Look up the addresses of several transactions, in the order of `hashes`
+    fn get_transaction_addresses_batch(&self, hashes: &[H256]) -> Vec<Option<TransactionAddress>>;
+    /// Check whether each of `hashes` is a committed transaction
+    fn contain_transactions_batch(&self, hashes: &[H256]) -> Vec<bool>;
+    /// Get the block and the position in it of a committed transaction
+    fn get_transaction_confirmation_status(&self, hash: &H256) -> Option<TransactionConfirmation>;
     fn get_cell_meta(&self, tx_hash: &H256, index: u32) -> Option<CellMeta>;
+    /// Look up the metas of several cells, in the order of `out_points`
+    fn get_cell_metas_batch(&self, out_points: &[CellOutPoint]) -> Vec<Option<CellMeta>>;
     fn get_cell_output(&self, tx_hash: &H256, index: u32) -> Option<CellOutput>;
+    /// Get the out points of the main chain cells whose lock or type script has the hash,
+    /// ordered by the number of the block that created them. Spent cells are included.
+    fn get_cells_by_script(
+        &self,
+        script_hash: &H256,
+        skip: usize,
+        take: usize,
+    ) -> Vec<CellOutPoint>;
     // Get current epoch ext
     fn get_current_epoch_ext(&self) -> Option<EpochExt>;
     // Get epoch ext by epoch index
@@ -124,16 +328,104 @@ pub trait ChainStore: Sync + Send {
     fn get_epoch_index(&self, number: EpochNumber) -> Option<H256>;
     // Get epoch index by block hash
     fn get_block_epoch_index(&self, h256: &H256) -> Option<H256>;
+    /// Get the number of the epoch the block belongs to
+    fn get_block_epoch_number(&self, block_hash: &H256) -> Option<EpochNumber> {
+        self.get_block_epoch_index(block_hash)
+            .and_then(|index| self.get_epoch_ext(&index))
+            .map(|epoch| epoch.number())
+    }
+    /// Get the header of the first main chain block in the epoch
+    fn get_epoch_start_block(&self, epoch_number: EpochNumber) -> Option<Header>;
+    /// Get the number of the epoch the tip belongs to
+    fn get_current_epoch(&self, tip: &Header) -> EpochNumber {
+        tip.epoch()
+    }
+    /// Get the start, length and difficulty of an epoch of the main chain
+    fn get_epoch_info(&self, epoch_number: EpochNumber) -> Option<EpochInfo> {
+        let epoch = self
+            .get_epoch_index(epoch_number)
+            .and_then(|index| self.get_epoch_ext(&index))?;
+        let start_block = self.get_epoch_start_block(epoch_number)?;
+        Some(EpochInfo {
+            start_number: epoch.start_number(),
+            length: epoch.length(),
+            start_block_hash: start_block.hash().to_owned(),
+            difficulty: epoch.difficulty().to_owned(),
+        })
+    }
+    /// Get the info of the epoch the tip belongs to
+    fn get_current_epoch_info(&self, tip: &Header) -> Option<EpochInfo> {
+        self.get_epoch_info(self.get_current_epoch(tip))
+    }
+    /// Get the hashes of the main chain blocks in the epoch, up to the tip if the epoch is
+    /// the current one, or an empty vec if the epoch has not started
+    fn get_block_hashes_in_epoch(&self, epoch_number: EpochNumber) -> Vec<H256> {
+        match self.get_epoch_info(epoch_number) {
+            Some(info) => (info.start_number..info.start_number + info.length)
+                .scan((), |_, number| self.get_block_hash(number))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+    /// Get the main chain blocks in the epoch, see `get_block_hashes_in_epoch`
+    fn get_blocks_in_epoch(&self, epoch_number: EpochNumber) -> Vec<Block> {
+        self.get_block_hashes_in_epoch(epoch_number)
+            .into_iter()
+            .filter_map(|hash| self.get_block(&hash))
+            .collect()
+    }
+    /// Get the running totals over the main chain
+    fn get_total_counts(&self) -> TotalCounts;
+    /// Get the number of transactions committed in the main chain, cellbases included
+    fn get_total_transactions_count(&self) -> u64 {
+        self.get_total_counts().transactions
+    }
+    /// Get the number of cells created in the main chain, spent or not
+    fn get_total_cells_created_count(&self) -> u64 {
+        self.get_total_counts().cells_created
+    }
+    /// Get the number of cells spent in the main chain
+    fn get_total_cells_consumed_count(&self) -> u64 {
+        self.get_total_counts().cells_consumed
+    }
     fn traverse_cell_set<F>(&self, callback: F) -> Result<(), Error>
     where
         F: FnMut(H256, TransactionMeta) -> Result<(), Error>;
     fn is_uncle(&self, hash: &H256) -> bool;
+    /// Get the hashes of the main chain blocks which included the uncle
+    fn get_blocks_including_uncle(&self, uncle_hash: &H256) -> Vec<H256>;
     // Get cellbase by block hash
     fn get_cellbase(&self, hash: &H256) -> Option<Transaction>;
+    /// Iterate over the main chain blocks from genesis to tip, blocks are read lazily
+    fn iter_all_blocks(&self) -> Box<dyn Iterator<Item = Block> + '_> {
+        let mut hashes = Vec::new();
+        let mut header = self.get_tip_header();
+        while let Some(current) = header {
+            hashes.push(current.hash().to_owned());
+            header = if current.is_genesis() {
+                None
+            } else {
+                self.get_block_header(current.parent_hash())
+            };
+        }
+        Box::new(
+            hashes
+                .into_iter()
+                .rev()
+                .filter_map(move |hash| self.get_block(&hash)),
+        )
+    }
 }
 
 pub trait StoreBatch {
     fn insert_block(&mut self, block: &Block) -> Result<(), Error>;
+    /// Insert the block body, LZ4 compressed when `compress` is true
+    fn insert_block_body_compressed(
+        &mut self,
+        hash: &H256,
+        transactions: &[Transaction],
+        compress: bool,
+    ) -> Result<(), Error>;
     fn insert_block_ext(&mut self, block_hash: &H256, ext: &BlockExt) -> Result<(), Error>;
     fn insert_tip_header(&mut self, header: &Header) -> Result<(), Error>;
     fn insert_current_epoch_ext(&mut self, epoch: &EpochExt) -> Result<(), Error>;
@@ -143,9 +435,19 @@ pub trait StoreBatch {
         epoch_hash: &H256,
     ) -> Result<(), Error>;
     fn insert_epoch_ext(&mut self, hash: &H256, epoch: &EpochExt) -> Result<(), Error>;
+    /// Mark the block as the first main chain block of the epoch
+    fn insert_epoch_start(
+        &mut self,
+        epoch_number: EpochNumber,
+        block_hash: &H256,
+    ) -> Result<(), Error>;
+    fn delete_epoch_start(&mut self, epoch_number: EpochNumber) -> Result<(), Error>;
+    fn insert_total_counts(&mut self, counts: &TotalCounts) -> Result<(), Error>;
 
     fn attach_block(&mut self, block: &Block) -> Result<(), Error>;
     fn detach_block(&mut self, block: &Block) -> Result<(), Error>;
+    /// Remove the transaction addresses of all transactions in the block
+    fn delete_transaction_addresses_for_block(&mut self, block: &Block) -> Result<(), Error>;
 
     fn update_cell_set(&mut self, tx_hash: &H256, meta: &TransactionMeta) -> Result<(), Error>;
     fn delete_cell_set(&mut self, tx_hash: &H256) -> Result<(), Error>;
@@ -159,33 +461,40 @@ impl<T: KeyValueDB> ChainStore for ChainKVStore<T> {
     fn new_batch(&self) -> Result<Self::Batch, Error> {
         Ok(DefaultStoreBatch {
             inner: self.db.batch()?,
+            block_body_compression: self.block_body_compression,
         })
     }
 
     fn get_block(&self, h: &H256) -> Option<Block> {
-        self.get_block_header(h).map(|header| {
-            let transactions = self
-                .get_block_body(h)
-                .expect("block transactions must be stored");
-            let uncles = self
-                .get_block_uncles(h)
-                .expect("block uncles must be stored");
-            let proposals = self
-                .get_block_proposal_txs_ids(h)
-                .expect("block proposal_ids must be stored");
+        let header = self.get_block_header(h)?;
+        // The body is gone once the transactions are all vacuumed
+        let transactions = self.get_block_body(h)?;
+        let uncles = self
+            .get_block_uncles(h)
+            .expect("block uncles must be stored");
+        let proposals = self
+            .get_block_proposal_txs_ids(h)
+            .expect("block proposal_ids must be stored");
+        Some(
             BlockBuilder::default()
                 .header(header)
                 .uncles(uncles)
                 .transactions(transactions)
                 .proposals(proposals)
-                .build()
-        })
+                .build(),
+        )
     }
 
     fn is_uncle(&self, hash: &H256) -> bool {
         self.get(COLUMN_UNCLES, hash.as_bytes()).is_some()
     }
 
+    fn get_blocks_including_uncle(&self, uncle_hash: &H256) -> Vec<H256> {
+        self.get(COLUMN_UNCLE_TO_BLOCK, uncle_hash.as_bytes())
+            .map(|raw| deserialize(&raw[..]).expect("deserialize block hashes should be ok"))
+            .unwrap_or_default()
+    }
+
     fn get_block_header(&self, hash: &H256) -> Option<Header> {
         let mut header_cache_unlocked = self
             .header_cache
@@ -279,6 +588,8 @@ impl<T: KeyValueDB> ChainStore for ChainKVStore<T> {
                     .unwrap_or_else(Capacity::zero)
                     .as_u64(),
             },
+            size: genesis.serialized_size(consensus.pow_engine().proof_size()) as u32,
+            reward_components: None,
         };
 
         let mut cells = Vec::with_capacity(genesis.transactions().len());
@@ -314,10 +625,193 @@ impl<T: KeyValueDB> ChainStore for ChainKVStore<T> {
         batch.insert_current_epoch_ext(epoch)?;
         batch.insert_block_epoch_index(&genesis_hash, epoch.last_block_hash_in_previous_epoch())?;
         batch.insert_epoch_ext(epoch.last_block_hash_in_previous_epoch(), &epoch)?;
+        batch.insert_epoch_start(epoch.number(), &genesis_hash)?;
         batch.attach_block(genesis)?;
+        let mut counts = TotalCounts::default();
+        counts.attach(genesis);
+        batch.insert_total_counts(&counts)?;
         batch.commit()
     }
 
+    fn vacuum_spent_transactions(
+        &self,
+        before_block: BlockNumber,
+        finalization_delay_length: BlockNumber,
+    ) -> Result<u64, Error> {
+        let tip_number = match self.get_tip_header() {
+            Some(tip) => tip.number(),
+            None => return Ok(0),
+        };
+        if before_block.saturating_add(finalization_delay_length) > tip_number + 1 {
+            return Err(Error::DBError(format!(
+                "cannot vacuum the blocks before {}, the blocks within {} of the tip {} may be reverted",
+                before_block, finalization_delay_length, tip_number
+            )));
+        }
+        // The transactions with live outputs are in the cell set
+        let mut live = HashSet::new();
+        self.db.traverse(COLUMN_CELL_SET, |key, _| {
+            live.insert(key.to_vec());
+            Ok(())
+        })?;
+
+        // The cell metas tell the block number of each transaction
+        let spent = Arc::new(Mutex::new(HashSet::new()));
+        let spent_in_filter = Arc::clone(&spent);
+        self.db
+            .compact_with_filter(COLUMN_CELL_META, move |key, value| {
+                let tx_hash = &key[..32];
+                let vacuum = !live.contains(tx_hash)
+                    && deserialize::<CellMeta>(value)
+                        .ok()
+                        .and_then(|meta| meta.block_info)
+                        .map_or(false, |info| info.number < before_block);
+                if vacuum {
+                    spent_in_filter
+                        .lock()
+                        .expect("poisoned vacuum lock")
+                        .insert(tx_hash.to_vec());
+                }
+                vacuum
+            })?;
+        let spent = Arc::new(mem::replace(
+            &mut *spent.lock().expect("poisoned vacuum lock"),
+            HashSet::new(),
+        ));
+
+        let blocks = Arc::new(Mutex::new(HashSet::new()));
+        let (spent_in_filter, blocks_in_filter) = (Arc::clone(&spent), Arc::clone(&blocks));
+        self.db
+            .compact_with_filter(COLUMN_TRANSACTION_ADDR, move |key, value| {
+                if !spent_in_filter.contains(key) {
+                    return false;
+                }
+                if let Ok(stored) = deserialize::<TransactionAddressStored>(value) {
+                    blocks_in_filter
+                        .lock()
+                        .expect("poisoned vacuum lock")
+                        .insert(stored.block_hash);
+                }
+                true
+            })?;
+
+        // A block body goes once none of its transactions can be looked up
+        let emptied: HashSet<Vec<u8>> = blocks
+            .lock()
+            .expect("poisoned vacuum lock")
+            .iter()
+            .filter(|block_hash| {
+                self.get_block_txs_hashes(block_hash)
+                    .map_or(false, |tx_hashes| {
+                        tx_hashes.iter().all(|tx_hash| {
+                            self.get(COLUMN_TRANSACTION_ADDR, tx_hash.as_bytes())
+                                .is_none()
+                        })
+                    })
+            })
+            .map(|block_hash| block_hash.as_bytes().to_vec())
+            .collect();
+        if !emptied.is_empty() {
+            self.db
+                .compact_with_filter(COLUMN_BLOCK_BODY, move |key, _| emptied.contains(key))?;
+        }
+        // The outputs of the vacuumed transactions may be cached
+        self.cell_output_cache
+            .lock()
+            .expect("poisoned cell output cache lock")
+            .clear();
+        Ok(spent.len() as u64)
+    }
+
+    fn delete_blocks_after(&self, keep_from: BlockNumber) -> Result<u64, Error> {
+        let tip = match self.get_tip_header() {
+            Some(ref tip) if tip.number() > keep_from => tip.to_owned(),
+            _ => return Ok(0),
+        };
+        let keep_header = self
+            .get_block_hash(keep_from)
+            .and_then(|hash| self.get_block_header(&hash))
+            .ok_or_else(|| Error::DBError(format!("main chain block {} not found", keep_from)))?;
+        let keep_epoch = self
+            .get_block_epoch_index(keep_header.hash())
+            .and_then(|index| self.get_epoch_ext(&index))
+            .ok_or_else(|| Error::DBError(format!("epoch of block {} not found", keep_from)))?;
+        let tip_epoch = self
+            .get_current_epoch_ext()
+            .map_or(tip.epoch(), |epoch| epoch.number());
+
+        let mut batch = self.new_batch()?;
+        let mut counts = self.get_total_counts();
+        // The cell set entries changed so far, `None` means deleted
+        let mut tx_metas: HashMap<H256, Option<TransactionMeta>> = HashMap::new();
+        for number in (keep_from + 1..=tip.number()).rev() {
+            let block = self
+                .get_block_hash(number)
+                .and_then(|hash| self.get_block(&hash))
+                .ok_or_else(|| Error::DBError(format!("main chain block {} not found", number)))?;
+
+            // Walk backwards, a transaction may spend the outputs of a previous one in the block
+            for tx in block.transactions().iter().rev() {
+                tx_metas.insert(tx.hash().to_owned(), None);
+                for cell in tx.input_pts_iter().filter_map(|pt| pt.cell.as_ref()) {
+                    let mut meta = match tx_metas.remove(&cell.tx_hash) {
+                        Some(Some(meta)) => meta,
+                        _ => self.get_spent_transaction_meta(&cell.tx_hash)?,
+                    };
+                    meta.unset_dead(cell.index as usize);
+                    tx_metas.insert(cell.tx_hash.to_owned(), Some(meta));
+                }
+            }
+
+            batch.detach_block(&block)?;
+            counts.detach(&block);
+            let hash = block.header().hash().as_bytes();
+            for col in &[
+                COLUMN_BLOCK_HEADER,
+                COLUMN_BLOCK_BODY,
+                COLUMN_BLOCK_UNCLE,
+                COLUMN_BLOCK_PROPOSAL_IDS,
+                COLUMN_BLOCK_TRANSACTION_ADDRESSES,
+                COLUMN_EXT,
+                COLUMN_BLOCK_EPOCH,
+                // the epoch following the block, if it is the last one of its epoch
+                COLUMN_EPOCH,
+            ] {
+                batch.delete(*col, hash)?;
+            }
+        }
+
+        for (tx_hash, meta) in &tx_metas {
+            match meta {
+                Some(meta) => batch.update_cell_set(tx_hash, meta)?,
+                None => batch.delete_cell_set(tx_hash)?,
+            }
+        }
+        for epoch_number in keep_epoch.number() + 1..=tip_epoch {
+            // the epoch after `keep_from` is keyed by it when it ends its epoch
+            if let Some(index) = self.get_epoch_index(epoch_number) {
+                batch.delete(COLUMN_EPOCH, index.as_bytes())?;
+            }
+            batch.delete_epoch_start(epoch_number)?;
+            batch.delete(COLUMN_EPOCH, &epoch_number.to_le_bytes())?;
+        }
+        batch.insert_current_epoch_ext(&keep_epoch)?;
+        batch.insert_tip_header(&keep_header)?;
+        batch.insert_total_counts(&counts)?;
+        batch.commit()?;
+
+        self.header_cache
+            .lock()
+            .expect("poisoned header cache lock")
+            .clear();
+        self.cell_output_cache
+            .lock()
+            .expect("poisoned cell output cache lock")
+            .clear();
+
+        Ok(tip.number() - keep_from)
+    }
+
     fn get_block_hash(&self, number: BlockNumber) -> Option<H256> {
         self.get(COLUMN_INDEX, &number.to_le_bytes())
             .map(|raw| H256::from_slice(&raw[..]).expect("db safe access"))
@@ -341,6 +835,19 @@ impl<T: KeyValueDB> ChainStore for ChainKVStore<T> {
             .map(|raw| deserialize(&raw[..]).expect("db safe access"))
     }
 
+    fn get_total_counts(&self) -> TotalCounts {
+        let count = |key: &[u8]| {
+            self.get(COLUMN_META, key).map_or(0, |raw| {
+                u64::from_le_bytes(raw[..].try_into().expect("db safe access"))
+            })
+        };
+        TotalCounts {
+            transactions: count(META_TOTAL_TX_COUNT_KEY),
+            cells_created: count(META_TOTAL_CELLS_CREATED_COUNT_KEY),
+            cells_consumed: count(META_TOTAL_CELLS_CONSUMED_COUNT_KEY),
+        }
+    }
+
     fn get_epoch_ext(&self, hash: &H256) -> Option<EpochExt> {
         self.get(COLUMN_EPOCH, hash.as_bytes())
             .map(|raw| deserialize(&raw[..]).expect("db safe access"))
@@ -356,13 +863,20 @@ impl<T: KeyValueDB> ChainStore for ChainKVStore<T> {
             .map(|raw| H256::from_slice(&raw[..]).expect("db safe access"))
     }
 
+    fn get_epoch_start_block(&self, epoch_number: EpochNumber) -> Option<Header> {
+        self.get(COLUMN_EPOCH_START, &epoch_number.to_be_bytes())
+            .and_then(|raw| {
+                let hash = H256::from_slice(&raw[..]).expect("db safe access");
+                self.get_block_header(&hash)
+            })
+    }
+
     fn get_transaction(&self, h: &H256) -> Option<(Transaction, H256)> {
         self.get(COLUMN_TRANSACTION_ADDR, h.as_bytes())
             .map(|raw| deserialize(&raw[..]).expect("deserialize tx address should be ok"))
             .and_then(|addr: TransactionAddressStored| {
-                self.partial_get(
-                    COLUMN_BLOCK_BODY,
-                    addr.block_hash.as_bytes(),
+                self.get_block_body_slice(
+                    &addr.block_hash,
                     &(addr.inner.offset..(addr.inner.offset + addr.inner.length)),
                 )
                 .map(|ref serialized_transaction| {
@@ -388,6 +902,33 @@ impl<T: KeyValueDB> ChainStore for ChainKVStore<T> {
             })
     }
 
+    // rocksdb 0.12 has no multi_get, so the keys are read one by one
+    fn get_transaction_addresses_batch(&self, hashes: &[H256]) -> Vec<Option<TransactionAddress>> {
+        hashes
+            .iter()
+            .map(|hash| self.get_transaction_address(hash))
+            .collect()
+    }
+
+    fn contain_transactions_batch(&self, hashes: &[H256]) -> Vec<bool> {
+        hashes
+            .iter()
+            .map(|hash| self.get(COLUMN_TRANSACTION_ADDR, hash.as_bytes()).is_some())
+            .collect()
+    }
+
+    fn get_transaction_confirmation_status(&self, h: &H256) -> Option<TransactionConfirmation> {
+        let stored: TransactionAddressStored = self
+            .get(COLUMN_TRANSACTION_ADDR, h.as_bytes())
+            .map(|raw| deserialize(&raw[..]).expect("deserialize tx address should be ok"))?;
+        let block_number = self.get_block_number(&stored.block_hash)?;
+        Some(TransactionConfirmation {
+            block_hash: stored.block_hash,
+            block_number,
+            tx_index: stored.inner.index as u32,
+        })
+    }
+
     fn get_cell_meta(&self, tx_hash: &H256, index: u32) -> Option<CellMeta> {
         self.get(
             COLUMN_CELL_META,
@@ -396,15 +937,22 @@ impl<T: KeyValueDB> ChainStore for ChainKVStore<T> {
         .map(|raw| deserialize(&raw[..]).expect("deserialize cell meta should be ok"))
     }
 
+    // Like the transaction addresses, read one by one until rocksdb offers multi_get
+    fn get_cell_metas_batch(&self, out_points: &[CellOutPoint]) -> Vec<Option<CellMeta>> {
+        out_points
+            .iter()
+            .map(|out_point| self.get_cell_meta(&out_point.tx_hash, out_point.index))
+            .collect()
+    }
+
     fn get_cellbase(&self, h: &H256) -> Option<Transaction> {
         self.get(COLUMN_BLOCK_TRANSACTION_ADDRESSES, h.as_bytes())
             .and_then(|serialized_addresses| {
                 let addresses: Vec<TransactionAddressInner> =
                     deserialize(&serialized_addresses).expect("deserialize address should be ok");
                 let cellbase_address = addresses.get(0).expect("cellbase address should exist");
-                self.partial_get(
-                    COLUMN_BLOCK_BODY,
-                    h.as_bytes(),
+                self.get_block_body_slice(
+                    h,
                     &(cellbase_address.offset..(cellbase_address.offset + cellbase_address.length)),
                 )
                 .map(|ref serialized_transaction| {
@@ -438,9 +986,8 @@ impl<T: KeyValueDB> ChainStore for ChainKVStore<T> {
                     .get(index as usize)
                     .and_then(|addr| {
                         let output_offset = stored.inner.offset + addr.offset;
-                        self.partial_get(
-                            COLUMN_BLOCK_BODY,
-                            stored.block_hash.as_bytes(),
+                        self.get_block_body_slice(
+                            &stored.block_hash,
                             &(output_offset..(output_offset + addr.length)),
                         )
                         .map(|ref serialized_cell_output| {
@@ -458,6 +1005,39 @@ impl<T: KeyValueDB> ChainStore for ChainKVStore<T> {
             })
     }
 
+    fn get_cells_by_script(
+        &self,
+        script_hash: &H256,
+        skip: usize,
+        take: usize,
+    ) -> Vec<CellOutPoint> {
+        let mut out_points = Vec::new();
+        if take == 0 {
+            return out_points;
+        }
+        // The keys are ordered by the block number following the script hash
+        let mut skipped = 0;
+        self.db
+            .traverse_prefix(COLUMN_SCRIPT_TO_CELLS, script_hash.as_bytes(), |key, _| {
+                if skipped < skip {
+                    skipped += 1;
+                    return Ok(true);
+                }
+                out_points.push(CellOutPoint {
+                    tx_hash: H256::from_slice(&key[40..72])
+                        .expect("deserialize tx hash should be ok"),
+                    index: u32::from_be_bytes(
+                        key[72..SCRIPT_CELL_KEY_LEN]
+                            .try_into()
+                            .expect("deserialize output index should be ok"),
+                    ),
+                });
+                Ok(out_points.len() < take)
+            })
+            .expect("db operation should be ok");
+        out_points
+    }
+
     fn traverse_cell_set<F>(&self, mut callback: F) -> Result<(), Error>
     where
         F: FnMut(H256, TransactionMeta) -> Result<(), Error>,
@@ -469,10 +1049,24 @@ impl<T: KeyValueDB> ChainStore for ChainKVStore<T> {
             callback(tx_hash, tx_meta)
         })
     }
+
+    fn iter_all_blocks(&self) -> Box<dyn Iterator<Item = Block> + '_> {
+        // Walk forward through the number index instead of collecting the
+        // whole chain of parent hashes first.
+        let tip_number = match self.get_tip_header() {
+            Some(header) => header.number(),
+            None => return Box::new(::std::iter::empty()),
+        };
+        Box::new((0..=tip_number).scan((), move |_, number| {
+            self.get_block_hash(number)
+                .and_then(|hash| self.get_block(&hash))
+        }))
+    }
 }
 
 pub struct DefaultStoreBatch<B> {
     inner: B,
+    block_body_compression: Compression,
 }
 
 /// helper methods
@@ -509,8 +1103,24 @@ impl<B: DbBatch> StoreBatch for DefaultStoreBatch<B> {
             hash.as_bytes(),
             block.proposals(),
         )?;
-        let (block_data, block_addresses) = serialize_block_body(block.transactions())
-            .expect("flat serialize block body should be ok");
+        let compress = self.block_body_compression == Compression::Lz4;
+        self.insert_block_body_compressed(hash, block.transactions(), compress)
+    }
+
+    fn insert_block_body_compressed(
+        &mut self,
+        hash: &H256,
+        transactions: &[Transaction],
+        compress: bool,
+    ) -> Result<(), Error> {
+        let compression = if compress {
+            Compression::Lz4
+        } else {
+            Compression::None
+        };
+        let (block_data, block_addresses) =
+            serialize_block_body_compressed(transactions, compression)
+                .expect("flat serialize block body should be ok");
         self.insert_raw(COLUMN_BLOCK_BODY, hash.as_bytes(), &block_data)?;
         self.insert_serialize(
             COLUMN_BLOCK_TRANSACTION_ADDRESSES,
@@ -558,6 +1168,15 @@ impl<B: DbBatch> StoreBatch for DefaultStoreBatch<B> {
                     data_hash: Some(output.data_hash()),
                 };
                 self.insert_serialize(COLUMN_CELL_META, store_key.as_ref(), &cell_meta)?;
+                for script_hash in output_script_hashes(output) {
+                    let key = script_cell_key(
+                        &script_hash,
+                        block.header().number(),
+                        &tx_hash,
+                        index as u32,
+                    );
+                    self.insert_raw(COLUMN_SCRIPT_TO_CELLS, &key, &[])?;
+                }
             }
         }
 
@@ -565,27 +1184,50 @@ impl<B: DbBatch> StoreBatch for DefaultStoreBatch<B> {
         self.insert_raw(COLUMN_INDEX, &number, hash.as_bytes())?;
         for uncle in block.uncles() {
             self.insert_raw(COLUMN_UNCLES, &uncle.hash().as_bytes(), &[])?;
+            // An uncle is included by at most one block in the main chain
+            self.insert_serialize(
+                COLUMN_UNCLE_TO_BLOCK,
+                &uncle.hash().as_bytes(),
+                &vec![hash.to_owned()],
+            )?;
         }
         self.insert_raw(COLUMN_INDEX, hash.as_bytes(), &number)
     }
 
     fn detach_block(&mut self, block: &Block) -> Result<(), Error> {
+        self.delete_transaction_addresses_for_block(block)?;
         for tx in block.transactions() {
             let tx_hash = tx.hash();
-            self.delete(COLUMN_TRANSACTION_ADDR, tx_hash.as_bytes())?;
-            for index in 0..tx.outputs().len() {
+            for (index, output) in tx.outputs().iter().enumerate() {
                 let store_key = CellKey::calculate(&tx_hash, index as u32);
                 self.delete(COLUMN_CELL_META, store_key.as_ref())?;
+                for script_hash in output_script_hashes(output) {
+                    let key = script_cell_key(
+                        &script_hash,
+                        block.header().number(),
+                        &tx_hash,
+                        index as u32,
+                    );
+                    self.delete(COLUMN_SCRIPT_TO_CELLS, &key)?;
+                }
             }
         }
 
         for uncle in block.uncles() {
             self.delete(COLUMN_UNCLES, &uncle.hash().as_bytes())?;
+            self.delete(COLUMN_UNCLE_TO_BLOCK, &uncle.hash().as_bytes())?;
         }
         self.delete(COLUMN_INDEX, &block.header().number().to_le_bytes())?;
         self.delete(COLUMN_INDEX, block.header().hash().as_bytes())
     }
 
+    fn delete_transaction_addresses_for_block(&mut self, block: &Block) -> Result<(), Error> {
+        for tx in block.transactions() {
+            self.delete(COLUMN_TRANSACTION_ADDR, tx.hash().as_bytes())?;
+        }
+        Ok(())
+    }
+
     fn insert_tip_header(&mut self, h: &Header) -> Result<(), Error> {
         self.insert_raw(COLUMN_META, META_TIP_HEADER_KEY, h.hash().as_bytes())
     }
@@ -613,6 +1255,40 @@ impl<B: DbBatch> StoreBatch for DefaultStoreBatch<B> {
         self.insert_serialize(COLUMN_META, META_CURRENT_EPOCH_KEY, epoch)
     }
 
+    fn insert_epoch_start(
+        &mut self,
+        epoch_number: EpochNumber,
+        block_hash: &H256,
+    ) -> Result<(), Error> {
+        self.insert_raw(
+            COLUMN_EPOCH_START,
+            &epoch_number.to_be_bytes(),
+            block_hash.as_bytes(),
+        )
+    }
+
+    fn delete_epoch_start(&mut self, epoch_number: EpochNumber) -> Result<(), Error> {
+        self.delete(COLUMN_EPOCH_START, &epoch_number.to_be_bytes())
+    }
+
+    fn insert_total_counts(&mut self, counts: &TotalCounts) -> Result<(), Error> {
+        self.insert_raw(
+            COLUMN_META,
+            META_TOTAL_TX_COUNT_KEY,
+            &counts.transactions.to_le_bytes(),
+        )?;
+        self.insert_raw(
+            COLUMN_META,
+            META_TOTAL_CELLS_CREATED_COUNT_KEY,
+            &counts.cells_created.to_le_bytes(),
+        )?;
+        self.insert_raw(
+            COLUMN_META,
+            META_TOTAL_CELLS_CONSUMED_COUNT_KEY,
+            &counts.cells_consumed.to_le_bytes(),
+        )
+    }
+
     fn update_cell_set(&mut self, tx_hash: &H256, meta: &TransactionMeta) -> Result<(), Error> {
         self.insert_serialize(COLUMN_CELL_SET, tx_hash.as_bytes(), meta)
     }
@@ -628,12 +1304,14 @@ impl<B: DbBatch> StoreBatch for DefaultStoreBatch<B> {
 
 #[cfg(test)]
 mod tests {
-    use super::super::COLUMNS;
     use super::*;
     use crate::store::StoreBatch;
     use ckb_chain_spec::consensus::Consensus;
-    use ckb_core::transaction::TransactionBuilder;
-    use ckb_db::{DBConfig, RocksDB};
+    use ckb_core::header::HeaderBuilder;
+    use ckb_core::script::Script;
+    use ckb_core::transaction::{CellInput, OutPoint, TransactionBuilder};
+    use ckb_core::Bytes;
+    use ckb_db::DBConfig;
     use tempfile;
 
     fn setup_db(prefix: &str, columns: u32) -> RocksDB {
@@ -646,6 +1324,25 @@ mod tests {
         RocksDB::open(&config, columns)
     }
 
+    // Store the block and attach it to the main chain, recording the start of a new epoch
+    fn attach_block<T: KeyValueDB>(store: &ChainKVStore<T>, block: &Block) {
+        let mut batch = store.new_batch().unwrap();
+        batch.insert_block(block).unwrap();
+        batch
+            .insert_block_ext(block.header().hash(), &BlockExt::default())
+            .unwrap();
+        batch.attach_block(block).unwrap();
+        let is_epoch_start = store
+            .get_block_header(block.header().parent_hash())
+            .map_or(false, |parent| parent.epoch() != block.header().epoch());
+        if is_epoch_start {
+            batch
+                .insert_epoch_start(block.header().epoch(), block.header().hash())
+                .unwrap();
+        }
+        batch.commit().unwrap();
+    }
+
     #[test]
     fn save_and_get_block() {
         let db = setup_db("save_and_get_block", COLUMNS);
@@ -677,6 +1374,652 @@ mod tests {
         assert_eq!(block, store.get_block(&hash).unwrap());
     }
 
+    #[test]
+    fn save_and_get_compressed_block() {
+        let db = setup_db("save_and_get_compressed_block", COLUMNS);
+        let config = StoreConfig {
+            block_body_compression: Compression::Lz4,
+            ..Default::default()
+        };
+        let store = ChainKVStore::with_config(db, config);
+        let block = BlockBuilder::default()
+            .transaction(TransactionBuilder::default().build())
+            .transaction(
+                TransactionBuilder::default()
+                    .outputs(vec![CellOutput::default(); 10])
+                    .build(),
+            )
+            .build();
+
+        let hash = block.header().hash();
+        let mut batch = store.new_batch().unwrap();
+        batch.insert_block(&block).unwrap();
+        batch.attach_block(&block).unwrap();
+        batch.commit().unwrap();
+        assert_eq!(block, store.get_block(&hash).unwrap());
+
+        let tx = &block.transactions()[1];
+        assert_eq!(tx, &store.get_transaction(tx.hash()).unwrap().0);
+        assert_eq!(
+            tx.outputs()[9],
+            store.get_cell_output(tx.hash(), 9).unwrap()
+        );
+        assert_eq!(
+            &block.transactions()[0],
+            &store.get_cellbase(&hash).unwrap()
+        );
+    }
+
+    #[test]
+    fn insert_block_body_compressed() {
+        let db = setup_db("insert_block_body_compressed", COLUMNS);
+        let store = ChainKVStore::new(db);
+        let transactions = vec![
+            TransactionBuilder::default().build(),
+            TransactionBuilder::default()
+                .outputs(vec![CellOutput::default(); 10])
+                .build(),
+        ];
+        let raw_hash = H256::from_slice(&[1; 32]).unwrap();
+        let compressed_hash = H256::from_slice(&[2; 32]).unwrap();
+
+        let mut batch = store.new_batch().unwrap();
+        batch
+            .insert_block_body_compressed(&raw_hash, &transactions, false)
+            .unwrap();
+        batch
+            .insert_block_body_compressed(&compressed_hash, &transactions, true)
+            .unwrap();
+        batch.commit().unwrap();
+
+        let raw = store.get(COLUMN_BLOCK_BODY, raw_hash.as_bytes()).unwrap();
+        let compressed = store
+            .get(COLUMN_BLOCK_BODY, compressed_hash.as_bytes())
+            .unwrap();
+        assert!(!is_compressed(raw[0]));
+        assert!(is_compressed(compressed[0]));
+        assert_eq!(transactions, store.get_block_body(&raw_hash).unwrap());
+        assert_eq!(
+            transactions,
+            store.get_block_body(&compressed_hash).unwrap()
+        );
+    }
+
+    #[test]
+    fn iter_all_blocks() {
+        let db = setup_db("iter_all_blocks", COLUMNS);
+        let store = ChainKVStore::new(db);
+        let consensus = Consensus::default();
+        store.init(&consensus).unwrap();
+
+        let mut blocks = vec![consensus.genesis_block().to_owned()];
+        for number in 1..=50 {
+            let header = HeaderBuilder::default()
+                .parent_hash(blocks[blocks.len() - 1].header().hash().to_owned())
+                .number(number)
+                .build();
+            let block = BlockBuilder::default().header(header).build();
+            let mut batch = store.new_batch().unwrap();
+            batch.insert_block(&block).unwrap();
+            batch.attach_block(&block).unwrap();
+            batch.insert_tip_header(block.header()).unwrap();
+            batch.commit().unwrap();
+            blocks.push(block);
+        }
+
+        let iterated: Vec<Block> = store.iter_all_blocks().collect();
+        assert_eq!(iterated.len(), 51);
+        for (expected, block) in blocks.iter().zip(iterated.iter()) {
+            assert_eq!(expected.header().hash(), block.header().hash());
+        }
+        assert_eq!(blocks, iterated);
+    }
+
+    #[test]
+    fn estimate_column_sizes() {
+        let db = setup_db("estimate_column_sizes", COLUMNS);
+        let store = ChainKVStore::new(db);
+        let sizes = store.estimate_column_sizes().unwrap();
+        assert_eq!(sizes.len(), COLUMNS as usize);
+        assert!(sizes
+            .iter()
+            .all(|size| size.keys == 0 && size.total_bytes() == 0));
+
+        let consensus = Consensus::default();
+        store.init(&consensus).unwrap();
+        let hash = consensus.genesis_block().header().hash();
+        let header_size = store
+            .get(COLUMN_BLOCK_HEADER, hash.as_bytes())
+            .unwrap()
+            .len();
+
+        let sizes = store.estimate_column_sizes().unwrap();
+        let headers = &sizes[COLUMN_BLOCK_HEADER as usize];
+        assert_eq!(headers.column, COLUMN_BLOCK_HEADER);
+        assert_eq!(headers.keys, 1);
+        assert_eq!(headers.key_bytes, 32);
+        assert_eq!(headers.value_bytes, header_size as u64);
+        assert_eq!(sizes[COLUMN_META as usize].keys, 5);
+    }
+
+    #[test]
+    fn get_epoch_info() {
+        let db = setup_db("get_epoch_info", COLUMNS);
+        let store = ChainKVStore::new(db);
+        let consensus = Consensus::default();
+        store.init(&consensus).unwrap();
+        let genesis = consensus.genesis_block().header().to_owned();
+        let genesis_epoch = consensus.genesis_epoch_ext();
+        assert_eq!(
+            store.get_epoch_info(0),
+            Some(EpochInfo {
+                start_number: 0,
+                length: genesis_epoch.length(),
+                start_block_hash: genesis.hash().to_owned(),
+                difficulty: genesis_epoch.difficulty().to_owned(),
+            })
+        );
+
+        // blocks 1..=4 in epochs 0, 1, 1 and 2
+        let mut parent = genesis;
+        let mut headers = Vec::new();
+        for (number, epoch) in [0u64, 1, 1, 2].iter().enumerate() {
+            let header = HeaderBuilder::default()
+                .parent_hash(parent.hash().to_owned())
+                .number(number as u64 + 1)
+                .epoch(*epoch)
+                .build();
+            let block = BlockBuilder::default().header(header.clone()).build();
+            attach_block(&store, &block);
+            headers.push(header.clone());
+            parent = header;
+        }
+        let epochs: Vec<EpochExt> = [(1, 2, 2), (2, 4, 3)]
+            .iter()
+            .map(|(number, start_number, length)| {
+                EpochExt::new(
+                    *number,
+                    Capacity::zero(),
+                    Capacity::zero(),
+                    headers[*start_number as usize - 2].hash().to_owned(),
+                    *start_number,
+                    *length,
+                    genesis_epoch.difficulty().to_owned(),
+                )
+            })
+            .collect();
+        let mut batch = store.new_batch().unwrap();
+        for epoch in &epochs {
+            batch
+                .insert_epoch_ext(epoch.last_block_hash_in_previous_epoch(), epoch)
+                .unwrap();
+        }
+        batch.commit().unwrap();
+
+        assert_eq!(
+            store.get_epoch_info(1),
+            Some(EpochInfo {
+                start_number: 2,
+                length: 2,
+                start_block_hash: headers[1].hash().to_owned(),
+                difficulty: genesis_epoch.difficulty().to_owned(),
+            })
+        );
+        let epoch_2 = EpochInfo {
+            start_number: 4,
+            length: 3,
+            start_block_hash: headers[3].hash().to_owned(),
+            difficulty: genesis_epoch.difficulty().to_owned(),
+        };
+        assert_eq!(store.get_epoch_info(2), Some(epoch_2.clone()));
+        assert_eq!(store.get_current_epoch_info(&headers[3]), Some(epoch_2));
+        assert_eq!(store.get_epoch_info(3), None);
+    }
+
+    #[test]
+    fn get_blocks_in_epoch() {
+        let db = setup_db("get_blocks_in_epoch", COLUMNS);
+        let store = ChainKVStore::new(db);
+        let consensus = Consensus::default();
+        store.init(&consensus).unwrap();
+        let genesis = consensus.genesis_block().header().to_owned();
+        let genesis_epoch = consensus.genesis_epoch_ext();
+
+        // blocks 1..=6 in epochs 0, 1, 1, 2, 2 and 3, the epoch 3 is not finished
+        let mut parent = genesis;
+        let mut blocks = Vec::new();
+        for (number, epoch) in [0u64, 1, 1, 2, 2, 3].iter().enumerate() {
+            let header = HeaderBuilder::default()
+                .parent_hash(parent.hash().to_owned())
+                .number(number as u64 + 1)
+                .epoch(*epoch)
+                .build();
+            let block = BlockBuilder::default().header(header.clone()).build();
+            attach_block(&store, &block);
+            blocks.push(block);
+            parent = header;
+        }
+        let mut batch = store.new_batch().unwrap();
+        for (number, start_number) in [(1u64, 2u64), (2, 4), (3, 6)].iter() {
+            let epoch = EpochExt::new(
+                *number,
+                Capacity::zero(),
+                Capacity::zero(),
+                blocks[*start_number as usize - 2]
+                    .header()
+                    .hash()
+                    .to_owned(),
+                *start_number,
+                2,
+                genesis_epoch.difficulty().to_owned(),
+            );
+            batch
+                .insert_epoch_ext(epoch.last_block_hash_in_previous_epoch(), &epoch)
+                .unwrap();
+        }
+        batch.commit().unwrap();
+
+        assert_eq!(store.get_blocks_in_epoch(1), blocks[1..3].to_vec());
+        assert_eq!(store.get_blocks_in_epoch(2), blocks[3..5].to_vec());
+        assert_eq!(
+            store.get_block_hashes_in_epoch(2),
+            vec![
+                blocks[3].header().hash().to_owned(),
+                blocks[4].header().hash().to_owned(),
+            ]
+        );
+        assert_eq!(store.get_blocks_in_epoch(3), vec![blocks[5].clone()]);
+        assert!(store.get_blocks_in_epoch(4).is_empty());
+        assert!(store.get_block_hashes_in_epoch(4).is_empty());
+    }
+
+    #[test]
+    fn save_and_get_block_epoch_number() {
+        let db = setup_db("save_and_get_block_epoch_number", COLUMNS);
+        let store = ChainKVStore::new(db);
+        let consensus = Consensus::default();
+        store.init(&consensus).unwrap();
+        let genesis_hash = consensus.genesis_block().header().hash();
+        assert_eq!(store.get_block_epoch_number(genesis_hash), Some(0));
+
+        let hash = H256::from_slice(&[1; 32]).unwrap();
+        assert_eq!(store.get_block_epoch_number(&hash), None);
+        let epoch_index = H256::from_slice(&[2; 32]).unwrap();
+        let epoch = EpochExt::new(
+            42,
+            Capacity::zero(),
+            Capacity::zero(),
+            epoch_index.clone(),
+            0,
+            0,
+            consensus.genesis_epoch_ext().difficulty().clone(),
+        );
+        let mut batch = store.new_batch().unwrap();
+        batch.insert_block_epoch_index(&hash, &epoch_index).unwrap();
+        batch.insert_epoch_ext(&epoch_index, &epoch).unwrap();
+        batch.commit().unwrap();
+        assert_eq!(store.get_block_epoch_number(&hash), Some(42));
+    }
+
+    #[test]
+    fn attach_and_detach_script_to_cells() {
+        let db = setup_db("attach_and_detach_script_to_cells", COLUMNS);
+        let store = ChainKVStore::new(db);
+        let lock = Script::new(vec![], H256::from_slice(&[1; 32]).unwrap());
+        let type_ = Script::new(vec![], H256::from_slice(&[2; 32]).unwrap());
+        let tx = TransactionBuilder::default()
+            .output(CellOutput::new(
+                Capacity::zero(),
+                Bytes::default(),
+                lock.clone(),
+                Some(type_.clone()),
+            ))
+            .output(CellOutput::new(
+                Capacity::zero(),
+                Bytes::default(),
+                lock.clone(),
+                Some(lock.clone()),
+            ))
+            .build();
+        let tx_hash = tx.hash().to_owned();
+        let block = BlockBuilder::default()
+            .header(HeaderBuilder::default().number(1).build())
+            .transaction(tx)
+            .build();
+
+        let mut batch = store.new_batch().unwrap();
+        batch.attach_block(&block).unwrap();
+        batch.commit().unwrap();
+        let out_point = |index| CellOutPoint {
+            tx_hash: tx_hash.clone(),
+            index,
+        };
+        assert_eq!(
+            store.get_cells_by_script(&lock.hash(), 0, 10),
+            vec![out_point(0), out_point(1)]
+        );
+        assert_eq!(
+            store.get_cells_by_script(&type_.hash(), 0, 10),
+            vec![out_point(0)]
+        );
+        assert_eq!(
+            store.get_cells_by_script(&lock.hash(), 1, 10),
+            vec![out_point(1)]
+        );
+
+        let mut batch = store.new_batch().unwrap();
+        batch.detach_block(&block).unwrap();
+        batch.commit().unwrap();
+        assert!(store.get_cells_by_script(&lock.hash(), 0, 10).is_empty());
+        assert!(store.get_cells_by_script(&type_.hash(), 0, 10).is_empty());
+    }
+
+    #[test]
+    fn get_blocks_including_uncle() {
+        let db = setup_db("get_blocks_including_uncle", COLUMNS);
+        let store = ChainKVStore::new(db);
+        let uncles: Vec<UncleBlock> = (1..=2)
+            .map(|number| {
+                BlockBuilder::default()
+                    .header(HeaderBuilder::default().number(number).build())
+                    .build()
+                    .into()
+            })
+            .collect();
+        let block = BlockBuilder::default()
+            .header(HeaderBuilder::default().number(3).build())
+            .uncles(uncles.clone())
+            .build();
+        let hash = block.header().hash();
+
+        let mut batch = store.new_batch().unwrap();
+        batch.insert_block(&block).unwrap();
+        batch.attach_block(&block).unwrap();
+        batch.commit().unwrap();
+        for uncle in &uncles {
+            assert_eq!(
+                store.get_blocks_including_uncle(uncle.hash()),
+                vec![hash.to_owned()]
+            );
+        }
+
+        // the block is rolled back by a chain reorganization
+        let mut batch = store.new_batch().unwrap();
+        batch.detach_block(&block).unwrap();
+        batch.commit().unwrap();
+        for uncle in &uncles {
+            assert!(store.get_blocks_including_uncle(uncle.hash()).is_empty());
+        }
+    }
+
+    #[test]
+    fn delete_transaction_addresses_for_block() {
+        let db = setup_db("delete_transaction_addresses_for_block", COLUMNS);
+        let store = ChainKVStore::new(db);
+        let block = BlockBuilder::default()
+            .transaction(TransactionBuilder::default().build())
+            .transaction(
+                TransactionBuilder::default()
+                    .input(CellInput::new(OutPoint::null(), 1))
+                    .build(),
+            )
+            .build();
+
+        let mut batch = store.new_batch().unwrap();
+        batch.insert_block(&block).unwrap();
+        batch.attach_block(&block).unwrap();
+        batch.commit().unwrap();
+        for tx in block.transactions() {
+            assert!(store.get_transaction_address(tx.hash()).is_some());
+        }
+
+        let mut batch = store.new_batch().unwrap();
+        batch
+            .delete_transaction_addresses_for_block(&block)
+            .unwrap();
+        batch.commit().unwrap();
+        for tx in block.transactions() {
+            assert!(store.get_transaction_address(tx.hash()).is_none());
+            assert!(store.get_transaction(tx.hash()).is_none());
+        }
+    }
+
+    #[test]
+    fn vacuum_spent_transactions() {
+        let db = setup_db("vacuum_spent_transactions", COLUMNS);
+        let store = ChainKVStore::new(db);
+        let consensus = Consensus::default();
+        store.init(&consensus).unwrap();
+
+        // the cellbase and the last transaction have live outputs, the middle one is spent
+        let transactions: Vec<_> = (0..3u8)
+            .map(|index| {
+                let builder = if index == 0 {
+                    TransactionBuilder::default().input(CellInput::new_cellbase_input(1))
+                } else {
+                    TransactionBuilder::default()
+                };
+                builder
+                    .output(CellOutput::new(
+                        Capacity::shannons(u64::from(index) + 1),
+                        Bytes::default(),
+                        Script::default(),
+                        None,
+                    ))
+                    .build()
+            })
+            .collect();
+        let block = BlockBuilder::default()
+            .header_builder(
+                HeaderBuilder::default()
+                    .parent_hash(consensus.genesis_hash().to_owned())
+                    .number(1),
+            )
+            .transactions(transactions.clone())
+            .build();
+        attach_block(&store, &block);
+        let mut batch = store.new_batch().unwrap();
+        batch.insert_tip_header(block.header()).unwrap();
+        batch
+            .update_cell_set(
+                transactions[0].hash(),
+                &TransactionMeta::new_cellbase(1, 0, 1, false),
+            )
+            .unwrap();
+        batch
+            .update_cell_set(
+                transactions[2].hash(),
+                &TransactionMeta::new(1, 0, 1, false),
+            )
+            .unwrap();
+        batch.commit().unwrap();
+
+        // block 1 is the tip, it may still be reverted
+        assert!(store.vacuum_spent_transactions(2, 1).is_err());
+        assert_eq!(store.vacuum_spent_transactions(1, 1), Ok(0));
+        assert_eq!(store.vacuum_spent_transactions(2, 0), Ok(1));
+        assert!(store.get_transaction(transactions[1].hash()).is_none());
+        assert!(store.get_cell_meta(transactions[1].hash(), 0).is_none());
+        for tx in &[&transactions[0], &transactions[2]] {
+            assert!(store.get_transaction(tx.hash()).is_some());
+            assert!(store.get_cell_meta(tx.hash(), 0).is_some());
+        }
+        // the block body is kept while some transactions are not vacuumed
+        assert_eq!(store.get_block(block.header().hash()), Some(block.clone()));
+        assert_eq!(store.vacuum_spent_transactions(2, 0), Ok(0));
+
+        let mut batch = store.new_batch().unwrap();
+        batch.delete_cell_set(transactions[0].hash()).unwrap();
+        batch.delete_cell_set(transactions[2].hash()).unwrap();
+        batch.commit().unwrap();
+        assert_eq!(store.vacuum_spent_transactions(2, 0), Ok(2));
+        for tx in &transactions {
+            assert!(store.get_transaction(tx.hash()).is_none());
+        }
+        assert!(store.get_block(block.header().hash()).is_none());
+        assert!(store.get_block_header(block.header().hash()).is_some());
+    }
+
+    #[test]
+    fn backup_to() {
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("backup_to")
+            .tempdir()
+            .unwrap();
+        let config = DBConfig {
+            path: tmp_dir.path().join("db"),
+            ..Default::default()
+        };
+        let store = ChainKVStore::new(RocksDB::open(&config, COLUMNS));
+        let consensus = Consensus::default();
+        store.init(&consensus).unwrap();
+
+        let backup_path = tmp_dir.path().join("backup");
+        store.backup_to(&backup_path).unwrap();
+        // The checkpoint does not follow later writes
+        let block = BlockBuilder::from_header_builder(
+            HeaderBuilder::default()
+                .parent_hash(consensus.genesis_hash().to_owned())
+                .number(1),
+        )
+        .build();
+        attach_block(&store, &block);
+
+        let backup_config = DBConfig {
+            path: backup_path,
+            ..Default::default()
+        };
+        let backup = ChainKVStore::new(RocksDB::open(&backup_config, COLUMNS));
+        let genesis_hash = consensus.genesis_hash();
+        assert_eq!(
+            backup.get_block(genesis_hash).as_ref(),
+            Some(consensus.genesis_block())
+        );
+        assert_eq!(backup.get_block_hash(0).as_ref(), Some(genesis_hash));
+        assert_eq!(
+            backup.get_current_epoch_ext().as_ref(),
+            Some(consensus.genesis_epoch_ext())
+        );
+        assert!(backup.get_block(block.header().hash()).is_none());
+        assert_eq!(
+            backup.get_tip_header(),
+            store.get_block_header(genesis_hash)
+        );
+    }
+
+    #[test]
+    fn get_cell_metas_batch() {
+        let db = setup_db("get_cell_metas_batch", COLUMNS);
+        let store = ChainKVStore::new(db);
+        let tx = TransactionBuilder::default()
+            .outputs(vec![CellOutput::default(); 2])
+            .build();
+        let block = BlockBuilder::default().transaction(tx.clone()).build();
+        let mut batch = store.new_batch().unwrap();
+        batch.insert_block(&block).unwrap();
+        batch.attach_block(&block).unwrap();
+        batch.commit().unwrap();
+
+        let out_points = vec![
+            CellOutPoint {
+                tx_hash: tx.hash().to_owned(),
+                index: 1,
+            },
+            CellOutPoint {
+                tx_hash: H256::zero(),
+                index: 0,
+            },
+            CellOutPoint {
+                tx_hash: tx.hash().to_owned(),
+                index: 0,
+            },
+        ];
+        let metas = store.get_cell_metas_batch(&out_points);
+        assert_eq!(
+            metas,
+            out_points
+                .iter()
+                .map(|out_point| store.get_cell_meta(&out_point.tx_hash, out_point.index))
+                .collect::<Vec<_>>()
+        );
+        assert!(metas[0].is_some());
+        assert!(metas[1].is_none());
+    }
+
+    #[test]
+    fn get_transaction_addresses_batch() {
+        let db = setup_db("get_transaction_addresses_batch", COLUMNS);
+        let store = ChainKVStore::new(db);
+        let block = BlockBuilder::default()
+            .transaction(TransactionBuilder::default().build())
+            .transaction(
+                TransactionBuilder::default()
+                    .input(CellInput::new(OutPoint::null(), 1))
+                    .build(),
+            )
+            .build();
+
+        let mut batch = store.new_batch().unwrap();
+        batch.insert_block(&block).unwrap();
+        batch.attach_block(&block).unwrap();
+        batch.commit().unwrap();
+
+        let hashes = vec![
+            block.transactions()[1].hash().to_owned(),
+            H256::zero(),
+            block.transactions()[0].hash().to_owned(),
+        ];
+        let addresses = store.get_transaction_addresses_batch(&hashes);
+        assert_eq!(
+            addresses,
+            hashes
+                .iter()
+                .map(|hash| store.get_transaction_address(hash))
+                .collect::<Vec<_>>()
+        );
+        assert!(addresses[0].is_some());
+        assert_eq!(
+            store.contain_transactions_batch(&hashes),
+            vec![true, false, true]
+        );
+        assert!(store.contain_transactions_batch(&[]).is_empty());
+    }
+
+    #[test]
+    fn get_transaction_confirmation_status() {
+        let db = setup_db("get_transaction_confirmation_status", COLUMNS);
+        let store = ChainKVStore::new(db);
+        let block = BlockBuilder::default()
+            .transaction(TransactionBuilder::default().build())
+            .transaction(
+                TransactionBuilder::default()
+                    .input(CellInput::new(OutPoint::null(), 1))
+                    .build(),
+            )
+            .header_builder(HeaderBuilder::default().number(1))
+            .build();
+
+        let mut batch = store.new_batch().unwrap();
+        batch.insert_block(&block).unwrap();
+        batch.attach_block(&block).unwrap();
+        batch.commit().unwrap();
+
+        for (index, tx) in block.transactions().iter().enumerate() {
+            assert_eq!(
+                store.get_transaction_confirmation_status(tx.hash()),
+                Some(TransactionConfirmation {
+                    block_hash: block.header().hash().to_owned(),
+                    block_number: 1,
+                    tx_index: index as u32,
+                })
+            );
+        }
+        assert_eq!(
+            store.get_transaction_confirmation_status(&H256::zero()),
+            None
+        );
+    }
+
     #[test]
     fn save_and_get_block_ext() {
         let db = setup_db("save_and_get_block_ext", COLUMNS);
@@ -694,6 +2037,8 @@ mod tests {
                 accumulated_rate: DEFAULT_ACCUMULATED_RATE,
                 accumulated_capacity: block.outputs_capacity().unwrap().as_u64(),
             },
+            size: 0,
+            reward_components: None,
         };
 
         let hash = block.header().hash();
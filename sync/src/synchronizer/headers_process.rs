@@ -236,12 +236,11 @@ where
         }
 
         if log_enabled!(Level::Debug) {
-            let chain_state = self.synchronizer.shared.lock_chain_state();
+            let chain_info = self.synchronizer.shared.get_chain_info();
             let peer_best_known = self.synchronizer.peers().get_best_known_header(self.peer);
             debug!(
                 "chain: num={}, diff={:#x};",
-                chain_state.tip_number(),
-                chain_state.total_difficulty()
+                chain_info.tip_number, chain_info.total_difficulty
             );
             debug!(
                 "shared best_known_header: num={}, diff={:#x}, hash={:#x};",
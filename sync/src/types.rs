@@ -1,8 +1,8 @@
 use crate::relayer::compact_block::CompactBlock;
 use crate::NetworkProtocol;
 use crate::BLOCK_DOWNLOAD_TIMEOUT;
+use crate::MAX_HEADERS_LEN;
 use crate::MAX_PEERS_PER_BLOCK;
-use crate::{MAX_HEADERS_LEN, MAX_TIP_AGE};
 use bitflags::bitflags;
 use ckb_chain_spec::consensus::Consensus;
 use ckb_core::block::Block;
@@ -14,7 +14,7 @@ use ckb_logger::{debug, debug_target};
 use ckb_network::{CKBProtocolContext, PeerIndex};
 use ckb_protocol::SyncMessage;
 use ckb_shared::chain_state::ChainState;
-use ckb_shared::shared::Shared;
+use ckb_shared::shared::{ChainInfo, Shared};
 use ckb_store::ChainStore;
 use ckb_traits::ChainProvider;
 use ckb_util::{Mutex, MutexGuard};
@@ -729,13 +729,14 @@ impl<CS: ChainStore> SyncSharedState<CS> {
     pub fn tip_header(&self) -> Header {
         self.shared.lock_chain_state().tip_header().to_owned()
     }
+    pub fn get_chain_info(&self) -> ChainInfo {
+        self.shared.get_chain_info()
+    }
     pub fn consensus(&self) -> &Consensus {
         self.shared.consensus()
     }
     pub fn is_initial_block_download(&self) -> bool {
-        unix_time_as_millis()
-            .saturating_sub(self.shared.lock_chain_state().tip_header().timestamp())
-            > MAX_TIP_AGE
+        self.shared.get_chain_info().is_initial_block_download
     }
 
     pub fn shared_best_header(&self) -> HeaderView {
@@ -57,6 +57,9 @@ fn main() {
     specs.insert("tx_pool_size_limit", Box::new(SizeLimit));
     specs.insert("tx_pool_cycles_limit", Box::new(CyclesLimit));
     specs.insert("alert_propagation", Box::new(AlertPropagation::default()));
+    specs.insert("run_dry_run", Box::new(RunDryRun));
+    #[cfg(unix)]
+    specs.insert("reload_logger_filter", Box::new(ReloadLoggerFilter));
 
     if let Some(spec_name) = env::args().nth(3) {
         if let Some(spec) = specs.get(spec_name.as_str()) {
@@ -16,7 +16,7 @@ use std::convert::TryInto;
 use std::fs;
 use std::io::Error;
 use std::path::Path;
-use std::process::{self, Child, Command, Stdio};
+use std::process::{self, Child, Command, ExitStatus, Stdio};
 
 pub struct Node {
     binary: String,
@@ -110,6 +110,68 @@ impl Node {
         }
     }
 
+    /// Run `ckb run --dry-run`, return the exit status if the process exits within `secs`.
+    pub fn dry_run(
+        &mut self,
+        modify_chain_spec: Box<dyn Fn(&mut ChainSpec) -> ()>,
+        modify_ckb_config: Box<dyn Fn(&mut CKBAppConfig) -> ()>,
+        secs: u64,
+    ) -> Option<ExitStatus> {
+        self.init_config_file(modify_chain_spec, modify_ckb_config)
+            .expect("failed to init config file");
+
+        let mut child_process = Command::new(self.binary.to_owned())
+            .env("RUST_BACKTRACE", "full")
+            .args(&["-C", &self.dir, "run", "--dry-run"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .expect("failed to run binary");
+
+        let mut status = None;
+        wait_until(secs, || {
+            status = child_process.try_wait().expect("failed to wait node");
+            status.is_some()
+        });
+        if status.is_none() {
+            let _ = child_process.kill();
+        }
+        status
+    }
+
+    pub fn rpc_port(&self) -> u16 {
+        self.rpc_port
+    }
+
+    pub fn dir(&self) -> &str {
+        &self.dir
+    }
+
+    /// Rewrite `ckb.toml` of a running node, the node only picks it up on reload or restart.
+    pub fn modify_ckb_config(
+        &self,
+        modify_ckb_config: Box<dyn Fn(&mut CKBAppConfig) -> ()>,
+    ) -> Result<(), Error> {
+        self.rewrite_spec(modify_ckb_config)
+    }
+
+    /// Send a signal to the node process via `kill`, e.g., `send_signal("HUP")`.
+    pub fn send_signal(&self, signal: &str) {
+        let pid = self
+            .guard
+            .as_ref()
+            .expect("node is not started")
+            .0
+            .id()
+            .to_string();
+        let status = Command::new("kill")
+            .args(&["-s", signal, &pid])
+            .status()
+            .expect("failed to run kill");
+        assert!(status.success(), "failed to send SIG{} to node", signal);
+    }
+
     pub fn connect(&self, node: &Node) {
         let node_info = node.rpc_client().local_node_info();
 
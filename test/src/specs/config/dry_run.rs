@@ -0,0 +1,44 @@
+use crate::{Net, Spec};
+use log::info;
+use std::net::TcpListener;
+
+pub struct RunDryRun;
+
+impl Spec for RunDryRun {
+    fn run(&self, mut net: Net) {
+        info!("Running RunDryRun");
+        let node = &mut net.nodes[0];
+
+        // The ports are taken during the dry run, which must not check them
+        let listeners: Vec<_> = [node.p2p_port(), node.rpc_port()]
+            .iter()
+            .map(|port| TcpListener::bind(("127.0.0.1", *port)).expect("bind the node port"))
+            .collect();
+        let status = node.dry_run(self.modify_chain_spec(), self.modify_ckb_config(), 60);
+        let status = status.expect("dry run should exit by itself");
+        assert!(status.success(), "dry run should exit with 0: {}", status);
+        drop(listeners);
+
+        // Nothing is left behind listening on the node ports
+        for port in &[node.p2p_port(), node.rpc_port()] {
+            assert!(
+                TcpListener::bind(("127.0.0.1", *port)).is_ok(),
+                "port {} should be free",
+                port
+            );
+        }
+    }
+
+    fn num_nodes(&self) -> usize {
+        1
+    }
+
+    fn connect_all(&self) -> bool {
+        false
+    }
+
+    // The node must not be started before the dry run
+    fn setup_net(&self, binary: &str, start_port: u16) -> Net {
+        Net::new(binary, self.num_nodes(), start_port, self.test_protocols())
+    }
+}
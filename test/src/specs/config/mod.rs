@@ -0,0 +1,5 @@
+mod dry_run;
+mod reload_logger;
+
+pub use dry_run::RunDryRun;
+pub use reload_logger::ReloadLoggerFilter;
@@ -0,0 +1,44 @@
+use crate::utils::wait_until;
+use crate::{Net, Spec};
+use ckb_app_config::CKBAppConfig;
+use log::info;
+use std::fs;
+use std::path::Path;
+
+pub struct ReloadLoggerFilter;
+
+impl Spec for ReloadLoggerFilter {
+    fn run(&self, net: Net) {
+        info!("Running ReloadLoggerFilter");
+        let node = &net.nodes[0];
+        let log_file = Path::new(node.dir()).join("data/logs/run.log");
+        let reloaded = "reloaded logger filter: Some(\"info\")";
+
+        node.modify_ckb_config(Box::new(|config| {
+            config.logger.filter = Some("info".to_owned());
+        }))
+        .expect("rewrite ckb.toml");
+        node.send_signal("HUP");
+
+        // The message is logged at info level, so it only shows up when the new filter is applied
+        let found = wait_until(10, || {
+            fs::read_to_string(&log_file)
+                .map(|content| content.contains(reloaded))
+                .unwrap_or(false)
+        });
+        assert!(found, "logger filter should be reloaded on SIGHUP");
+    }
+
+    fn num_nodes(&self) -> usize {
+        1
+    }
+
+    fn modify_ckb_config(&self) -> Box<dyn Fn(&mut CKBAppConfig) -> ()> {
+        Box::new(|config| {
+            config.network.connect_outbound_interval_secs = 1;
+            config.network.discovery_local_address = true;
+            config.logger.filter = Some("warn".to_owned());
+            config.logger.log_to_file = true;
+        })
+    }
+}
@@ -1,4 +1,5 @@
 mod alert;
+mod config;
 mod mining;
 mod p2p;
 mod relay;
@@ -6,6 +7,7 @@ mod sync;
 mod tx_pool;
 
 pub use alert::*;
+pub use config::*;
 pub use mining::*;
 pub use p2p::*;
 pub use relay::*;
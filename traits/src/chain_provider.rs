@@ -1,14 +1,62 @@
 use ckb_chain_spec::consensus::Consensus;
-use ckb_core::extras::EpochExt;
+use ckb_core::block::Block;
+use ckb_core::cell::CellWithStatus;
+pub use ckb_core::extras::BlockRewardComponents;
+use ckb_core::extras::{BlockExt, EpochExt};
 use ckb_core::header::{BlockNumber, Header};
 use ckb_core::script::Script;
-use ckb_core::Capacity;
+use ckb_core::transaction::{OutPoint, Transaction};
+use ckb_core::{Capacity, EpochNumber};
+use ckb_merkle_tree::{build_merkle_path, merkle_root_from_path};
 use ckb_script::ScriptConfig;
 use ckb_store::ChainStore;
+use dao::calculate_g2;
 use failure::Error as FailureError;
 use numext_fixed_hash::H256;
+use numext_fixed_uint::U256;
+use occupied_capacity::Ratio;
+use std::cmp;
 use std::sync::Arc;
 
+/// A block with the figures most callers look up together with it
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockWithMetadata {
+    pub block: Block,
+    pub total_difficulty: U256,
+    pub uncle_count: usize,
+    pub tx_count: usize,
+    /// Sum of the fees of all transactions in the block
+    pub fee: Capacity,
+}
+
+/// A proof that a transaction is committed in a block, checked against the
+/// `transactions_root` of the block header by `verify_transaction_proof`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransactionProof {
+    pub block_hash: H256,
+    pub tx_index: u32,
+    /// The number of transactions in the block, which fixes the shape of the merkle tree
+    pub transactions_count: u32,
+    /// The siblings on the path from the transaction hash up to the root
+    pub merkle_siblings: Vec<H256>,
+}
+
+/// Check that the proof leads from `tx_hash` to the `transactions_root` of its block
+pub fn verify_transaction_proof(
+    proof: &TransactionProof,
+    tx_hash: &H256,
+    block_transactions_root: &H256,
+) -> bool {
+    merkle_root_from_path(
+        tx_hash,
+        proof.tx_index as usize,
+        proof.transactions_count as usize,
+        &proof.merkle_siblings,
+    )
+    .as_ref()
+        == Some(block_transactions_root)
+}
+
 pub trait ChainProvider: Sync + Send {
     type Store: ChainStore;
 
@@ -26,5 +74,353 @@ pub trait ChainProvider: Sync + Send {
 
     fn finalize_block_reward(&self, parent: &Header) -> Result<(Script, Capacity), FailureError>;
 
+    /// Return the parts of the reward paid by the cellbase of the block, `None` for the
+    /// blocks which finalize no target
+    fn get_block_reward_components(&self, hash: &H256) -> Option<BlockRewardComponents>;
+
     fn consensus(&self) -> &Consensus;
+
+    /// Return whether the cell is live, dead or unknown, with the output of a live cell
+    fn get_live_cell_with_status(&self, out_point: &OutPoint) -> CellWithStatus;
+
+    /// Return the tip of the main chain read by the default methods
+    fn get_tip_header(&self) -> Option<Header> {
+        self.store().get_tip_header()
+    }
+
+    /// Return the epoch the block following the tip belongs to
+    fn get_current_epoch_ext(&self) -> Option<EpochExt> {
+        self.store().get_current_epoch_ext()
+    }
+
+    /// Return the hash of the main chain block at `number`. The default methods look the
+    /// main chain up only through this method and the two above, so a provider overriding
+    /// them gets the defaults bounded by its own tip.
+    fn get_block_hash(&self, number: BlockNumber) -> Option<H256> {
+        self.store().get_block_hash(number)
+    }
+
+    /// Return the closest and the farthest distance between the proposal of a transaction and
+    /// its commitment
+    fn get_proposal_window(&self) -> (BlockNumber, BlockNumber) {
+        let proposal_window = self.consensus().tx_proposal_window();
+        (proposal_window.closest(), proposal_window.farthest())
+    }
+
+    /// Whether a transaction proposed in `proposal_block` can be committed in `commit_block`
+    fn is_in_proposal_window(
+        &self,
+        proposal_block: BlockNumber,
+        commit_block: BlockNumber,
+    ) -> bool {
+        let (closest, farthest) = self.get_proposal_window();
+        commit_block
+            .checked_sub(proposal_block)
+            .map_or(false, |distance| {
+                closest <= distance && distance <= farthest
+            })
+    }
+
+    /// Return the number of the epoch the block belongs to
+    fn get_block_epoch_number(&self, hash: &H256) -> Option<EpochNumber> {
+        self.store().get_block_epoch_number(hash)
+    }
+
+    /// Return the block size limit in effect at the given block number
+    fn get_max_block_size_bytes(&self, block_number: BlockNumber) -> u64 {
+        block_epoch_at(self, block_number)
+            .map(|epoch| epoch.max_block_bytes())
+            .unwrap_or_else(|| self.consensus().max_block_bytes())
+    }
+
+    /// Return the secondary issuance of the block paid for the occupied capacity,
+    /// `base_issuance * occupied_capacity / total_supply`, where the base issuance is the
+    /// one the DAO accumulates for the block, see `dao::calculate_g2`.
+    fn calculate_secondary_issuance(
+        &self,
+        block_number: BlockNumber,
+        occupied_capacity: Capacity,
+        total_supply: Capacity,
+    ) -> Capacity {
+        if total_supply == Capacity::zero() {
+            return Capacity::zero();
+        }
+
+        let epoch = block_epoch_at(self, block_number)
+            .unwrap_or_else(|| self.consensus().genesis_epoch_ext().to_owned());
+
+        let epoch_reward = self.consensus().secondary_epoch_reward_at(epoch.number());
+        let base_issuance = match calculate_g2(block_number, &epoch, epoch_reward) {
+            Ok(base_issuance) => base_issuance,
+            Err(_) => return Capacity::zero(),
+        };
+
+        let occupied_capacity = cmp::min(occupied_capacity, total_supply);
+        mul_ratio(
+            base_issuance,
+            Ratio(occupied_capacity.as_u64(), total_supply.as_u64()),
+        )
+    }
+
+    /// Return the work done after `start_hash` up to and including `end_hash`
+    fn compute_chain_work_between(&self, start_hash: &H256, end_hash: &H256) -> Option<U256> {
+        let start = self.store().get_block_ext(start_hash)?;
+        let end = self.store().get_block_ext(end_hash)?;
+        // Less accumulated work at the end would underflow the subtraction
+        if end.total_difficulty < start.total_difficulty {
+            return None;
+        }
+        Some(&end.total_difficulty - &start.total_difficulty)
+    }
+
+    /// Return the headers of the main chain blocks `from..=to`, truncated at the tip, or an
+    /// empty vec if `from > to`
+    fn block_headers_range(&self, from: BlockNumber, to: BlockNumber) -> Vec<Header> {
+        let tip_number = match self.get_tip_header() {
+            Some(tip) => tip.number(),
+            None => return Vec::new(),
+        };
+        (from..=cmp::min(to, tip_number))
+            .scan((), |_, number| {
+                self.get_block_hash(number)
+                    .and_then(|hash| self.store().get_block_header(&hash))
+            })
+            .collect()
+    }
+
+    /// Return the proof that the transaction is committed in its main chain block, which
+    /// light clients check with `verify_transaction_proof`
+    fn get_transaction_proof(&self, tx_hash: &H256) -> Option<TransactionProof> {
+        let block_hash = self.store().get_transaction_address(tx_hash)?.block_hash;
+        let number = self.store().get_block_header(&block_hash)?.number();
+        if self.get_block_hash(number)? != block_hash {
+            return None;
+        }
+        let hashes = self.store().get_block_txs_hashes(&block_hash)?;
+        let tx_index = hashes.iter().position(|hash| hash == tx_hash)?;
+        let merkle_siblings = build_merkle_path(&hashes, tx_index)?;
+        Some(TransactionProof {
+            block_hash,
+            tx_index: tx_index as u32,
+            transactions_count: hashes.len() as u32,
+            merkle_siblings,
+        })
+    }
+
+    /// Return the median fee of the transactions committed in the last `window_size` main
+    /// chain blocks, cellbases excluded
+    fn get_median_transaction_fee(&self, window_size: u64) -> Option<Capacity> {
+        let mut fees: Vec<Capacity> = recent_txs_fees(self, window_size)
+            .into_iter()
+            .map(|(fee, _)| fee)
+            .collect();
+        if fees.is_empty() {
+            return None;
+        }
+        fees.sort_unstable();
+        Some(fees[fees.len() / 2])
+    }
+
+    /// Return the fee rate, in shannons per 1000 bytes, at the `percentile` of the
+    /// transactions committed in the last `window_size` main chain blocks
+    fn get_fee_rate_percentile(&self, percentile: u8, window_size: u64) -> Option<u64> {
+        if percentile > 100 {
+            return None;
+        }
+        let mut rates: Vec<u64> = recent_txs_fees(self, window_size)
+            .into_iter()
+            .map(|(fee, size)| fee.as_u64().saturating_mul(1000) / cmp::max(size, 1) as u64)
+            .collect();
+        if rates.is_empty() {
+            return None;
+        }
+        rates.sort_unstable();
+        Some(rates[(rates.len() - 1) * usize::from(percentile) / 100])
+    }
+
+    /// Return the number of uncles per 1000 blocks in the `window` blocks up to and including
+    /// `tip`. The window is clamped to the blocks after the genesis.
+    fn get_uncle_rate(&self, tip: &Header, window: u64) -> u64 {
+        let window = cmp::min(window, tip.number());
+        if window == 0 {
+            return 0;
+        }
+        let start = match self.get_ancestor(tip.hash(), tip.number() - window) {
+            Some(start) => start,
+            None => return 0,
+        };
+        match (
+            self.store().get_block_ext(start.hash()),
+            self.store().get_block_ext(tip.hash()),
+        ) {
+            (Some(start_ext), Some(tip_ext)) => {
+                tip_ext
+                    .total_uncles_count
+                    .saturating_sub(start_ext.total_uncles_count)
+                    .saturating_mul(1000)
+                    / window
+            }
+            _ => 0,
+        }
+    }
+
+    /// Call `callback` with each main chain block and its ext from `from` to `to`, inclusive
+    fn replay_chain<F>(&self, from: BlockNumber, to: BlockNumber, mut callback: F)
+    where
+        F: FnMut(&Block, &BlockExt),
+    {
+        for number in from..=to {
+            let hash = match self.get_block_hash(number) {
+                Some(hash) => hash,
+                None => break,
+            };
+            if let (Some(block), Some(ext)) = (
+                self.store().get_block(&hash),
+                self.store().get_block_ext(&hash),
+            ) {
+                callback(&block, &ext);
+            }
+        }
+    }
+
+    /// Return the block together with its total difficulty, uncle count, tx count and fee
+    fn get_block_with_metadata(&self, hash: &H256) -> Option<BlockWithMetadata> {
+        let block = self.store().get_block(hash)?;
+        let ext = self.store().get_block_ext(hash)?;
+        let fee = ext
+            .txs_fees
+            .iter()
+            .try_fold(Capacity::zero(), |acc, fee| acc.safe_add(*fee))
+            .ok()?;
+
+        Some(BlockWithMetadata {
+            uncle_count: block.uncles().len(),
+            tx_count: block.transactions().len(),
+            total_difficulty: ext.total_difficulty,
+            fee,
+            block,
+        })
+    }
+
+    /// Return the serialized size of the block, as checked against the block size limit
+    fn get_block_size(&self, hash: &H256) -> Option<usize> {
+        self.store()
+            .get_block_ext(hash)
+            .map(|ext| ext.size as usize)
+    }
+
+    /// Return the median serialized size of the block and its ancestors, `window` blocks
+    /// at most. Fewer blocks are taken near the genesis.
+    fn get_block_median_size(&self, hash: &H256, window: usize) -> Option<u64> {
+        let mut sizes = Vec::with_capacity(window);
+        let mut block_hash = hash.to_owned();
+        while sizes.len() < window {
+            let header = match self.store().get_block_header(&block_hash) {
+                Some(header) => header,
+                None => break,
+            };
+            let ext = match self.store().get_block_ext(&block_hash) {
+                Some(ext) => ext,
+                None => break,
+            };
+            sizes.push(u64::from(ext.size));
+            if header.number() == 0 {
+                break;
+            }
+            block_hash = header.parent_hash().to_owned();
+        }
+        if sizes.is_empty() {
+            return None;
+        }
+        sizes.sort_unstable();
+        Some(sizes[sizes.len() / 2])
+    }
+
+    /// Return the transactions of the block with their indexes in it, the cellbase is at 0
+    fn get_transactions_by_block(&self, hash: &H256) -> Option<Vec<(u32, Transaction)>> {
+        self.store().get_block_body(hash).map(|transactions| {
+            transactions
+                .into_iter()
+                .enumerate()
+                .map(|(index, tx)| (index as u32, tx))
+                .collect()
+        })
+    }
+
+    /// Return the DAO accumulated rate of the main chain block, multiplied by 10**16
+    fn get_accumulated_rate(&self, block_number: BlockNumber) -> Option<u64> {
+        self.get_block_hash(block_number)
+            .and_then(|hash| self.store().get_block_ext(&hash))
+            .map(|ext| ext.dao_stats.accumulated_rate)
+    }
+
+    /// Return the index of a committed transaction in its block, read from its address
+    fn get_transaction_index_in_block(&self, tx_hash: &H256) -> Option<u32> {
+        self.store()
+            .get_transaction_confirmation_status(tx_hash)
+            .map(|confirmation| confirmation.tx_index)
+    }
+}
+
+// The epoch of the main chain block, the blocks not stored yet fall into the current epoch
+fn block_epoch_at<P: ChainProvider + ?Sized>(
+    provider: &P,
+    block_number: BlockNumber,
+) -> Option<EpochExt> {
+    provider
+        .get_block_hash(block_number)
+        .and_then(|hash| provider.get_block_epoch(&hash))
+        .or_else(|| {
+            provider
+                .get_current_epoch_ext()
+                .filter(|epoch| block_number >= epoch.start_number())
+        })
+}
+
+// The fee and the serialized size of each transaction committed in the last `window_size`
+// main chain blocks. The fees are recorded in the block ext once the block is verified.
+fn recent_txs_fees<P: ChainProvider + ?Sized>(
+    provider: &P,
+    window_size: u64,
+) -> Vec<(Capacity, usize)> {
+    let tip_number = match provider.get_tip_header() {
+        Some(tip) => tip.number(),
+        None => return Vec::new(),
+    };
+    let start = (tip_number + 1).saturating_sub(window_size);
+    let mut fees = Vec::new();
+    for number in start..=tip_number {
+        let hash = match provider.get_block_hash(number) {
+            Some(hash) => hash,
+            None => continue,
+        };
+        if let (Some(block), Some(ext)) = (
+            provider.store().get_block(&hash),
+            provider.store().get_block_ext(&hash),
+        ) {
+            fees.extend(
+                block
+                    .transactions()
+                    .iter()
+                    .skip(1)
+                    .zip(ext.txs_fees.iter())
+                    .map(|(tx, fee)| (*fee, tx.serialized_size())),
+            );
+        }
+    }
+    fees
+}
+
+// `safe_mul_ratio` fails once `capacity * numer` exceeds u64, which is common for ratios
+// of total capacities, so fall back to u128 and saturate the result.
+fn mul_ratio(capacity: Capacity, ratio: Ratio) -> Capacity {
+    capacity.safe_mul_ratio(ratio).unwrap_or_else(|_| {
+        let shannons = u128::from(capacity.as_u64()) * u128::from(ratio.numer())
+            / u128::from(cmp::max(ratio.denom(), 1));
+        if shannons > u128::from(u64::max_value()) {
+            Capacity::shannons(u64::max_value())
+        } else {
+            Capacity::shannons(shannons as u64)
+        }
+    })
 }
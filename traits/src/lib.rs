@@ -2,4 +2,7 @@ pub mod block_median_time_context;
 pub mod chain_provider;
 
 pub use crate::block_median_time_context::BlockMedianTimeContext;
-pub use crate::chain_provider::ChainProvider;
+pub use crate::chain_provider::{
+    verify_transaction_proof, BlockRewardComponents, BlockWithMetadata, ChainProvider,
+    TransactionProof,
+};
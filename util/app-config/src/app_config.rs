@@ -4,7 +4,11 @@
 //! we must put nested config struct in the tail to make it serializable,
 //! details https://docs.rs/toml/0.5.0/toml/ser/index.html
 
+use std::collections::BTreeSet;
+use std::env;
+use std::fmt;
 use std::fs;
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 
 use serde_derive::{Deserialize, Serialize};
@@ -14,6 +18,7 @@ use ckb_db::DBConfig;
 use ckb_logger::Config as LogConfig;
 use ckb_miner::BlockAssemblerConfig;
 use ckb_miner::MinerConfig;
+use ckb_network::multiaddr::{Multiaddr, Protocol};
 use ckb_network::NetworkConfig;
 use ckb_network_alert::config::Config as AlertConfig;
 use ckb_resource::Resource;
@@ -67,6 +72,60 @@ pub struct ChainConfig {
     pub spec: Resource,
 }
 
+const ENV_RPC_PORT: &str = "CKB_RPC_PORT";
+const ENV_P2P_PORT: &str = "CKB_P2P_PORT";
+const ENV_LOG_LEVEL: &str = "CKB_LOG_LEVEL";
+const ENV_CHAIN: &str = "CKB_CHAIN";
+const ENV_DATA_DIR: &str = "CKB_DATA_DIR";
+
+const LOG_LEVELS: &[&str] = &["off", "error", "warn", "info", "debug", "trace"];
+
+/// Misconfiguration found by `AppConfig::validate`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// `rpc.listen_address` is not a socket address with a non-zero port.
+    InvalidRpcPort(String),
+    /// RPC and P2P listen on the same port, as `(rpc_port, p2p_port)`.
+    ConflictingPorts(u16, u16),
+    /// The chain spec file does not exist.
+    MissingChainSpec,
+    /// The logger filter names an unknown level.
+    InvalidLogLevel(String),
+    /// The directory is missing or read only.
+    PathNotWritable(PathBuf),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::InvalidRpcPort(address) => {
+                write!(f, "invalid rpc listen address {:?}", address)
+            }
+            ConfigError::ConflictingPorts(rpc_port, p2p_port) => write!(
+                f,
+                "rpc port {} conflicts with p2p port {}",
+                rpc_port, p2p_port
+            ),
+            ConfigError::MissingChainSpec => write!(f, "chain spec does not exist"),
+            ConfigError::InvalidLogLevel(level) => write!(f, "invalid log level {:?}", level),
+            ConfigError::PathNotWritable(path) => {
+                write!(f, "path {} is not writable", path.display())
+            }
+        }
+    }
+}
+
+/// A field changed between two configs, found by `AppConfig::diff`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigDiff {
+    /// The dotted path to the field, such as `rpc.listen_address`
+    pub field_path: String,
+    /// `Null` if the field is missing in the old config
+    pub old_value: serde_json::Value,
+    /// `Null` if the field is missing in the new config
+    pub new_value: serde_json::Value,
+}
+
 impl AppConfig {
     pub fn load_for_subcommand<P: AsRef<Path>>(
         root_dir: P,
@@ -77,18 +136,69 @@ impl AppConfig {
                 let resource = ensure_ckb_dir(Resource::miner_config(root_dir.as_ref()))?;
                 let config: MinerAppConfig = toml::from_slice(&resource.get()?)?;
 
-                Ok(AppConfig::with_miner(
-                    config.derive_options(root_dir.as_ref())?,
-                ))
+                let mut config = AppConfig::with_miner(config);
+                config.apply_env_overrides()?;
+                config.derive_options(root_dir.as_ref(), subcommand_name)
             }
             _ => {
                 let resource = ensure_ckb_dir(Resource::ckb_config(root_dir.as_ref()))?;
                 let config: CKBAppConfig = toml::from_slice(&resource.get()?)?;
-                Ok(AppConfig::with_ckb(
-                    config.derive_options(root_dir.as_ref(), subcommand_name)?,
-                ))
+
+                let mut config = AppConfig::with_ckb(config);
+                config.apply_env_overrides()?;
+                config.derive_options(root_dir.as_ref(), subcommand_name)
+            }
+        }
+    }
+
+    /// Overrides config values by the environment variables listed below. Relative
+    /// paths are resolved against the CKB directory like those in the config files.
+    ///
+    /// - `CKB_RPC_PORT`: the port of `rpc.listen_address`, ckb.toml only
+    /// - `CKB_P2P_PORT`: the tcp port of all `network.listen_addresses`, ckb.toml only
+    /// - `CKB_LOG_LEVEL`: `logger.filter`
+    /// - `CKB_CHAIN`: `chain.spec`, `testnet` selects the bundled spec and any other
+    ///   name the file `specs/<name>.toml`
+    /// - `CKB_DATA_DIR`: `data_dir`
+    pub fn apply_env_overrides(&mut self) -> Result<(), ExitCode> {
+        self.apply_overrides(|name| env::var(name).ok())
+    }
+
+    fn apply_overrides<F>(&mut self, var: F) -> Result<(), ExitCode>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let (data_dir, chain, logger) = match self {
+            AppConfig::CKB(config) => (&mut config.data_dir, &mut config.chain, &mut config.logger),
+            AppConfig::Miner(config) => {
+                (&mut config.data_dir, &mut config.chain, &mut config.logger)
             }
+        };
+        if let Some(level) = var(ENV_LOG_LEVEL) {
+            logger.filter = Some(level);
+        }
+        if let Some(name) = var(ENV_CHAIN) {
+            chain.spec = if name == "testnet" {
+                Resource::bundled(format!("specs/{}.toml", name))
+            } else {
+                Resource::file_system(Path::new("specs").join(format!("{}.toml", name)))
+            };
+        }
+        if let Some(dir) = var(ENV_DATA_DIR) {
+            *data_dir = PathBuf::from(dir);
         }
+        if let AppConfig::CKB(config) = self {
+            let partial = CKBAppConfigPartial {
+                rpc_port: var(ENV_RPC_PORT)
+                    .map(|port| parse_port(ENV_RPC_PORT, &port))
+                    .transpose()?,
+                p2p_port: var(ENV_P2P_PORT)
+                    .map(|port| parse_port(ENV_P2P_PORT, &port))
+                    .transpose()?,
+            };
+            config.merge_with(&partial);
+        }
+        Ok(())
     }
 
     pub fn logger(&self) -> &LogConfig {
@@ -116,6 +226,50 @@ impl AppConfig {
         })
     }
 
+    /// Checks the loaded config for mistakes which would otherwise only show up
+    /// after the node has started, reporting all of them at once.
+    pub fn validate(&self) -> Result<(), Vec<ConfigError>> {
+        let mut errors = Vec::new();
+        let (data_dir, chain, logger) = match self {
+            AppConfig::CKB(config) => (&config.data_dir, &config.chain, &config.logger),
+            AppConfig::Miner(config) => (&config.data_dir, &config.chain, &config.logger),
+        };
+        if !chain.spec.exists() {
+            errors.push(ConfigError::MissingChainSpec);
+        }
+        if let Some(filter) = &logger.filter {
+            errors.extend(invalid_log_levels(filter).map(ConfigError::InvalidLogLevel));
+        }
+        if !is_writable(data_dir) {
+            errors.push(ConfigError::PathNotWritable(data_dir.to_owned()));
+        }
+        if let AppConfig::CKB(config) = self {
+            match config.rpc.listen_address.parse::<SocketAddr>() {
+                Ok(address) if address.port() != 0 => {
+                    let rpc_port = address.port();
+                    errors.extend(
+                        config
+                            .network
+                            .listen_addresses
+                            .iter()
+                            .filter_map(tcp_port)
+                            .filter(|p2p_port| *p2p_port == rpc_port)
+                            .map(|p2p_port| ConfigError::ConflictingPorts(rpc_port, p2p_port)),
+                    );
+                }
+                _ => errors.push(ConfigError::InvalidRpcPort(
+                    config.rpc.listen_address.to_owned(),
+                )),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
     pub fn into_ckb(self) -> Result<Box<CKBAppConfig>, ExitCode> {
         match self {
             AppConfig::CKB(config) => Ok(config),
@@ -135,9 +289,30 @@ impl AppConfig {
             }
         }
     }
+
+    /// Lists the fields which differ from `a` to `b`. Tables are compared field by field,
+    /// and any other value, arrays included, as a whole.
+    pub fn diff(a: &CKBAppConfig, b: &CKBAppConfig) -> Vec<ConfigDiff> {
+        let a = serde_json::to_value(a).expect("serialize config");
+        let b = serde_json::to_value(b).expect("serialize config");
+        let mut diffs = Vec::new();
+        diff_values(String::new(), &a, &b, &mut diffs);
+        diffs
+    }
 }
 
 impl AppConfig {
+    fn derive_options(self, root_dir: &Path, subcommand_name: &str) -> Result<Self, ExitCode> {
+        match self {
+            AppConfig::CKB(config) => Ok(AppConfig::with_ckb(
+                (*config).derive_options(root_dir, subcommand_name)?,
+            )),
+            AppConfig::Miner(config) => {
+                Ok(AppConfig::with_miner((*config).derive_options(root_dir)?))
+            }
+        }
+    }
+
     fn with_ckb(config: CKBAppConfig) -> AppConfig {
         AppConfig::CKB(Box::new(config))
     }
@@ -146,7 +321,39 @@ impl AppConfig {
     }
 }
 
+/// The `CKBAppConfig` fields set on top of the config file, `None` keeps the value read
+/// from the file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CKBAppConfigPartial {
+    /// Replaces the port of `rpc.listen_address`
+    pub rpc_port: Option<u16>,
+    /// Replaces the tcp port of all `network.listen_addresses`
+    pub p2p_port: Option<u16>,
+}
+
 impl CKBAppConfig {
+    /// Overwrites the fields which are set in `other`
+    pub fn merge_with(&mut self, other: &CKBAppConfigPartial) {
+        if let Some(port) = other.rpc_port {
+            let address = &self.rpc.listen_address;
+            self.rpc.listen_address = match address.rfind(':') {
+                Some(index) => format!("{}:{}", &address[..index], port),
+                None => format!("{}:{}", address, port),
+            };
+        }
+        if let Some(port) = other.p2p_port {
+            for address in self.network.listen_addresses.iter_mut() {
+                *address = address
+                    .iter()
+                    .map(|protocol| match protocol {
+                        Protocol::Tcp(_) => Protocol::Tcp(port),
+                        value => value,
+                    })
+                    .collect();
+            }
+        }
+    }
+
     fn derive_options(mut self, root_dir: &Path, subcommand_name: &str) -> Result<Self, ExitCode> {
         self.data_dir = canonicalize_data_dir(self.data_dir, root_dir)?;
         if self.logger.log_to_file {
@@ -201,6 +408,72 @@ fn touch(path: PathBuf) -> Result<PathBuf, ExitCode> {
     Ok(path)
 }
 
+// Only the `target=level` directives are checked, a bare word may be either a
+// level or a target.
+fn invalid_log_levels<'a>(filter: &'a str) -> impl Iterator<Item = String> + 'a {
+    filter
+        .split(',')
+        .filter_map(|directive| directive.splitn(2, '=').nth(1))
+        .map(str::trim)
+        .filter(|level| !LOG_LEVELS.contains(&level.to_lowercase().as_str()))
+        .map(ToOwned::to_owned)
+}
+
+fn parse_port(name: &str, value: &str) -> Result<u16, ExitCode> {
+    value.parse().map_err(|_| {
+        eprintln!("Config Error: invalid port {:?} in {}", value, name);
+        ExitCode::Config
+    })
+}
+
+fn is_writable(path: &Path) -> bool {
+    fs::metadata(path)
+        .map(|metadata| metadata.is_dir() && !metadata.permissions().readonly())
+        .unwrap_or(false)
+}
+
+fn tcp_port(address: &Multiaddr) -> Option<u16> {
+    address.iter().find_map(|protocol| match protocol {
+        Protocol::Tcp(port) => Some(port),
+        _ => None,
+    })
+}
+
+fn diff_values(
+    path: String,
+    old: &serde_json::Value,
+    new: &serde_json::Value,
+    diffs: &mut Vec<ConfigDiff>,
+) {
+    match (old, new) {
+        (serde_json::Value::Object(old), serde_json::Value::Object(new)) => {
+            let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+            for key in keys {
+                let field_path = if path.is_empty() {
+                    key.to_owned()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                diff_values(
+                    field_path,
+                    old.get(key).unwrap_or(&serde_json::Value::Null),
+                    new.get(key).unwrap_or(&serde_json::Value::Null),
+                    diffs,
+                );
+            }
+        }
+        (old, new) => {
+            if old != new {
+                diffs.push(ConfigDiff {
+                    field_path: path,
+                    old_value: old.to_owned(),
+                    new_value: new.to_owned(),
+                });
+            }
+        }
+    }
+}
+
 fn ensure_ckb_dir(r: Resource) -> Result<Resource, ExitCode> {
     if r.exists() {
         Ok(r)
@@ -214,6 +487,7 @@ fn ensure_ckb_dir(r: Resource) -> Result<Resource, ExitCode> {
 mod tests {
     use super::*;
     use ckb_resource::TemplateContext;
+    use std::collections::HashMap;
 
     fn mkdir() -> tempfile::TempDir {
         tempfile::Builder::new()
@@ -222,6 +496,221 @@ mod tests {
             .unwrap()
     }
 
+    fn load_dev_config(dir: &Path) -> Box<CKBAppConfig> {
+        let context = TemplateContext {
+            spec: "dev",
+            rpc_port: "7000",
+            p2p_port: "8000",
+            log_to_file: false,
+            log_to_stdout: true,
+            runner: "Rust",
+            block_assembler: "",
+            profile: None,
+        };
+        Resource::bundled_ckb_config()
+            .export(&context, dir)
+            .expect("export config files");
+        Resource::bundled("specs/dev.toml".to_string())
+            .export(&context, dir)
+            .expect("export spec file");
+        AppConfig::load_for_subcommand(dir, cli::CMD_RUN)
+            .unwrap_or_else(|err| panic!(err))
+            .into_ckb()
+            .unwrap_or_else(|err| panic!(err))
+    }
+
+    fn validate(config: Box<CKBAppConfig>) -> Result<(), Vec<ConfigError>> {
+        AppConfig::CKB(config).validate()
+    }
+
+    #[test]
+    fn test_validate_dev_config() {
+        let dir = mkdir();
+        assert_eq!(validate(load_dev_config(dir.path())), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_invalid_rpc_port() {
+        let dir = mkdir();
+        let mut config = load_dev_config(dir.path());
+        config.rpc.listen_address = "127.0.0.1".to_string();
+        assert_eq!(
+            validate(config.clone()),
+            Err(vec![ConfigError::InvalidRpcPort("127.0.0.1".to_string())])
+        );
+        config.rpc.listen_address = "127.0.0.1:0".to_string();
+        assert_eq!(
+            validate(config),
+            Err(vec![ConfigError::InvalidRpcPort("127.0.0.1:0".to_string())])
+        );
+    }
+
+    #[test]
+    fn test_validate_conflicting_ports() {
+        let dir = mkdir();
+        let mut config = load_dev_config(dir.path());
+        config.rpc.listen_address = "127.0.0.1:8000".to_string();
+        assert_eq!(
+            validate(config),
+            Err(vec![ConfigError::ConflictingPorts(8000, 8000)])
+        );
+    }
+
+    #[test]
+    fn test_validate_missing_chain_spec() {
+        let dir = mkdir();
+        let mut config = load_dev_config(dir.path());
+        config.chain.spec = Resource::file_system(dir.path().join("specs").join("missing.toml"));
+        assert_eq!(validate(config), Err(vec![ConfigError::MissingChainSpec]));
+    }
+
+    #[test]
+    fn test_validate_invalid_log_level() {
+        let dir = mkdir();
+        let mut config = load_dev_config(dir.path());
+        config.logger.filter = Some("info,ckb-chain=verbose,ckb-sync=DEBUG".to_string());
+        assert_eq!(
+            validate(config),
+            Err(vec![ConfigError::InvalidLogLevel("verbose".to_string())])
+        );
+    }
+
+    #[test]
+    fn test_validate_path_not_writable() {
+        let dir = mkdir();
+        let mut config = load_dev_config(dir.path());
+        let data_dir = dir.path().join("missing");
+        config.data_dir = data_dir.clone();
+        assert_eq!(
+            validate(config.clone()),
+            Err(vec![ConfigError::PathNotWritable(data_dir)])
+        );
+
+        let read_only_dir = dir.path().join("read_only");
+        fs::create_dir(&read_only_dir).unwrap();
+        let mut permissions = fs::metadata(&read_only_dir).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&read_only_dir, permissions).unwrap();
+        config.data_dir = read_only_dir.clone();
+        assert_eq!(
+            validate(config),
+            Err(vec![ConfigError::PathNotWritable(read_only_dir)])
+        );
+    }
+
+    #[test]
+    fn test_validate_reports_all_errors() {
+        let dir = mkdir();
+        let mut config = load_dev_config(dir.path());
+        config.rpc.listen_address = "localhost".to_string();
+        config.logger.filter = Some("ckb=loud".to_string());
+        assert_eq!(
+            validate(config),
+            Err(vec![
+                ConfigError::InvalidLogLevel("loud".to_string()),
+                ConfigError::InvalidRpcPort("localhost".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_merge_with() {
+        let dir = mkdir();
+        let config = load_dev_config(dir.path());
+
+        let mut merged = config.clone();
+        merged.merge_with(&CKBAppConfigPartial::default());
+        assert_eq!(AppConfig::diff(&config, &merged), Vec::new());
+
+        let mut merged = config.clone();
+        merged.merge_with(&CKBAppConfigPartial {
+            rpc_port: Some(7100),
+            p2p_port: None,
+        });
+        assert_eq!(merged.rpc.listen_address, "127.0.0.1:7100");
+        assert_eq!(
+            merged.network.listen_addresses,
+            config.network.listen_addresses
+        );
+
+        let mut merged = config.clone();
+        merged.merge_with(&CKBAppConfigPartial {
+            rpc_port: None,
+            p2p_port: Some(8100),
+        });
+        assert_eq!(merged.rpc.listen_address, config.rpc.listen_address);
+        assert_eq!(
+            merged.network.listen_addresses,
+            vec!["/ip4/0.0.0.0/tcp/8100".parse().unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_apply_overrides() {
+        let dir = mkdir();
+        let mut config = AppConfig::CKB(load_dev_config(dir.path()));
+        let vars: HashMap<&str, &str> = vec![
+            ("CKB_RPC_PORT", "7100"),
+            ("CKB_P2P_PORT", "8100"),
+            ("CKB_LOG_LEVEL", "debug"),
+            ("CKB_CHAIN", "testnet"),
+            ("CKB_DATA_DIR", "/tmp/ckb"),
+        ]
+        .into_iter()
+        .collect();
+        config
+            .apply_overrides(|name| vars.get(name).map(|value| value.to_string()))
+            .unwrap();
+
+        let ckb_config = config.into_ckb().unwrap_or_else(|err| panic!(err));
+        assert_eq!(ckb_config.rpc.listen_address, "127.0.0.1:7100");
+        assert_eq!(
+            ckb_config.network.listen_addresses,
+            vec!["/ip4/0.0.0.0/tcp/8100".parse().unwrap()]
+        );
+        assert_eq!(ckb_config.logger.filter, Some("debug".to_string()));
+        assert_eq!(
+            ckb_config.chain.spec,
+            Resource::bundled("specs/testnet.toml".to_string())
+        );
+        assert_eq!(ckb_config.data_dir, PathBuf::from("/tmp/ckb"));
+    }
+
+    #[test]
+    fn test_apply_overrides_to_miner() {
+        let mut config = AppConfig::Miner(Box::new(
+            toml::from_slice(&Resource::bundled_miner_config().get().unwrap()).unwrap(),
+        ));
+        config
+            .apply_overrides(|name| match name {
+                "CKB_CHAIN" => Some("integration".to_string()),
+                // ignored by the miner
+                "CKB_RPC_PORT" => Some("7100".to_string()),
+                _ => None,
+            })
+            .unwrap();
+        let miner_config = config.into_miner().unwrap_or_else(|err| panic!(err));
+        assert_eq!(
+            miner_config.chain.spec,
+            Resource::file_system(PathBuf::from("specs/integration.toml"))
+        );
+    }
+
+    #[test]
+    fn test_apply_overrides_with_invalid_port() {
+        let dir = mkdir();
+        let mut config = AppConfig::CKB(load_dev_config(dir.path()));
+        assert_eq!(
+            config
+                .apply_overrides(|name| match name {
+                    "CKB_P2P_PORT" => Some("80000".to_string()),
+                    _ => None,
+                })
+                .err(),
+            Some(ExitCode::Config)
+        );
+    }
+
     #[test]
     fn test_bundled_config_files() {
         let resource = Resource::bundled_ckb_config();
@@ -244,6 +733,7 @@ mod tests {
             log_to_stdout: true,
             runner: "Rust",
             block_assembler: "",
+            profile: None,
         };
         {
             Resource::bundled_ckb_config()
@@ -280,6 +770,48 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_export_profile_config_files() {
+        let export = |spec: &str, profile: Option<&str>| {
+            let dir = mkdir();
+            let context = TemplateContext {
+                spec,
+                rpc_port: "7000",
+                p2p_port: "8000",
+                log_to_file: true,
+                log_to_stdout: true,
+                runner: "Rust",
+                block_assembler: "",
+                profile,
+            };
+            Resource::bundled_ckb_config()
+                .export(&context, dir.path())
+                .expect("export config files");
+            let app_config = AppConfig::load_for_subcommand(dir.path(), cli::CMD_RUN)
+                .unwrap_or_else(|err| panic!(err));
+            app_config.into_ckb().unwrap_or_else(|err| panic!(err))
+        };
+
+        let dev = export("dev", Some("dev"));
+        let testnet = export("dev", Some("testnet"));
+        let mainnet = export("dev", Some("mainnet"));
+        assert!(!dev.sentry.is_enabled());
+        assert!(testnet.sentry.is_enabled());
+        assert!(mainnet.sentry.is_enabled());
+        assert!(dev.store.header_cache_size < testnet.store.header_cache_size);
+        assert!(testnet.store.header_cache_size < mainnet.store.header_cache_size);
+        assert!(dev.store.cell_output_cache_size < testnet.store.cell_output_cache_size);
+        assert!(testnet.store.cell_output_cache_size < mainnet.store.cell_output_cache_size);
+
+        // the profile takes precedence over the chain
+        assert!(export("testnet", None).sentry.is_enabled());
+        assert!(!export("testnet", Some("dev")).sentry.is_enabled());
+        assert_eq!(
+            export("testnet", Some("mainnet")).chain.spec,
+            Resource::bundled("specs/testnet.toml".to_string())
+        );
+    }
+
     #[test]
     fn test_log_to_stdout_only() {
         let dir = mkdir();
@@ -291,6 +823,7 @@ mod tests {
             log_to_stdout: true,
             runner: "Rust",
             block_assembler: "",
+            profile: None,
         };
         {
             Resource::bundled_ckb_config()
@@ -327,6 +860,7 @@ mod tests {
             log_to_stdout: true,
             runner: "Rust",
             block_assembler: "",
+            profile: None,
         };
         {
             Resource::bundled_ckb_config()
@@ -374,6 +908,7 @@ mod tests {
             log_to_stdout: true,
             runner: "Rust",
             block_assembler: "",
+            profile: None,
         };
         {
             Resource::bundled_ckb_config()
@@ -419,6 +954,7 @@ mod tests {
             log_to_stdout: true,
             runner: "Assembly",
             block_assembler: "",
+            profile: None,
         };
         {
             Resource::bundled_ckb_config()
@@ -454,4 +990,37 @@ mod tests {
             assert_eq!(miner_config.miner.client.rpc_url, "http://127.0.0.1:7000/");
         }
     }
+
+    #[test]
+    fn test_diff() {
+        let dir = mkdir();
+        let old = load_dev_config(dir.path());
+        let mut new = old.clone();
+        assert_eq!(AppConfig::diff(&old, &new), Vec::new());
+
+        new.rpc.listen_address = "127.0.0.1:7001".to_string();
+        new.store.header_cache_size = 1024;
+        new.sentry.dsn = "https://key@sentry.io/1".to_string();
+        let diffs = AppConfig::diff(&old, &new);
+        assert_eq!(diffs.len(), 3);
+        assert_eq!(
+            diffs
+                .iter()
+                .map(|diff| diff.field_path.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "rpc.listen_address",
+                "sentry.dsn",
+                "store.header_cache_size"
+            ]
+        );
+        assert_eq!(
+            diffs[0],
+            ConfigDiff {
+                field_path: "rpc.listen_address".to_string(),
+                old_value: serde_json::Value::String("127.0.0.1:7000".to_string()),
+                new_value: serde_json::Value::String("127.0.0.1:7001".to_string()),
+            }
+        );
+    }
 }
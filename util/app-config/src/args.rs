@@ -23,6 +23,9 @@ pub struct ImportArgs {
 pub struct RunArgs {
     pub config: Box<CKBAppConfig>,
     pub consensus: Consensus,
+    pub dry_run: bool,
+    pub repair: bool,
+    pub delete_blocks_after: Option<u64>,
 }
 
 pub struct ProfArgs {
@@ -32,9 +35,31 @@ pub struct ProfArgs {
     pub to: u64,
 }
 
+pub struct DbStatsArgs {
+    pub config: Box<CKBAppConfig>,
+    pub json: bool,
+    pub repair: bool,
+}
+
+pub struct VerifyArgs {
+    pub config: Box<CKBAppConfig>,
+    pub consensus: Consensus,
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+}
+
+pub struct MigrateArgs {
+    pub config: Box<CKBAppConfig>,
+    pub consensus: Consensus,
+    pub from_version: String,
+    pub to_version: String,
+}
+
 pub struct MinerArgs {
     pub config: MinerConfig,
     pub pow_engine: Arc<dyn PowEngine>,
+    /// Overrides the threads of the CuckooSimple workers when specified
+    pub num_workers: Option<usize>,
 }
 
 pub struct InitArgs {
@@ -48,4 +73,7 @@ pub struct InitArgs {
     pub force: bool,
     pub block_assembler_code_hash: Option<String>,
     pub block_assembler_args: Vec<String>,
+    pub export_consensus: bool,
+    /// Tunes the generated config for `dev`, `testnet` or `mainnet`
+    pub profile: Option<String>,
 }
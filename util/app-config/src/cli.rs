@@ -1,5 +1,5 @@
 use build_info::Version;
-use ckb_resource::{DEFAULT_P2P_PORT, DEFAULT_RPC_PORT, DEFAULT_SPEC};
+use ckb_resource::{AVAILABLE_PROFILES, DEFAULT_P2P_PORT, DEFAULT_RPC_PORT, DEFAULT_SPEC};
 use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 
 pub const CMD_RUN: &str = "run";
@@ -8,7 +8,12 @@ pub const CMD_EXPORT: &str = "export";
 pub const CMD_IMPORT: &str = "import";
 pub const CMD_INIT: &str = "init";
 pub const CMD_PROF: &str = "prof";
+pub const CMD_DB_STATS: &str = "db-stats";
+pub const CMD_MIGRATE: &str = "migrate";
+pub const CMD_VERIFY: &str = "verify";
 pub const CMD_CLI: &str = "cli";
+pub const CMD_CONFIG: &str = "config";
+pub const CMD_DIFF: &str = "diff";
 pub const CMD_HASHES: &str = "hashes";
 pub const CMD_BLAKE256: &str = "blake256";
 pub const CMD_BLAKE160: &str = "blake160";
@@ -28,6 +33,15 @@ pub const ARG_LOG_TO: &str = "log-to";
 pub const ARG_BUNDLED: &str = "bundled";
 pub const ARG_BA_CODE_HASH: &str = "ba-code-hash";
 pub const ARG_BA_ARG: &str = "ba-arg";
+pub const ARG_JSON: &str = "json";
+pub const ARG_DRY_RUN: &str = "dry-run";
+pub const ARG_WORKERS: &str = "workers";
+pub const ARG_EXPORT_CONSENSUS: &str = "export-consensus";
+pub const ARG_DELETE_BLOCKS_AFTER: &str = "delete-blocks-after";
+pub const ARG_REPAIR: &str = "repair";
+pub const ARG_PROFILE: &str = "profile";
+pub const ARG_FROM: &str = "from";
+pub const ARG_TO: &str = "to";
 
 pub fn get_matches(version: &Version) -> ArgMatches<'static> {
     App::new("ckb")
@@ -51,17 +65,64 @@ pub fn get_matches(version: &Version) -> ArgMatches<'static> {
         .subcommand(export())
         .subcommand(import())
         .subcommand(cli())
+        .subcommand(config())
         .subcommand(init())
         .subcommand(prof())
+        .subcommand(db_stats())
+        .subcommand(migrate())
+        .subcommand(verify())
         .get_matches()
 }
 
 fn run() -> App<'static, 'static> {
-    SubCommand::with_name(CMD_RUN).about("Runs ckb node")
+    SubCommand::with_name(CMD_RUN)
+        .about("Runs ckb node")
+        .arg(
+            Arg::with_name(ARG_DRY_RUN)
+                .long(ARG_DRY_RUN)
+                .help("Checks the database and the tip, then exits without starting the node."),
+        )
+        .arg(
+            Arg::with_name(ARG_DELETE_BLOCKS_AFTER)
+                .long(ARG_DELETE_BLOCKS_AFTER)
+                .value_name("number")
+                .takes_value(true)
+                .help("Deletes the main chain blocks after <number> before starting the node"),
+        )
+        .arg(
+            Arg::with_name(ARG_REPAIR)
+                .long(ARG_REPAIR)
+                .help("Repairs the database before starting the node, e.g. after a crash."),
+        )
+        .arg(
+            Arg::with_name(ARG_RPC_PORT)
+                .long(ARG_RPC_PORT)
+                .value_name("port")
+                .takes_value(true)
+                .help("Overrides the port of rpc.listen_address in the config file"),
+        )
+        .arg(
+            Arg::with_name(ARG_P2P_PORT)
+                .long(ARG_P2P_PORT)
+                .value_name("port")
+                .takes_value(true)
+                .help("Overrides the port of network.listen_addresses in the config file"),
+        )
 }
 
 fn miner() -> App<'static, 'static> {
-    SubCommand::with_name(CMD_MINER).about("Runs ckb miner")
+    SubCommand::with_name(CMD_MINER)
+        .about("Runs ckb miner")
+        .arg(
+            Arg::with_name(ARG_WORKERS)
+                .long(ARG_WORKERS)
+                .value_name("number")
+                .takes_value(true)
+                .help(
+                    "Specifies the number of threads searching nonces in each CuckooSimple \
+                     worker, overrides `threads` in ckb-miner.toml.",
+                ),
+        )
 }
 
 fn prof() -> App<'static, 'static> {
@@ -85,6 +146,45 @@ fn prof() -> App<'static, 'static> {
         )
 }
 
+fn db_stats() -> App<'static, 'static> {
+    SubCommand::with_name(CMD_DB_STATS)
+        .about("Prints the number of entries and bytes in each chain database column")
+        .arg(
+            Arg::with_name(ARG_JSON)
+                .long(ARG_JSON)
+                .help("Prints the statistics in JSON instead of a table."),
+        )
+        .arg(
+            Arg::with_name(ARG_REPAIR)
+                .long(ARG_REPAIR)
+                .help("Repairs the database before reading it."),
+        )
+}
+
+fn migrate() -> App<'static, 'static> {
+    SubCommand::with_name(CMD_MIGRATE)
+        .about("Migrates the chain database to the version of this ckb")
+}
+
+fn verify() -> App<'static, 'static> {
+    SubCommand::with_name(CMD_VERIFY)
+        .about("Verifies the main chain blocks in the database without starting the node")
+        .arg(
+            Arg::with_name(ARG_FROM)
+                .long(ARG_FROM)
+                .value_name("number")
+                .takes_value(true)
+                .help("Specifies the first block number to verify, defaults to 1."),
+        )
+        .arg(
+            Arg::with_name(ARG_TO)
+                .long(ARG_TO)
+                .value_name("number")
+                .takes_value(true)
+                .help("Specifies the last block number to verify, defaults to the tip."),
+        )
+}
+
 fn arg_format() -> Arg<'static, 'static> {
     Arg::with_name(ARG_FORMAT)
         .short("f")
@@ -135,6 +235,34 @@ fn cli() -> App<'static, 'static> {
         .subcommand(cli_secp256k1_lock())
 }
 
+fn config() -> App<'static, 'static> {
+    SubCommand::with_name(CMD_CONFIG)
+        .about("Config tools")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(config_diff())
+}
+
+fn config_diff() -> App<'static, 'static> {
+    SubCommand::with_name(CMD_DIFF)
+        .about("Lists the fields changed between two ckb.toml files")
+        .arg(
+            Arg::with_name(ARG_FROM)
+                .long(ARG_FROM)
+                .value_name("path")
+                .required(true)
+                .takes_value(true)
+                .help("Specifies the old config file."),
+        )
+        .arg(
+            Arg::with_name(ARG_TO)
+                .long(ARG_TO)
+                .value_name("path")
+                .required(true)
+                .takes_value(true)
+                .help("Specifies the new config file."),
+        )
+}
+
 fn cli_hashes() -> App<'static, 'static> {
     SubCommand::with_name(CMD_HASHES)
         .about("Lists well known hashes")
@@ -252,6 +380,18 @@ fn init() -> App<'static, 'static> {
                 .number_of_values(1)
                 .help("Sets args in [block_assembler]"),
         )
+        .arg(
+            Arg::with_name(ARG_EXPORT_CONSENSUS)
+                .long(ARG_EXPORT_CONSENSUS)
+                .help("Exports the consensus parameters of <chain> as JSON"),
+        )
+        .arg(
+            Arg::with_name(ARG_PROFILE)
+                .long(ARG_PROFILE)
+                .possible_values(AVAILABLE_PROFILES)
+                .takes_value(true)
+                .help("Tunes the created config file for <profile>"),
+        )
         .arg(
             Arg::with_name("export-specs")
                 .long("export-specs")
@@ -1,3 +1,4 @@
+use crate::ConfigError;
 use std::io;
 
 /// Uses 0, 64 - 113 as exit code.
@@ -43,3 +44,40 @@ impl From<clap::Error> for ExitCode {
         ExitCode::Cli
     }
 }
+
+impl From<ckb_db::Error> for ExitCode {
+    fn from(err: ckb_db::Error) -> ExitCode {
+        eprintln!("DB Error: {:?}", err);
+        ExitCode::Failure
+    }
+}
+
+impl From<ConfigError> for ExitCode {
+    fn from(err: ConfigError) -> ExitCode {
+        eprintln!("Config Error: {}", err);
+        ExitCode::Config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_db_error() {
+        let err = ckb_db::Error::DBError("corrupted".to_string());
+        assert_eq!(ExitCode::from(err), ExitCode::Failure);
+    }
+
+    #[test]
+    fn test_from_config_error() {
+        assert_eq!(
+            ExitCode::from(ConfigError::MissingChainSpec),
+            ExitCode::Config
+        );
+        assert_eq!(
+            ExitCode::from(ConfigError::ConflictingPorts(8114, 8114)),
+            ExitCode::Config
+        );
+    }
+}
@@ -2,29 +2,45 @@ mod app_config;
 mod args;
 pub mod cli;
 mod exit_code;
+mod reload;
 mod sentry_config;
 
-pub use app_config::{AppConfig, CKBAppConfig, MinerAppConfig};
-pub use args::{ExportArgs, ImportArgs, InitArgs, MinerArgs, ProfArgs, RunArgs};
+pub use app_config::{
+    AppConfig, CKBAppConfig, CKBAppConfigPartial, ConfigDiff, ConfigError, MinerAppConfig,
+};
+pub use args::{
+    DbStatsArgs, ExportArgs, ImportArgs, InitArgs, MigrateArgs, MinerArgs, ProfArgs, RunArgs,
+    VerifyArgs,
+};
 pub use ckb_miner::BlockAssemblerConfig;
 pub use exit_code::ExitCode;
 
 use build_info::Version;
 use ckb_chain_spec::{consensus::Consensus, ChainSpec};
+use ckb_db::rocksdb::VERSION_VALUE;
+use ckb_db::RocksDB;
 use ckb_instrument::Format;
 use ckb_logger::{info_target, LoggerInitGuard};
+use ckb_network::multiaddr::{Multiaddr, Protocol};
+use ckb_store::COLUMNS;
 use clap::{value_t, ArgMatches};
+use reload::ReloadGuard;
+use std::net::{IpAddr, SocketAddr, TcpListener};
 use std::path::PathBuf;
 
 pub(crate) const LOG_TARGET_SENTRY: &str = "sentry";
 
 pub struct Setup {
     subcommand_name: String,
+    root_dir: PathBuf,
     config: AppConfig,
     is_sentry_enabled: bool,
 }
 
 pub struct SetupGuard {
+    // Declared first so the reload thread stops before the logger is flushed.
+    #[allow(dead_code)]
+    reload_guard: Option<ReloadGuard>,
     #[allow(dead_code)]
     logger_guard: LoggerInitGuard,
     #[allow(dead_code)]
@@ -42,11 +58,23 @@ impl Setup {
         };
 
         let root_dir = Self::root_dir_from_matches(matches)?;
-        let config = AppConfig::load_for_subcommand(&root_dir, subcommand_name)?;
+        let mut config = AppConfig::load_for_subcommand(&root_dir, subcommand_name)?;
+        if let (cli::CMD_RUN, Some(run_matches)) = matches.subcommand() {
+            if let AppConfig::CKB(ckb_config) = &mut config {
+                ckb_config.merge_with(&run_overrides(run_matches)?);
+            }
+        }
+        if let Err(errors) = config.validate() {
+            for error in errors {
+                eprintln!("Config Error: {}", error);
+            }
+            return Err(ExitCode::Config);
+        }
         let is_sentry_enabled = is_daemon(&subcommand_name) && config.sentry().is_enabled();
 
         Ok(Setup {
             subcommand_name: subcommand_name.to_string(),
+            root_dir,
             config,
             is_sentry_enabled,
         })
@@ -82,27 +110,93 @@ impl Setup {
             None
         };
 
+        // Send `SIGHUP` to a daemon to reload the logger filter from the config file.
+        let reload_guard = if is_daemon(&self.subcommand_name) {
+            Some(ReloadGuard::spawn(
+                self.root_dir.clone(),
+                self.subcommand_name.clone(),
+            ))
+        } else {
+            None
+        };
+
         Ok(SetupGuard {
+            reload_guard,
             logger_guard,
             sentry_guard,
         })
     }
 
-    pub fn run(self) -> Result<RunArgs, ExitCode> {
+    /// Binds the RPC and P2P listen addresses and releases them right away, so a port in
+    /// use is reported before the node starts instead of failing later in the network or
+    /// RPC service.
+    pub fn check_port_conflicts(config: &CKBAppConfig) -> Result<(), ExitCode> {
+        let rpc_address = config.rpc.listen_address.parse::<SocketAddr>().ok();
+        let p2p_addresses = config
+            .network
+            .listen_addresses
+            .iter()
+            .filter_map(socket_addr);
+        for address in rpc_address.into_iter().chain(p2p_addresses) {
+            // The OS picks a free port for 0
+            if address.port() == 0 {
+                continue;
+            }
+            if let Err(err) = TcpListener::bind(address) {
+                eprintln!(
+                    "Config Error: port {} is unavailable, cannot listen on {}: {}",
+                    address.port(),
+                    address,
+                    err
+                );
+                return Err(ExitCode::Config);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn run<'m>(self, matches: &ArgMatches<'m>) -> Result<RunArgs, ExitCode> {
         let consensus = self.consensus()?;
         let config = self.config.into_ckb()?;
+        let dry_run = matches.is_present(cli::ARG_DRY_RUN);
+        let repair = matches.is_present(cli::ARG_REPAIR);
+        let delete_blocks_after = if matches.is_present(cli::ARG_DELETE_BLOCKS_AFTER) {
+            Some(value_t!(
+                matches.value_of(cli::ARG_DELETE_BLOCKS_AFTER),
+                u64
+            )?)
+        } else {
+            None
+        };
 
-        Ok(RunArgs { config, consensus })
+        Ok(RunArgs {
+            config,
+            consensus,
+            dry_run,
+            repair,
+            delete_blocks_after,
+        })
     }
 
-    pub fn miner(self) -> Result<MinerArgs, ExitCode> {
+    pub fn miner<'m>(self, matches: &ArgMatches<'m>) -> Result<MinerArgs, ExitCode> {
         let spec = self.chain_spec()?;
         let config = self.config.into_miner()?;
         let pow_engine = spec.pow_engine();
+        let num_workers = if matches.is_present(cli::ARG_WORKERS) {
+            let num_workers = value_t!(matches.value_of(cli::ARG_WORKERS), usize)?;
+            if num_workers == 0 {
+                eprintln!("Option `--{}` must be at least 1", cli::ARG_WORKERS);
+                return Err(ExitCode::Cli);
+            }
+            Some(num_workers)
+        } else {
+            None
+        };
 
         Ok(MinerArgs {
             pow_engine,
             config: config.miner,
+            num_workers,
         })
     }
 
@@ -148,6 +242,58 @@ impl Setup {
         })
     }
 
+    pub fn db_stats<'m>(self, matches: &ArgMatches<'m>) -> Result<DbStatsArgs, ExitCode> {
+        let config = self.config.into_ckb()?;
+        let json = matches.is_present(cli::ARG_JSON);
+        let repair = matches.is_present(cli::ARG_REPAIR);
+
+        Ok(DbStatsArgs {
+            config,
+            json,
+            repair,
+        })
+    }
+
+    pub fn verify_chain<'m>(self, matches: &ArgMatches<'m>) -> Result<VerifyArgs, ExitCode> {
+        let consensus = self.consensus()?;
+        let config = self.config.into_ckb()?;
+        let number = |name: &str| -> Result<Option<u64>, ExitCode> {
+            if matches.is_present(name) {
+                Ok(Some(value_t!(matches.value_of(name), u64)?))
+            } else {
+                Ok(None)
+            }
+        };
+        let from = number(cli::ARG_FROM)?;
+        let to = number(cli::ARG_TO)?;
+
+        Ok(VerifyArgs {
+            config,
+            consensus,
+            from,
+            to,
+        })
+    }
+
+    pub fn migrate(self) -> Result<MigrateArgs, ExitCode> {
+        let consensus = self.consensus()?;
+        let config = self.config.into_ckb()?;
+        // The version check of a normal open would refuse exactly the databases to migrate
+        let from_version = RocksDB::open_for_migration(&config.db, COLUMNS)?
+            .version()?
+            .ok_or_else(|| {
+                eprintln!("Migrate error: version info about database is lost");
+                ExitCode::Failure
+            })?;
+
+        Ok(MigrateArgs {
+            config,
+            consensus,
+            from_version,
+            to_version: VERSION_VALUE.to_owned(),
+        })
+    }
+
     pub fn init<'m>(matches: &ArgMatches<'m>) -> Result<InitArgs, ExitCode> {
         if matches.is_present("list-specs") {
             eprintln!(
@@ -165,6 +311,8 @@ impl Setup {
         let list_chains =
             matches.is_present(cli::ARG_LIST_CHAINS) || matches.is_present("list-specs");
         let force = matches.is_present(cli::ARG_FORCE);
+        let export_consensus = matches.is_present(cli::ARG_EXPORT_CONSENSUS);
+        let profile = matches.value_of(cli::ARG_PROFILE).map(str::to_string);
         let chain = if matches.occurrences_of(cli::ARG_CHAIN) > 0 || !matches.is_present("spec") {
             matches.value_of(cli::ARG_CHAIN).unwrap().to_string()
         } else {
@@ -197,6 +345,8 @@ impl Setup {
             log_to_stdout,
             block_assembler_code_hash,
             block_assembler_args,
+            export_consensus,
+            profile,
         })
     }
 
@@ -246,9 +396,85 @@ fn is_daemon(subcommand_name: &str) -> bool {
     }
 }
 
+// The ports given to `ckb run`, which take precedence over the config file and the
+// environment variables
+fn run_overrides<'m>(matches: &ArgMatches<'m>) -> Result<CKBAppConfigPartial, ExitCode> {
+    let port = |name: &str| -> Result<Option<u16>, ExitCode> {
+        if matches.is_present(name) {
+            Ok(Some(value_t!(matches.value_of(name), u16)?))
+        } else {
+            Ok(None)
+        }
+    };
+    Ok(CKBAppConfigPartial {
+        rpc_port: port(cli::ARG_RPC_PORT)?,
+        p2p_port: port(cli::ARG_P2P_PORT)?,
+    })
+}
+
+fn socket_addr(address: &Multiaddr) -> Option<SocketAddr> {
+    let mut ip = None;
+    let mut port = None;
+    for protocol in address.iter() {
+        match protocol {
+            Protocol::Ip4(ipv4) => ip = Some(IpAddr::V4(ipv4)),
+            Protocol::Ip6(ipv6) => ip = Some(IpAddr::V6(ipv6)),
+            Protocol::Tcp(tcp_port) => port = Some(tcp_port),
+            _ => {}
+        }
+    }
+    match (ip, port) {
+        (Some(ip), Some(port)) => Some(SocketAddr::new(ip, port)),
+        _ => None,
+    }
+}
+
 fn consensus_from_spec(spec: &ChainSpec) -> Result<Consensus, ExitCode> {
     spec.build_consensus().map_err(|err| {
         eprintln!("chainspec error: {}", err);
         ExitCode::Config
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_resource::{Resource, TemplateContext};
+
+    fn dev_config(dir: &std::path::Path) -> CKBAppConfig {
+        let context = TemplateContext {
+            spec: "dev",
+            rpc_port: "0",
+            p2p_port: "0",
+            log_to_file: false,
+            log_to_stdout: true,
+            runner: "Rust",
+            block_assembler: "",
+            profile: None,
+        };
+        Resource::bundled_ckb_config()
+            .export(&context, dir)
+            .expect("export config files");
+        toml::from_slice(&Resource::ckb_config(dir).get().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_check_port_conflicts() {
+        let dir = tempfile::Builder::new()
+            .prefix("check_port_conflicts")
+            .tempdir()
+            .unwrap();
+        let mut config = dev_config(dir.path());
+        assert_eq!(Setup::check_port_conflicts(&config), Ok(()));
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        config.rpc.listen_address = format!("127.0.0.1:{}", port);
+        assert_eq!(Setup::check_port_conflicts(&config), Err(ExitCode::Config));
+
+        let mut config = dev_config(dir.path());
+        config.network.listen_addresses =
+            vec![format!("/ip4/127.0.0.1/tcp/{}", port).parse().unwrap()];
+        assert_eq!(Setup::check_port_conflicts(&config), Err(ExitCode::Config));
+    }
+}
@@ -0,0 +1,96 @@
+//! Reload the logger filter from the config file when the process receives `SIGHUP`.
+
+#[cfg(unix)]
+pub use self::unix::ReloadGuard;
+
+#[cfg(not(unix))]
+pub use self::noop::ReloadGuard;
+
+#[cfg(unix)]
+mod unix {
+    use crate::AppConfig;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread::{self, JoinHandle};
+    use std::time::Duration;
+
+    const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+    // Only async-signal-safe operations are allowed in the handler, so it just raises the flag
+    // and leaves the real work to the reload thread.
+    static SIGHUP_RECEIVED: AtomicBool = AtomicBool::new(false);
+
+    extern "C" fn on_sighup(_signal: libc::c_int) {
+        SIGHUP_RECEIVED.store(true, Ordering::SeqCst);
+    }
+
+    fn reload_logger(root_dir: &Path, subcommand_name: &str) {
+        match AppConfig::load_for_subcommand(root_dir, subcommand_name) {
+            Ok(config) => {
+                let filter = config.logger().filter.as_ref().map(String::as_str);
+                ckb_logger::set_level(filter);
+                ckb_logger::info!("reloaded logger filter: {:?}", filter);
+            }
+            Err(err) => {
+                ckb_logger::error!("failed to reload config: {:?}", err);
+            }
+        }
+    }
+
+    /// Stops and joins the reload thread when dropped.
+    pub struct ReloadGuard {
+        stop: Arc<AtomicBool>,
+        handle: Option<JoinHandle<()>>,
+    }
+
+    impl ReloadGuard {
+        pub fn spawn(root_dir: PathBuf, subcommand_name: String) -> ReloadGuard {
+            unsafe {
+                libc::signal(libc::SIGHUP, on_sighup as libc::sighandler_t);
+            }
+
+            let stop = Arc::new(AtomicBool::new(false));
+            let thread_stop = Arc::clone(&stop);
+            let handle = thread::Builder::new()
+                .name("ConfigReloader".to_owned())
+                .spawn(move || {
+                    while !thread_stop.load(Ordering::SeqCst) {
+                        if SIGHUP_RECEIVED.swap(false, Ordering::SeqCst) {
+                            reload_logger(&root_dir, &subcommand_name);
+                        }
+                        thread::sleep(POLL_INTERVAL);
+                    }
+                })
+                .expect("Start ConfigReloader failed!");
+
+            ReloadGuard {
+                stop,
+                handle: Some(handle),
+            }
+        }
+    }
+
+    impl Drop for ReloadGuard {
+        fn drop(&mut self) {
+            self.stop.store(true, Ordering::SeqCst);
+            if let Some(handle) = self.handle.take() {
+                let _ = handle.join();
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod noop {
+    use std::path::PathBuf;
+
+    /// There is no `SIGHUP` on this platform, the config is never reloaded.
+    pub struct ReloadGuard;
+
+    impl ReloadGuard {
+        pub fn spawn(_root_dir: PathBuf, _subcommand_name: String) -> ReloadGuard {
+            ReloadGuard
+        }
+    }
+}
@@ -46,7 +46,9 @@ pub fn calculate_maximum_withdraw(
     Ok(withdraw_capacity)
 }
 
-fn calculate_g2(
+/// The secondary issuance of the block, the remainder of the epoch reward is paid by the
+/// first block of the epoch
+pub fn calculate_g2(
     block_number: BlockNumber,
     current_epoch_ext: &EpochExt,
     secondary_epoch_reward: Capacity,
@@ -17,7 +17,7 @@ impl<CS: ChainStore> ChainIterator<CS> {
             .store()
             .get_block_hash(0)
             .and_then(|h| shared.store().get_block(&h));
-        let tip = shared.lock_chain_state().tip_number();
+        let tip = shared.tip_number();
         ChainIterator {
             shared,
             current,
@@ -1,5 +1,5 @@
 use crate::{Capacity, CellOutput, OutPoint, Script};
-use ckb_core::cell::CellStatus;
+use ckb_core::cell::{CellStatus, CellWithStatus as CoreCellWithStatus};
 use serde_derive::{Deserialize, Serialize};
 
 // This is used as return value of get_cells_by_type_hash RPC:
@@ -32,3 +32,13 @@ impl From<CellStatus> for CellWithStatus {
         }
     }
 }
+
+impl From<CoreCellWithStatus> for CellWithStatus {
+    fn from(cell_with_status: CoreCellWithStatus) -> Self {
+        let CoreCellWithStatus { cell, status } = cell_with_status;
+        Self {
+            cell: cell.map(Into::into),
+            ..status.into()
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{AlertMessage, EpochNumber, Timestamp};
+use crate::{AlertMessage, EpochNumber, Timestamp, Unsigned};
 use numext_fixed_uint::U256;
 use serde_derive::{Deserialize, Serialize};
 
@@ -14,6 +14,8 @@ pub struct ChainInfo {
     pub difficulty: U256,
     // estimate of whether this node is in InitialBlockDownload mode
     pub is_initial_block_download: bool,
+    // uncles per 1000 blocks over the recent blocks
+    pub uncle_rate: Unsigned,
     // any network and blockchain warnings
     pub alerts: Vec<AlertMessage>,
 }
@@ -5,7 +5,7 @@ use crossbeam_channel::unbounded;
 use env_logger::filter::{Builder, Filter};
 use lazy_static::lazy_static;
 use log::{LevelFilter, Log, Metadata, Record};
-use parking_lot::Mutex;
+use parking_lot::{Mutex, RwLock};
 use regex::Regex;
 use serde_derive::{Deserialize, Serialize};
 use std::io::Write;
@@ -115,20 +115,30 @@ enum Message {
 pub struct Logger {
     sender: crossbeam_channel::Sender<Message>,
     handle: Mutex<Option<thread::JoinHandle<()>>>,
-    filter: Filter,
 }
 
-impl Logger {
-    fn new(config: Config) -> Logger {
-        let mut builder = Builder::new();
+lazy_static! {
+    // Kept outside of `Logger` so that it can be replaced after the logger has been installed.
+    static ref FILTER: RwLock<Filter> = RwLock::new(build_filter(None));
+}
 
-        if let Ok(ref env_filter) = std::env::var("NERVOS_LOG") {
-            builder.parse(env_filter);
-        }
+fn build_filter(config_filter: Option<&str>) -> Filter {
+    let mut builder = Builder::new();
 
-        if let Some(ref config_filter) = config.filter {
-            builder.parse(config_filter);
-        }
+    if let Ok(ref env_filter) = std::env::var("NERVOS_LOG") {
+        builder.parse(env_filter);
+    }
+
+    if let Some(config_filter) = config_filter {
+        builder.parse(config_filter);
+    }
+
+    builder.build()
+}
+
+impl Logger {
+    fn new(config: Config) -> Logger {
+        *FILTER.write() = build_filter(config.filter.as_ref().map(String::as_str));
 
         let (sender, receiver) = unbounded();
         let Config {
@@ -177,12 +187,11 @@ impl Logger {
         Logger {
             sender,
             handle: Mutex::new(Some(tb)),
-            filter: builder.build(),
         }
     }
 
     pub fn filter(&self) -> LevelFilter {
-        self.filter.filter()
+        FILTER.read().filter()
     }
 }
 
@@ -209,12 +218,12 @@ impl Default for Config {
 
 impl Log for Logger {
     fn enabled(&self, metadata: &Metadata) -> bool {
-        self.filter.enabled(metadata)
+        FILTER.read().enabled(metadata)
     }
 
     fn log(&self, record: &Record) {
         // Check if the record is matched by the filter
-        if self.filter.matches(record) {
+        if FILTER.read().matches(record) {
             let thread = thread::current();
             let thread_name = thread.name().unwrap_or_default();
 
@@ -267,6 +276,15 @@ pub fn init(config: Config) -> Result<LoggerInitGuard, SetLoggerError> {
     log::set_boxed_logger(Box::new(logger)).map(|_| LoggerInitGuard)
 }
 
+/// Replace the filter of the installed logger, e.g., after the config file has been changed.
+///
+/// The directives in `NERVOS_LOG` still take effect, the same as in `init`.
+pub fn set_level(filter: Option<&str>) {
+    let filter = build_filter(filter);
+    log::set_max_level(filter.filter());
+    *FILTER.write() = filter;
+}
+
 pub fn flush() {
     log::logger().flush()
 }
@@ -35,3 +35,86 @@ pub fn build_merkle_tree(leaves: Vec<H256>) -> MerkleTree {
 pub fn build_merkle_proof(leaves: &[H256], indices: &[usize]) -> Option<MerkleProof> {
     CBMT::build_merkle_proof(leaves, indices)
 }
+
+/// Return the siblings on the path from the leaf at `index` up to the root, or `None` if
+/// `index` is out of bounds
+pub fn build_merkle_path(leaves: &[H256], index: usize) -> Option<Vec<H256>> {
+    let len = leaves.len();
+    if index >= len {
+        return None;
+    }
+    // The nodes of the complete binary tree, the children of `i` are `2i + 1` and `2i + 2`
+    // and the leaves come last.
+    let mut nodes = vec![H256::zero(); len - 1];
+    nodes.extend_from_slice(leaves);
+    for i in (0..len - 1).rev() {
+        nodes[i] = MergeH256::merge(&nodes[2 * i + 1], &nodes[2 * i + 2]);
+    }
+
+    let mut path = Vec::new();
+    let mut node = len - 1 + index;
+    while node > 0 {
+        path.push(nodes[sibling(node)].clone());
+        node = (node - 1) / 2;
+    }
+    Some(path)
+}
+
+/// Compute the root of a tree of `len` leaves from the leaf at `index` and its path built
+/// by `build_merkle_path`, or `None` if the path does not fit the tree
+pub fn merkle_root_from_path(leaf: &H256, index: usize, len: usize, path: &[H256]) -> Option<H256> {
+    if index >= len {
+        return None;
+    }
+    let mut hash = leaf.clone();
+    let mut siblings = path.iter();
+    let mut node = len - 1 + index;
+    while node > 0 {
+        let sibling = siblings.next()?;
+        hash = if node % 2 == 1 {
+            MergeH256::merge(&hash, sibling)
+        } else {
+            MergeH256::merge(sibling, &hash)
+        };
+        node = (node - 1) / 2;
+    }
+    if siblings.next().is_some() {
+        return None;
+    }
+    Some(hash)
+}
+
+fn sibling(node: usize) -> usize {
+    if node % 2 == 1 {
+        node + 1
+    } else {
+        node - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merkle_path() {
+        for len in 1..=9u8 {
+            let leaves: Vec<H256> = (0..len).map(|i| H256::from([i; 32])).collect();
+            let root = merkle_root(&leaves);
+            for (index, leaf) in leaves.iter().enumerate() {
+                let path = build_merkle_path(&leaves, index).unwrap();
+                let len = leaves.len();
+                assert_eq!(
+                    merkle_root_from_path(leaf, index, len, &path),
+                    Some(root.clone())
+                );
+                assert_ne!(
+                    merkle_root_from_path(&H256::zero(), index, len, &path),
+                    Some(root.clone())
+                );
+                assert_eq!(merkle_root_from_path(leaf, len, len, &path), None);
+            }
+            assert_eq!(build_merkle_path(&leaves, leaves.len()), None);
+        }
+    }
+}
@@ -8,7 +8,7 @@ use crate::OccupiedCapacity;
 )]
 pub struct Capacity(u64);
 
-#[derive(Clone, PartialEq, Debug, Eq, Copy)]
+#[derive(Clone, PartialEq, Debug, Eq, Copy, Serialize, Deserialize)]
 pub struct Ratio(pub u64, pub u64);
 
 impl Ratio {
@@ -5,7 +5,7 @@ use ckb_core::script::Script;
 use ckb_core::transaction::ProposalShortId;
 use ckb_core::Capacity;
 use ckb_store::ChainStore;
-use ckb_traits::ChainProvider;
+use ckb_traits::{BlockRewardComponents, ChainProvider};
 use failure::{Error as FailureError, Fail};
 use fnv::FnvHashSet;
 use numext_fixed_hash::H256;
@@ -29,37 +29,66 @@ impl<'a, P: ChainProvider> RewardCalculator<'a, P> {
     }
 
     /// `RewardCalculator` is used to calculate block finalize target's reward according to the parent header.
-    /// block reward consists of three parts: base block reward, tx fee, proposal reward.
+    /// block reward consists of four parts: base block reward, tx fee, proposal reward, uncles bonus.
+    /// The sum is capped by `max_block_reward` of consensus.
     pub fn block_reward(&self, parent: &Header) -> Result<(Script, Capacity), FailureError> {
-        let consensus = self.provider.consensus();
-        let store = self.provider.store();
-
-        let block_number = parent.number() + 1;
-        let target_number = consensus
-            .finalize_target(block_number)
-            .ok_or_else(|| Error::Target(block_number))?;
-
-        let target = self
-            .provider
-            .get_ancestor(parent.hash(), target_number)
-            .ok_or_else(|| Error::Target(block_number))?;
-
+        let target = self.finalize_target(parent)?;
         let target_lock = Script::from_witness(
-            &store
+            &self
+                .provider
+                .store()
                 .get_cellbase(target.hash())
                 .expect("target cellbase exist")
                 .witnesses()[0],
         )
         .ok_or_else(|| Error::Script(target.hash().to_owned()))?;
 
-        let txs_fees = self.txs_fees(&target)?;
-        let proposal_reward = self.proposal_reward(parent, &target)?;
-        let base_block_reward = self.base_block_reward(&target)?;
+        let components = self.reward_components(parent, &target)?;
+        let reward = components
+            .txs_fees
+            .safe_add(components.proposals)?
+            .safe_add(components.base)?
+            .safe_add(components.uncles)?;
+        Ok((
+            target_lock,
+            cmp::min(reward, self.provider.consensus().max_block_reward()),
+        ))
+    }
 
-        let reward = txs_fees
-            .safe_add(proposal_reward)?
-            .safe_add(base_block_reward)?;
-        Ok((target_lock, reward))
+    /// The four parts of `block_reward`, before the cap of `max_block_reward`
+    pub fn block_reward_components(
+        &self,
+        parent: &Header,
+    ) -> Result<BlockRewardComponents, FailureError> {
+        let target = self.finalize_target(parent)?;
+        self.reward_components(parent, &target)
+    }
+
+    fn finalize_target(&self, parent: &Header) -> Result<Header, FailureError> {
+        let block_number = parent.number() + 1;
+        let target_number = self
+            .provider
+            .consensus()
+            .finalize_target(block_number)
+            .ok_or_else(|| Error::Target(block_number))?;
+
+        self.provider
+            .get_ancestor(parent.hash(), target_number)
+            .ok_or_else(|| Error::Target(block_number).into())
+    }
+
+    fn reward_components(
+        &self,
+        parent: &Header,
+        target: &Header,
+    ) -> Result<BlockRewardComponents, FailureError> {
+        let base = self.base_block_reward(target)?;
+        Ok(BlockRewardComponents {
+            base,
+            proposals: self.proposal_reward(parent, target)?,
+            uncles: self.uncles_bonus(target, base)?,
+            txs_fees: self.txs_fees(target)?,
+        })
     }
 
     /// Miner get 60% of tx fee for tx commitment.
@@ -196,6 +225,24 @@ impl<'a, P: ChainProvider> RewardCalculator<'a, P> {
         epoch.block_reward(target.number()).map_err(Into::into)
     }
 
+    /// Miner get `uncle_inclusion_bonus_ratio` of base block reward for each uncle included in target.
+    pub fn uncles_bonus(
+        &self,
+        target: &Header,
+        base_block_reward: Capacity,
+    ) -> Result<Capacity, FailureError> {
+        let uncles_count = self
+            .provider
+            .store()
+            .get_block_uncles(target.hash())
+            .map_or(0, |uncles| uncles.len() as u64);
+
+        base_block_reward
+            .safe_mul_ratio(self.provider.consensus().uncle_inclusion_bonus_ratio())?
+            .safe_mul(uncles_count)
+            .map_err(Into::into)
+    }
+
     fn get_proposal_ids_by_hash(&self, hash: &H256) -> FnvHashSet<ProposalShortId> {
         let mut ids_set = FnvHashSet::default();
         if let Some(ids) = self.provider.store().get_block_proposal_txs_ids(&hash) {
@@ -139,9 +139,9 @@ impl<'a, CP: ChainProvider + Clone> CommitVerifier<'a, CP> {
             return Ok(());
         }
         let block_number = self.block.header().number();
-        let proposal_window = self.provider.consensus().tx_proposal_window();
-        let proposal_start = block_number.saturating_sub(proposal_window.farthest());
-        let mut proposal_end = block_number.saturating_sub(proposal_window.closest());
+        let (closest, farthest) = self.provider.get_proposal_window();
+        let proposal_start = block_number.saturating_sub(farthest);
+        let mut proposal_end = block_number.saturating_sub(closest);
 
         let mut block_hash = self
             .provider
@@ -195,7 +195,11 @@ impl<'a, CP: ChainProvider + Clone> CommitVerifier<'a, CP> {
                 self.block.header().number(),
                 self.block.header().hash()
             );
-            error_target!(crate::LOG_TARGET, "proposal_window {:?}", proposal_window);
+            error_target!(
+                crate::LOG_TARGET,
+                "proposal_window {:?}",
+                (closest, farthest)
+            );
             error_target!(
                 crate::LOG_TARGET,
                 "committed_ids {} ",
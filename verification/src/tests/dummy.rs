@@ -1,5 +1,5 @@
 use ckb_chain_spec::consensus::Consensus;
-use ckb_core::cell::{CellProvider, CellStatus};
+use ckb_core::cell::{CellProvider, CellStatus, CellWithStatus};
 use ckb_core::extras::EpochExt;
 use ckb_core::header::{BlockNumber, Header};
 use ckb_core::script::Script;
@@ -8,7 +8,7 @@ use ckb_core::Capacity;
 use ckb_db::MemoryKeyValueDB;
 use ckb_script::ScriptConfig;
 use ckb_store::ChainKVStore;
-use ckb_traits::ChainProvider;
+use ckb_traits::{BlockRewardComponents, ChainProvider};
 use failure::Error as FailureError;
 use numext_fixed_hash::H256;
 use std::sync::Arc;
@@ -50,6 +50,14 @@ impl ChainProvider for DummyChainProvider {
     fn finalize_block_reward(&self, _parent: &Header) -> Result<(Script, Capacity), FailureError> {
         unimplemented!();
     }
+
+    fn get_block_reward_components(&self, _hash: &H256) -> Option<BlockRewardComponents> {
+        unimplemented!();
+    }
+
+    fn get_live_cell_with_status(&self, _out_point: &OutPoint) -> CellWithStatus {
+        unimplemented!();
+    }
 }
 
 impl CellProvider for DummyChainProvider {